@@ -0,0 +1,44 @@
+use serde::{Deserialize, Serialize};
+
+/// Ambient temperature (°C) as a function of simulation time.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum AmbientProfile {
+    /// Fixed temperature.
+    Constant(f64),
+    /// `start + rate · t` (°C, °C/s).
+    Ramp { start: f64, rate: f64 },
+    /// Sinusoidal swing `mean + amplitude · sin(2π·t / period)`, e.g. a daily cycle.
+    Sinusoid { mean: f64, amplitude: f64, period: f64 },
+}
+
+impl AmbientProfile {
+    /// Ambient temperature at simulation time `time` (s).
+    pub fn at(&self, time: f64) -> f64 {
+        match *self {
+            AmbientProfile::Constant(temperature) => temperature,
+            AmbientProfile::Ramp { start, rate } => start + rate * time,
+            AmbientProfile::Sinusoid { mean, amplitude, period } => {
+                mean + amplitude * (2.0 * std::f64::consts::PI * time / period).sin()
+            }
+        }
+    }
+}
+
+impl Default for AmbientProfile {
+    fn default() -> Self {
+        AmbientProfile::Constant(25.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sinusoid_peaks_a_quarter_period_in() {
+        let daily = AmbientProfile::Sinusoid { mean: 20.0, amplitude: 8.0, period: 86_400.0 };
+        assert!((daily.at(0.0) - 20.0).abs() < 1e-12);
+        assert!((daily.at(21_600.0) - 28.0).abs() < 1e-9);
+        assert_eq!(AmbientProfile::default().at(1e6), 25.0);
+    }
+}
@@ -0,0 +1,42 @@
+/// Points of an SVG `<polyline>` drawing `values` across a `width` × `height` box.
+///
+/// Values are spread evenly along x and scaled so the min/max touch the
+/// bottom/top edges; a flat series is drawn through the middle.
+pub fn polyline_points(values: &[f64], width: f64, height: f64) -> String {
+    let (min, max) = value_range(values);
+    let span = max - min;
+    let step = if values.len() > 1 { width / (values.len() - 1) as f64 } else { 0.0 };
+    values
+        .iter()
+        .enumerate()
+        .map(|(i, &value)| {
+            let fraction = if span > 0.0 { (value - min) / span } else { 0.5 };
+            format!("{:.1},{:.1}", i as f64 * step, height * (1.0 - fraction))
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// (min, max) of the finite values; (0, 0) when there are none.
+pub fn value_range(values: &[f64]) -> (f64, f64) {
+    let finite = values.iter().copied().filter(|v| v.is_finite());
+    let (min, max) = finite.fold((f64::INFINITY, f64::NEG_INFINITY), |(lo, hi), v| (lo.min(v), hi.max(v)));
+    if min > max {
+        (0.0, 0.0)
+    } else {
+        (min, max)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_polyline_spans_the_box() {
+        assert_eq!(polyline_points(&[1.0, 3.0, 2.0], 100.0, 50.0), "0.0,50.0 50.0,0.0 100.0,25.0");
+        assert_eq!(polyline_points(&[4.0, 4.0], 10.0, 20.0), "0.0,10.0 10.0,10.0");
+        assert_eq!(polyline_points(&[], 10.0, 20.0), "");
+        assert_eq!(value_range(&[f64::INFINITY, 2.0, -1.0]), (-1.0, 2.0));
+    }
+}
@@ -0,0 +1,117 @@
+//! Run configuration read from TOML files.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::BmsError;
+use crate::influx::InfluxConfig;
+use crate::scenario::Scenario;
+
+/// Everything a run can be configured with: the plant, controller and timing
+/// parameters of the [`Scenario`], and where metrics are written.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SimulationConfig {
+    pub scenario: Scenario,
+    /// InfluxDB endpoint metrics are written to.
+    pub influx: InfluxConfig,
+}
+
+impl SimulationConfig {
+    /// The configuration `main_console` runs without a config file.
+    pub fn console() -> Self {
+        Self { scenario: Scenario::console(), ..Self::default() }
+    }
+
+    /// Load a TOML config file; an empty file yields the default configuration.
+    ///
+    /// The scenario is not validated; call [`Scenario::validate`] before running it.
+    pub fn from_path(path: &Path) -> Result<Self, BmsError> {
+        Self::from_path_over(path, &Self::default())
+    }
+
+    /// Load a TOML config file, taking every field it leaves out from `base`.
+    pub fn from_path_over(path: &Path, base: &Self) -> Result<Self, BmsError> {
+        let error = |message: String| BmsError::Config { path: path.display().to_string(), message };
+        let text = fs::read_to_string(path).map_err(|err| error(err.to_string()))?;
+        Self::from_toml_over(&text, base).map_err(error)
+    }
+
+    /// Parse TOML, taking every field it leaves out from `base`.
+    pub fn from_toml_over(text: &str, base: &Self) -> Result<Self, String> {
+        let overrides: toml::Table = toml::from_str(text).map_err(|err| err.to_string())?;
+        let mut merged = toml::Table::try_from(base).map_err(|err| err.to_string())?;
+        merge(&mut merged, overrides);
+        merged.try_into().map_err(|err: toml::de::Error| err.to_string())
+    }
+
+    /// Config path from command-line arguments (`--config path` or `--config=path`).
+    pub fn path_from_args<I: IntoIterator<Item = String>>(args: I) -> Result<Option<PathBuf>, BmsError> {
+        let mut args = args.into_iter();
+        let mut path = None;
+        while let Some(arg) = args.next() {
+            if arg == "--config" {
+                let value = args.next().ok_or(BmsError::InvalidArgument { argument: arg, reason: "missing value" })?;
+                path = Some(PathBuf::from(value));
+            } else if let Some(value) = arg.strip_prefix("--config=") {
+                path = Some(PathBuf::from(value));
+            }
+        }
+        Ok(path)
+    }
+}
+
+/// Overlay `overrides` onto `table`, recursing into tables present in both.
+fn merge(table: &mut toml::Table, overrides: toml::Table) {
+    for (key, value) in overrides {
+        match (table.get_mut(&key), value) {
+            (Some(toml::Value::Table(existing)), toml::Value::Table(nested)) => merge(existing, nested),
+            (_, value) => {
+                table.insert(key, value);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scenario::PidGains;
+
+    #[test]
+    fn test_config_file_overrides_only_given_fields() {
+        let path = std::env::temp_dir().join(format!("bms_config_{}.toml", std::process::id()));
+        fs::write(&path, "").unwrap();
+        assert_eq!(SimulationConfig::from_path(&path), Ok(SimulationConfig::default()));
+
+        fs::write(
+            &path,
+            "[scenario]\nduration = 10.0\noxygen_gains = { kp = 1.0, ki = 0.0, kd = 0.0 }\n\n[influx]\nbase_url = \"http://influx:8086\"\n",
+        )
+        .unwrap();
+        let config = SimulationConfig::from_path(&path).unwrap();
+        assert_eq!(config.scenario.duration, 10.0);
+        assert_eq!(config.scenario.oxygen_gains, PidGains::new(1.0, 0.0, 0.0));
+        assert_eq!(config.scenario.dt, Scenario::new().dt);
+        assert_eq!(config.influx.base_url, "http://influx:8086");
+        assert_eq!(config.influx.database, InfluxConfig::default().database);
+        fs::write(&path, "[scenario]\nduration = \"long\"\n").unwrap();
+        let error = SimulationConfig::from_path(&path).unwrap_err();
+        assert!(matches!(error, BmsError::Config { .. }));
+        assert!(error.to_string().starts_with("config `"));
+        let _ = fs::remove_file(&path);
+
+        let args = ["--format", "json", "--config=run.toml"].map(String::from);
+        assert_eq!(SimulationConfig::path_from_args(args), Ok(Some(PathBuf::from("run.toml"))));
+    }
+
+    #[test]
+    fn test_partial_config_keeps_the_base_preset() {
+        let console = SimulationConfig::console();
+        assert_eq!(SimulationConfig::from_toml_over("", &console), Ok(console.clone()));
+        let config = SimulationConfig::from_toml_over("[scenario]\nseed = 7\n", &console).unwrap();
+        assert_eq!(config.scenario, Scenario { seed: 7, ..console.scenario });
+    }
+}
@@ -0,0 +1,108 @@
+//! The `main_console` run, kept in the library so both front ends' paths can be tested.
+
+use std::path::PathBuf;
+
+use crate::config::SimulationConfig;
+use crate::engine::SimulationEngine;
+use crate::error::BmsError;
+use crate::influx::InfluxConfig;
+use crate::scenario::TimingWarning;
+use crate::supervisor::SystemState;
+use crate::telemetry::OutputFormat;
+
+pub const USAGE: &str = "usage: main_console [--format text|json] [--config config.toml]";
+
+/// Command-line options of `main_console`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConsoleOptions {
+    pub format: OutputFormat,
+    pub config: Option<PathBuf>,
+}
+
+impl ConsoleOptions {
+    pub fn from_args(args: Vec<String>) -> Result<Self, BmsError> {
+        Ok(Self { format: OutputFormat::from_args(args.clone())?, config: SimulationConfig::path_from_args(args)? })
+    }
+
+    /// Load and validate the configuration. A config file is laid over
+    /// [`SimulationConfig::console`], so an empty one runs the console's default loop.
+    pub fn start(&self) -> Result<ConsoleRun, Vec<BmsError>> {
+        let config = match &self.config {
+            Some(path) => SimulationConfig::from_path_over(path, &SimulationConfig::console()).map_err(|err| vec![err])?,
+            None => SimulationConfig::console(),
+        };
+        config.scenario.validate()?;
+        let warnings = config.scenario.timing_warnings();
+        let steps = config.scenario.steps();
+        let engine = SimulationEngine::new(config.scenario);
+        Ok(ConsoleRun {
+            format: self.format,
+            influx: config.influx,
+            warnings,
+            state: engine.supervisor.state,
+            engine,
+            steps,
+            step: 0,
+        })
+    }
+}
+
+/// A console run in progress.
+#[derive(Debug)]
+pub struct ConsoleRun {
+    pub format: OutputFormat,
+    pub engine: SimulationEngine,
+    /// Metrics endpoint from the config file.
+    pub influx: InfluxConfig,
+    /// Timing choices in the config worth reporting before the run.
+    pub warnings: Vec<TimingWarning>,
+    /// Steps in the whole run.
+    pub steps: usize,
+    state: SystemState,
+    step: usize,
+}
+
+impl ConsoleRun {
+    pub fn is_finished(&self) -> bool {
+        self.step >= self.steps
+    }
+
+    /// Advance one step and return the lines to print.
+    pub fn step(&mut self) -> Vec<String> {
+        let report = self.engine.step();
+        let mut lines = Vec::new();
+        // State-change notes are only printed in text mode so JSON output stays one object per line.
+        if self.format == OutputFormat::Text && self.engine.supervisor.state != self.state {
+            lines.push(format!("Step {}: Entering {} state", self.step, self.engine.supervisor.state));
+        }
+        self.state = self.engine.supervisor.state;
+        lines.push(self.format.format(self.step, &report));
+        self.step += 1;
+        lines
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_gui_and_console_agree_on_the_first_step_of_a_scenario() {
+        let options = ConsoleOptions::from_args(["--format", "json"].map(String::from).to_vec()).unwrap();
+        let mut console = options.start().unwrap();
+        assert_eq!(console.steps, 100);
+        let lines = console.step();
+        assert_eq!(lines.len(), 1, "JSON mode prints one object per step");
+
+        // The GUI model, given the console's scenario, shows the same first step.
+        let mut gui = crate::Model::new(SimulationConfig::console());
+        assert_eq!(OutputFormat::Json.format(0, &gui.advance()), lines[0]);
+    }
+
+    #[test]
+    fn test_first_text_step_reports_no_state_change() {
+        let mut console = ConsoleOptions::from_args(Vec::new()).unwrap().start().unwrap();
+        let lines = console.step();
+        assert!(!lines.iter().any(|line| line.contains("Entering")), "{:?}", lines);
+    }
+}
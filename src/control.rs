@@ -1,104 +1,481 @@
+use serde::{Deserialize, Serialize};
+
+use crate::error::BmsError;
+use crate::simulation::contactor::Contactor;
+use crate::simulation::array::FuelCellArray;
+use crate::simulation::Battery;
+
+/// Discrete-time approximation used for the PID integral term.
+///
+/// - `BackwardEuler` (default): `I[k] = I[k-1] + e[k]·dt`. This is the usual
+///   `integral += error * dt` loop found in hand-written firmware.
+/// - `ForwardEuler`: `I[k] = I[k-1] + e[k-1]·dt`. Lags one sample; matches
+///   firmware that updates the integrator after computing the output.
+/// - `Tustin`: `I[k] = I[k-1] + (e[k] + e[k-1])/2·dt`. Trapezoidal rule, as
+///   produced by `c2d(..., 'tustin')` and most auto-code generators.
+///
+/// The derivative always uses the backward difference `(e[k] - e[k-1]) / dt`:
+/// a forward-Euler derivative needs the future error, and an unfiltered Tustin
+/// differentiator has a pole at z = -1 that rings every sample.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum IntegrationMethod {
+    ForwardEuler,
+    #[default]
+    BackwardEuler,
+    Tustin,
+}
+
+/// Piecewise-linear gain schedule: (kp, ki, kd) registered at breakpoints of a
+/// scheduling variable and interpolated linearly between them.
+///
+/// Outside the first and last breakpoint the nearest gains are held.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct GainSchedule {
+    /// (point, (kp, ki, kd)), kept sorted by point.
+    breakpoints: Vec<(f64, (f64, f64, f64))>,
+}
+
+impl GainSchedule {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register gains at `point`, replacing any gains already there.
+    pub fn add(&mut self, point: f64, kp: f64, ki: f64, kd: f64) -> &mut Self {
+        let index = self.breakpoints.partition_point(|&(p, _)| p < point);
+        match self.breakpoints.get_mut(index) {
+            Some(entry) if entry.0 == point => entry.1 = (kp, ki, kd),
+            _ => self.breakpoints.insert(index, (point, (kp, ki, kd))),
+        }
+        self
+    }
+
+    pub fn len(&self) -> usize {
+        self.breakpoints.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.breakpoints.is_empty()
+    }
+
+    /// Interpolated (kp, ki, kd) at `point`; `None` for an empty schedule.
+    pub fn gains_at(&self, point: f64) -> Option<(f64, f64, f64)> {
+        let first = self.breakpoints.first()?;
+        let last = self.breakpoints.last()?;
+        if point <= first.0 {
+            return Some(first.1);
+        }
+        if point >= last.0 {
+            return Some(last.1);
+        }
+        let upper = self.breakpoints.partition_point(|&(p, _)| p <= point);
+        let (x0, (kp0, ki0, kd0)) = self.breakpoints[upper - 1];
+        let (x1, (kp1, ki1, kd1)) = self.breakpoints[upper];
+        let t = (point - x0) / (x1 - x0);
+        let lerp = |a: f64, b: f64| a + t * (b - a);
+        Some((lerp(kp0, kp1), lerp(ki0, ki1), lerp(kd0, kd1)))
+    }
+}
+
 /// Basic PID Controller with adaptive gain scheduling capability.
+///
+/// The step size is passed on every call rather than fixed at construction,
+/// so the controller stays correct when `dt` changes between steps.
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PidController {
     kp: f64,
     ki: f64,
     kd: f64,
     last_error: f64,
     integral: f64,
-    dt: f64,
+    method: IntegrationMethod,
+    /// Integral clamp applied after every update (anti-windup).
+    #[serde(with = "crate::snapshot::non_finite")]
+    integral_min: f64,
+    #[serde(with = "crate::snapshot::non_finite")]
+    integral_max: f64,
+    /// Conditional integration: while the raw output is outside this band,
+    /// updates that would push it further out are skipped.
+    conditional_band: Option<(f64, f64)>,
+    /// Output saturation applied by `compute` and `compute_adaptive`.
+    output_min: Option<f64>,
+    output_max: Option<f64>,
+    /// Gain schedule consulted on every update; `None` keeps the fixed gains.
+    schedule: Option<GainSchedule>,
+    /// (P, I, D) contributions from the most recent update, before saturation.
+    last_terms: (f64, f64, f64),
 }
 
 impl PidController {
-    /// Creates a new PID controller with gains and dt
-    pub fn new(kp: f64, ki: f64, kd: f64, dt: f64) -> Self {
+    /// Creates a new PID controller with the given gains.
+    pub fn new(kp: f64, ki: f64, kd: f64) -> Self {
+        Self::with_method(kp, ki, kd, IntegrationMethod::default())
+    }
+
+    /// Creates a PID controller using the given integration method.
+    pub fn with_method(kp: f64, ki: f64, kd: f64, method: IntegrationMethod) -> Self {
         Self {
             kp,
             ki,
             kd,
             last_error: 0.0,
             integral: 0.0,
-            dt,
+            method,
+            integral_min: f64::NEG_INFINITY,
+            integral_max: f64::INFINITY,
+            conditional_band: None,
+            output_min: None,
+            output_max: None,
+            schedule: None,
+            last_terms: (0.0, 0.0, 0.0),
         }
     }
 
-    /// Compute control signal based on setpoint vs measured using fixed gains.
-    pub fn compute(&mut self, setpoint: f64, measured: f64) -> f64 {
+    /// Creates a PID controller whose gains follow `schedule`.
+    ///
+    /// `compute` schedules on the error magnitude; `compute_scheduled` takes an
+    /// explicit operating point (e.g. load current) instead.
+    pub fn with_schedule(schedule: GainSchedule) -> Self {
+        let (kp, ki, kd) = schedule.gains_at(0.0).unwrap_or((0.0, 0.0, 0.0));
+        Self { schedule: Some(schedule), ..Self::new(kp, ki, kd) }
+    }
+
+    /// Current (kp, ki, kd), after any scheduling on the last update.
+    pub fn gains(&self) -> (f64, f64, f64) {
+        (self.kp, self.ki, self.kd)
+    }
+
+    /// Change the gains mid-run without a bump in the output.
+    ///
+    /// The integral is rescaled so the integral contribution `ki·∫e` carries
+    /// over unchanged (and cleared when `ki` drops to 0), then clamped to the
+    /// integral limits so a smaller `ki` cannot leave a wound-up state.
+    pub fn set_gains(&mut self, kp: f64, ki: f64, kd: f64) {
+        self.integral = if ki == 0.0 {
+            0.0
+        } else {
+            (self.ki * self.integral / ki).clamp(self.integral_min, self.integral_max)
+        };
+        self.kp = kp;
+        self.ki = ki;
+        self.kd = kd;
+    }
+
+    /// Creates a PID controller whose output is clamped to `[min, max]`.
+    ///
+    /// The integrator does not wind further while the output is pinned at a limit.
+    pub fn with_limits(kp: f64, ki: f64, kd: f64, min: f64, max: f64) -> Self {
+        Self {
+            output_min: Some(min),
+            output_max: Some(max),
+            ..Self::new(kp, ki, kd)
+        }
+    }
+
+    /// Clamp the integral state to `[min, max]` (unbounded by default).
+    pub fn set_integral_limits(&mut self, min: f64, max: f64) {
+        self.integral_min = min;
+        self.integral_max = max;
+        self.integral = self.integral.clamp(min, max);
+    }
+
+    /// Stop integrating while the raw output is beyond `[min, max]` and the
+    /// error would drive it further out. `None` always integrates.
+    pub fn set_conditional_integration(&mut self, band: Option<(f64, f64)>) {
+        self.conditional_band = band;
+    }
+
+    pub fn integral(&self) -> f64 {
+        self.integral
+    }
+
+    /// Error seen by the most recent update.
+    pub fn last_error(&self) -> f64 {
+        self.last_error
+    }
+
+    /// (P, I, D) contributions to the most recent output, before saturation.
+    pub fn last_terms(&self) -> (f64, f64, f64) {
+        self.last_terms
+    }
+
+    /// Clear the integral and derivative history, e.g. on a regime change.
+    pub fn reset(&mut self) {
+        self.reset_to(0.0, 0.0);
+    }
+
+    /// Seed the controller state for bumpless transfer into a known steady state.
+    pub fn reset_to(&mut self, integral: f64, last_error: f64) {
+        self.integral = integral.clamp(self.integral_min, self.integral_max);
+        self.last_error = last_error;
+        self.last_terms = (0.0, 0.0, 0.0);
+    }
+
+    /// Compute control signal based on setpoint vs measured using fixed gains,
+    /// `dt` seconds after the previous call.
+    pub fn compute(&mut self, setpoint: f64, measured: f64, dt: f64) -> f64 {
+        self.schedule_gains((setpoint - measured).abs());
+        let output = self.compute_unclamped(setpoint, measured, 1.0, dt);
+        self.saturate(output)
+    }
+
+    /// Like `compute`, but schedules the gains on `operating_point` rather
+    /// than on the error magnitude.
+    pub fn compute_scheduled(&mut self, setpoint: f64, measured: f64, operating_point: f64, dt: f64) -> f64 {
+        self.schedule_gains(operating_point);
+        let output = self.compute_unclamped(setpoint, measured, 1.0, dt);
+        self.saturate(output)
+    }
+
+    fn schedule_gains(&mut self, point: f64) {
+        if let Some((kp, ki, kd)) = self.schedule.as_ref().and_then(|schedule| schedule.gains_at(point)) {
+            self.kp = kp;
+            self.ki = ki;
+            self.kd = kd;
+        }
+    }
+
+    fn saturate(&self, output: f64) -> f64 {
+        let output = self.output_min.map_or(output, |min| output.max(min));
+        self.output_max.map_or(output, |max| output.min(max))
+    }
+
+    /// The PID output scaled by `factor`. The anti-windup check and the stored
+    /// terms use the scaled values, so the integrator stops once the output it
+    /// actually produces saturates.
+    fn compute_unclamped(&mut self, setpoint: f64, measured: f64, factor: f64, dt: f64) -> f64 {
         let error = setpoint - measured;
-        self.integral += error * self.dt;
-        let derivative = (error - self.last_error) / self.dt;
+        let increment = match self.method {
+            IntegrationMethod::ForwardEuler => self.last_error * dt,
+            IntegrationMethod::BackwardEuler => error * dt,
+            IntegrationMethod::Tustin => 0.5 * (error + self.last_error) * dt,
+        };
+        // A zero-length step has no defined derivative; skip the term.
+        let derivative = if dt > 0.0 { (error - self.last_error) / dt } else { 0.0 };
         self.last_error = error;
-        self.kp * error + self.ki * self.integral + self.kd * derivative
+        let (p_term, d_term) = (factor * self.kp * error, factor * self.kd * derivative);
+        let without_integral = p_term + d_term;
+        let candidate = self.integral + increment;
+        let raw = without_integral + factor * self.ki * candidate;
+        // Output limits imply conditional integration at the limits unless a band is set explicitly.
+        let band = self.conditional_band.or(match (self.output_min, self.output_max) {
+            (None, None) => None,
+            (min, max) => Some((min.unwrap_or(f64::NEG_INFINITY), max.unwrap_or(f64::INFINITY))),
+        });
+        let winding_out = match band {
+            Some((min, max)) => (raw > max && self.ki * increment > 0.0) || (raw < min && self.ki * increment < 0.0),
+            None => false,
+        };
+        if !winding_out {
+            self.integral = candidate.clamp(self.integral_min, self.integral_max);
+        }
+        let i_term = factor * self.ki * self.integral;
+        self.last_terms = (p_term, i_term, d_term);
+        without_integral + i_term
     }
 
     /// Compute control signal using adaptive gain scheduling.
     /// This method increases the proportional gain when the error magnitude is high.
-    pub fn compute_adaptive(&mut self, setpoint: f64, measured: f64) -> f64 {
+    pub fn compute_adaptive(&mut self, setpoint: f64, measured: f64, dt: f64) -> f64 {
         let error = setpoint - measured;
         let factor = if error.abs() > 1.0 { 1.5 } else { 1.0 };
-        factor * self.compute(setpoint, measured)
+        self.schedule_gains(error.abs());
+        let output = self.compute_unclamped(setpoint, measured, factor, dt);
+        self.saturate(output)
+    }
+
+    /// Classic Ziegler–Nichols PID gains from the ultimate gain `ku` and period `tu` (s).
+    pub fn ziegler_nichols(ku: f64, tu: f64) -> Self {
+        Self::new(0.6 * ku, 1.2 * ku / tu, 0.075 * ku * tu)
+    }
+
+    /// Relay-feedback (Åström–Hägglund) autotune.
+    ///
+    /// `plant` applies one input for `dt` seconds and returns the new output.
+    /// The relay drives it with ±`amplitude` whenever the output crosses
+    /// `setpoint`; once the limit cycle settles, `ku = 4·amplitude / (π·a)` with
+    /// `a` the oscillation half-amplitude, and `tu` is the cycle period. The
+    /// relay is centred on zero input, so `setpoint` should be the output the
+    /// plant settles at with zero input.
+    pub fn autotune(
+        mut plant: impl FnMut(f64) -> f64,
+        setpoint: f64,
+        amplitude: f64,
+        dt: f64,
+        max_steps: usize,
+    ) -> Result<Autotune, BmsError> {
+        // Cycles discarded while the transient dies out, then cycles averaged.
+        const WARMUP_CYCLES: usize = 2;
+        const MEASURED_CYCLES: usize = 3;
+
+        let mut relay = amplitude;
+        let mut switch_times = Vec::new();
+        let mut half_amplitudes = Vec::new();
+        let (mut high, mut low) = (f64::NEG_INFINITY, f64::INFINITY);
+        for step in 0..max_steps {
+            let measured = plant(relay);
+            high = high.max(measured);
+            low = low.min(measured);
+            let next = if measured < setpoint {
+                amplitude
+            } else if measured > setpoint {
+                -amplitude
+            } else {
+                relay
+            };
+            if next > relay {
+                // One full cycle ends each time the relay switches back up.
+                switch_times.push((step + 1) as f64 * dt);
+                half_amplitudes.push(0.5 * (high - low));
+                high = f64::NEG_INFINITY;
+                low = f64::INFINITY;
+                if switch_times.len() > WARMUP_CYCLES + MEASURED_CYCLES {
+                    let first = switch_times.len() - 1 - MEASURED_CYCLES;
+                    let tu = (switch_times[switch_times.len() - 1] - switch_times[first]) / MEASURED_CYCLES as f64;
+                    let a = half_amplitudes[first + 1..].iter().sum::<f64>() / MEASURED_CYCLES as f64;
+                    if a <= 0.0 {
+                        break;
+                    }
+                    let ku = 4.0 * amplitude / (std::f64::consts::PI * a);
+                    return Ok(Autotune { ku, tu, controller: Self::ziegler_nichols(ku, tu) });
+                }
+            }
+            relay = next;
+        }
+        Err(BmsError::AutotuneFailed { steps: max_steps })
     }
 }
 
+/// Result of a relay autotune: the detected ultimate gain and period, and the
+/// Ziegler–Nichols controller derived from them.
+#[derive(Debug, Clone)]
+pub struct Autotune {
+    pub ku: f64,
+    /// Ultimate period (s).
+    pub tu: f64,
+    pub controller: PidController,
+}
+
 /// High-level oxygen regulator built on top of PID.
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct OxygenController {
     pid: PidController,
 }
 
 impl OxygenController {
-    pub fn new(kp: f64, ki: f64, kd: f64, dt: f64) -> Self {
+    pub fn new(kp: f64, ki: f64, kd: f64) -> Self {
         Self {
-            pid: PidController::new(kp, ki, kd, dt),
+            pid: PidController::new(kp, ki, kd),
         }
     }
 
     /// Regulate using fixed PID control.
-    pub fn regulate(&mut self, desired: f64, measured: f64) -> f64 {
-        self.pid.compute(desired, measured)
+    pub fn regulate(&mut self, desired: f64, measured: f64, dt: f64) -> f64 {
+        self.pid.compute(desired, measured, dt)
     }
 
     /// Regulate using adaptive PID control.
-    pub fn regulate_adaptive(&mut self, desired: f64, measured: f64) -> f64 {
-        self.pid.compute_adaptive(desired, measured)
+    pub fn regulate_adaptive(&mut self, desired: f64, measured: f64, dt: f64) -> f64 {
+        self.pid.compute_adaptive(desired, measured, dt)
+    }
+
+    /// Drop accumulated PID state (see [`PidController::reset`]).
+    pub fn reset(&mut self) {
+        self.pid.reset();
+    }
+
+    /// The underlying PID, for inspecting its state.
+    pub fn pid(&self) -> &PidController {
+        &self.pid
+    }
+
+    /// Retune live (see [`PidController::set_gains`]).
+    pub fn set_gains(&mut self, kp: f64, ki: f64, kd: f64) {
+        self.pid.set_gains(kp, ki, kd);
     }
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AirSupplyController {
     pid: PidController,
     /// Desired oxygen concentration setpoint (for example, 0.21 for ambient air).
     desired_oxygen: f64,
+    /// Oxygen excess ratio setpoint (e.g. 2.0); when set, `compute_motor_torque_for_excess_ratio` regulates λ.
+    excess_ratio_setpoint: Option<f64>,
 }
 
 impl AirSupplyController {
-    pub fn new(kp: f64, ki: f64, kd: f64, dt: f64, desired_oxygen: f64) -> Self {
+    pub fn new(kp: f64, ki: f64, kd: f64, desired_oxygen: f64) -> Self {
         Self {
-            pid: PidController::new(kp, ki, kd, dt),
+            pid: PidController::new(kp, ki, kd),
             desired_oxygen,
+            excess_ratio_setpoint: None,
         }
     }
+
+    /// Controller that regulates the oxygen excess ratio λ to `excess_ratio` instead of concentration.
+    pub fn with_excess_ratio(kp: f64, ki: f64, kd: f64, desired_oxygen: f64, excess_ratio: f64) -> Self {
+        Self { excess_ratio_setpoint: Some(excess_ratio), ..Self::new(kp, ki, kd, desired_oxygen) }
+    }
+
+    pub fn excess_ratio_setpoint(&self) -> Option<f64> {
+        self.excess_ratio_setpoint
+    }
+
+    /// The underlying PID, for inspecting its state.
+    pub fn pid(&self) -> &PidController {
+        &self.pid
+    }
+
+    /// Retune live (see [`PidController::set_gains`]).
+    pub fn set_gains(&mut self, kp: f64, ki: f64, kd: f64) {
+        self.pid.set_gains(kp, ki, kd);
+    }
     
     /// Compute the compressor motor torque command.
     ///
     /// A feedforward term (here, a placeholder value) is combined with a PID correction.
-    pub fn compute_motor_torque(&mut self, measured_oxygen: f64) -> f64 {
+    pub fn compute_motor_torque(&mut self, measured_oxygen: f64, dt: f64) -> f64 {
         let feedforward = 10.0; // Replace with a value derived from your compressor map if available.
-        let correction = self.pid.compute(self.desired_oxygen, measured_oxygen);
+        let correction = self.pid.compute(self.desired_oxygen, measured_oxygen, dt);
         feedforward + correction
     }
+
+    /// Compute the torque command regulating the measured excess ratio λ.
+    ///
+    /// λ is infinite with no load; it is capped so the PID sees a finite error.
+    /// The placeholder feedforward belongs to the concentration loop and is not
+    /// applied here. Falls back to that loop when no λ setpoint is configured.
+    pub fn compute_motor_torque_for_excess_ratio(&mut self, measured_excess_ratio: f64, measured_oxygen: f64, dt: f64) -> f64 {
+        let Some(setpoint) = self.excess_ratio_setpoint else {
+            return self.compute_motor_torque(measured_oxygen, dt);
+        };
+        let measured = measured_excess_ratio.min(EXCESS_RATIO_CAP * setpoint);
+        self.pid.compute(setpoint, measured, dt)
+    }
 }
 
+/// Multiple of the λ setpoint at which the measured excess ratio is capped.
+const EXCESS_RATIO_CAP: f64 = 10.0;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct BatteryController {
     lower_threshold: f64,
     upper_threshold: f64,
+    /// Current (A) commanded while charging.
+    pub charging_current: f64,
     // Tracks the current mode: true if charging, false if discharging.
     pub charging_mode: bool,
 }
 
 impl BatteryController {
-    /// Create a new BatteryController with given lower and upper thresholds.
-    pub fn new(lower_threshold: f64, upper_threshold: f64) -> Self {
+    /// Create a new BatteryController with given lower and upper thresholds
+    /// and charging current.
+    pub fn new(lower_threshold: f64, upper_threshold: f64, charging_current: f64) -> Self {
         Self {
             lower_threshold,
             upper_threshold,
+            charging_current,
             charging_mode: false, // default mode: not charging
         }
     }
@@ -120,6 +497,338 @@ impl BatteryController {
         }
         self.charging_mode
     }
+
+    /// Charge current to apply: the charging current in charging mode, 0 otherwise.
+    pub fn charge_command(&self, charging_mode: bool) -> f64 {
+        if charging_mode {
+            self.charging_current
+        } else {
+            0.0
+        }
+    }
+}
+
+/// Temperature the on/off cooling controller regulates.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum CoolingTarget {
+    /// Stack body temperature (no separate coolant node).
+    #[default]
+    FuelCell,
+    /// Coolant outlet temperature; adds a coolant node between stack and radiator.
+    Coolant,
+}
+
+/// Phase of a CC-CV charge.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ChargePhase {
+    /// Fixed current until the terminal voltage reaches the limit.
+    ConstantCurrent,
+    /// Terminal voltage held at the limit while the current tapers.
+    ConstantVoltage,
+    /// Current tapered below the termination threshold; charging stopped.
+    Complete,
+}
+
+/// Constant-current / constant-voltage charger.
+///
+/// Charges at `cc_current` until the terminal voltage `OCV + I·R` would exceed
+/// `voltage_limit`, then supplies the current that holds the terminal voltage
+/// at the limit. The charge ends once that current falls to `termination_current`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CcCvCharger {
+    /// Constant-current phase current (A).
+    pub cc_current: f64,
+    /// Terminal voltage held during the CV phase (V).
+    pub voltage_limit: f64,
+    /// Current (A) at which the CV phase terminates.
+    pub termination_current: f64,
+    pub phase: ChargePhase,
+}
+
+impl CcCvCharger {
+    pub fn new(cc_current: f64, voltage_limit: f64, termination_current: f64) -> Self {
+        Self {
+            cc_current,
+            voltage_limit,
+            termination_current,
+            phase: ChargePhase::ConstantCurrent,
+        }
+    }
+
+    /// Start a new charge cycle from the CC phase.
+    pub fn reset(&mut self) {
+        self.phase = ChargePhase::ConstantCurrent;
+    }
+
+    /// Charge current (A) to apply to `battery` this step.
+    pub fn charge_current(&mut self, battery: &Battery) -> f64 {
+        let ocv = Battery::ocv_at(battery.soc);
+        if self.phase == ChargePhase::ConstantCurrent {
+            if ocv + self.cc_current * battery.effective_resistance() < self.voltage_limit {
+                return self.cc_current;
+            }
+            self.phase = ChargePhase::ConstantVoltage;
+        }
+        if self.phase == ChargePhase::ConstantVoltage {
+            let current = ((self.voltage_limit - ocv) / battery.effective_resistance()).clamp(0.0, self.cc_current);
+            if current > self.termination_current {
+                return current;
+            }
+            self.phase = ChargePhase::Complete;
+        }
+        0.0
+    }
+}
+
+/// Over-temperature trip with a separate, lower recovery threshold.
+///
+/// Trips once the temperature reaches `trip_temp` and stays tripped until it
+/// falls to `recovery_temp`, so a unit hovering at the trip point does not
+/// cycle on and off every step.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ThermalTrip {
+    /// Temperature (°C) at or above which the unit shuts down.
+    pub trip_temp: f64,
+    /// Temperature (°C) at or below which a tripped unit may resume.
+    pub recovery_temp: f64,
+    pub tripped: bool,
+    /// Number of trips since creation.
+    pub trip_count: u32,
+}
+
+impl ThermalTrip {
+    pub fn new(trip_temp: f64, recovery_temp: f64) -> Self {
+        Self {
+            trip_temp,
+            recovery_temp,
+            tripped: false,
+            trip_count: 0,
+        }
+    }
+
+    /// Update with the latest temperature; returns true while tripped.
+    pub fn update(&mut self, temperature: f64) -> bool {
+        if self.tripped {
+            if temperature <= self.recovery_temp {
+                self.tripped = false;
+            }
+        } else if temperature >= self.trip_temp {
+            self.tripped = true;
+            self.trip_count += 1;
+        }
+        self.tripped
+    }
+}
+
+/// How a [`ThermalController`] turns temperature into a fan command.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub enum ThermalMode {
+    /// Fan fully on or off, switched with the controller's hysteresis band.
+    #[default]
+    BangBang,
+    /// Fan duty `gain * (T - setpoint)`, saturating at 1 (gain in 1/°C).
+    Proportional { setpoint: f64, gain: f64 },
+}
+
+/// Cooling fan command from the measured temperature.
+///
+/// In bang-bang mode cooling switches on above `on_temp` and off at or below
+/// `off_temp`, so a temperature hovering between the two does not toggle the
+/// fan every step; the minimum on-time applies only to this mode.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ThermalController {
+    pub mode: ThermalMode,
+    /// Temperature (°C) above which cooling switches on.
+    pub on_temp: f64,
+    /// Temperature (°C) at or below which cooling may switch off.
+    pub off_temp: f64,
+    /// Shortest time (s) cooling stays on once switched on.
+    pub min_on_time: f64,
+    pub cooling_active: bool,
+    /// Fan duty (0-1) commanded in the last update.
+    pub duty: f64,
+    /// Time (s) since cooling last switched on.
+    on_elapsed: f64,
+}
+
+impl ThermalController {
+    pub fn new(on_temp: f64, off_temp: f64) -> Self {
+        Self {
+            mode: ThermalMode::BangBang,
+            on_temp,
+            off_temp,
+            min_on_time: 0.0,
+            cooling_active: false,
+            duty: 0.0,
+            on_elapsed: 0.0,
+        }
+    }
+
+    pub fn with_mode(mut self, mode: ThermalMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    pub fn with_min_on_time(mut self, min_on_time: f64) -> Self {
+        self.min_on_time = min_on_time;
+        self
+    }
+
+    /// Update with the latest temperature over a step of `dt` seconds; returns
+    /// whether the fan runs at all.
+    pub fn update(&mut self, temperature: f64, dt: f64) -> bool {
+        self.update_duty(temperature, dt) > 0.0
+    }
+
+    /// As [`update`](Self::update), returning the fan duty (0-1).
+    pub fn update_duty(&mut self, temperature: f64, dt: f64) -> f64 {
+        self.duty = match self.mode {
+            ThermalMode::BangBang => {
+                if self.cooling_active {
+                    self.on_elapsed += dt;
+                    if temperature <= self.off_temp && self.on_elapsed >= self.min_on_time {
+                        self.cooling_active = false;
+                    }
+                } else if temperature > self.on_temp {
+                    self.cooling_active = true;
+                    self.on_elapsed = 0.0;
+                }
+                if self.cooling_active { 1.0 } else { 0.0 }
+            }
+            ThermalMode::Proportional { setpoint, gain } => (gain * (temperature - setpoint)).clamp(0.0, 1.0),
+        };
+        self.cooling_active = self.duty > 0.0;
+        self.duty
+    }
+}
+
+/// How a [`LoadSharingController`] splits the total load across stacks.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum LoadSharingStrategy {
+    /// Every stack carries the same current.
+    #[default]
+    EqualCurrent,
+    /// Stacks hotter than the array mean get a smaller share: each weight is
+    /// `1 - gain * (T - T_mean)`, floored at zero (gain in 1/°C).
+    TemperatureBalancing { gain: f64 },
+}
+
+/// Distributes a total load over the stacks of a [`FuelCellArray`].
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct LoadSharingController {
+    pub strategy: LoadSharingStrategy,
+}
+
+impl LoadSharingController {
+    pub fn new(strategy: LoadSharingStrategy) -> Self {
+        Self { strategy }
+    }
+
+    /// Per-stack currents summing to `total_load`.
+    pub fn split(&self, total_load: f64, array: &FuelCellArray) -> Vec<f64> {
+        let count = array.stacks.len();
+        if count == 0 {
+            return Vec::new();
+        }
+        let equal = vec![total_load / count as f64; count];
+        match self.strategy {
+            LoadSharingStrategy::EqualCurrent => equal,
+            LoadSharingStrategy::TemperatureBalancing { gain } => {
+                let mean = array.stacks.iter().map(|s| s.temperature).sum::<f64>() / count as f64;
+                let weights: Vec<f64> = array
+                    .stacks
+                    .iter()
+                    .map(|s| (1.0 - gain * (s.temperature - mean)).max(0.0))
+                    .collect();
+                let total_weight: f64 = weights.iter().sum();
+                if total_weight <= 0.0 {
+                    return equal;
+                }
+                weights.iter().map(|w| total_load * w / total_weight).collect()
+            }
+        }
+    }
+}
+
+/// Phase of the pre-charge sequence.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PrechargePhase {
+    /// Bus disconnected, nothing happening.
+    Idle,
+    /// Bus charging through the pre-charge resistor; main contactor open.
+    Precharging,
+    /// Main contactor closed; pre-charge complete.
+    Connected,
+}
+
+/// Pre-charges the DC bus capacitance through a resistor before closing the
+/// main contactor, limiting inrush current.
+///
+/// The bus is modelled as an RC circuit: dV/dt = (V_bat - V_bus) / (R * C),
+/// stepped exactly so the bus never overshoots the battery at coarse `dt`.
+#[derive(Debug)]
+pub struct PrechargeController {
+    /// Pre-charge resistance [Ω].
+    pub resistance: f64,
+    /// Bus capacitance [F].
+    pub bus_capacitance: f64,
+    /// Fraction of battery voltage the bus must reach before the main contactor closes.
+    pub threshold_fraction: f64,
+    /// Present bus voltage [V].
+    pub bus_voltage: f64,
+    pub phase: PrechargePhase,
+}
+
+impl PrechargeController {
+    pub fn new(resistance: f64, bus_capacitance: f64, threshold_fraction: f64) -> Self {
+        Self {
+            resistance,
+            bus_capacitance,
+            threshold_fraction,
+            bus_voltage: 0.0,
+            phase: PrechargePhase::Idle,
+        }
+    }
+
+    /// Begin pre-charging (no-op once connected).
+    pub fn start(&mut self) {
+        if self.phase == PrechargePhase::Idle {
+            self.phase = PrechargePhase::Precharging;
+        }
+    }
+
+    /// Inrush current through the pre-charge resistor [A].
+    pub fn precharge_current(&self, battery_voltage: f64) -> f64 {
+        match self.phase {
+            PrechargePhase::Precharging => (battery_voltage - self.bus_voltage) / self.resistance,
+            _ => 0.0,
+        }
+    }
+
+    /// Advance the sequence by `dt`, closing `main_contactor` once the bus
+    /// voltage reaches the threshold.
+    pub fn update(&mut self, battery_voltage: f64, dt: f64, main_contactor: &mut Contactor) -> PrechargePhase {
+        match self.phase {
+            PrechargePhase::Idle => main_contactor.open(),
+            PrechargePhase::Precharging => {
+                let tau = self.resistance * self.bus_capacitance;
+                self.bus_voltage = if tau > 0.0 {
+                    battery_voltage + (self.bus_voltage - battery_voltage) * (-dt / tau).exp()
+                } else {
+                    battery_voltage
+                };
+                if self.bus_voltage >= self.threshold_fraction * battery_voltage {
+                    main_contactor.close();
+                    self.phase = PrechargePhase::Connected;
+                }
+            }
+            PrechargePhase::Connected => {
+                // With the contactor closed the bus follows the battery.
+                self.bus_voltage = battery_voltage;
+            }
+        }
+        self.phase
+    }
 }
 
 #[cfg(test)]
@@ -128,9 +837,381 @@ mod tests {
 
     #[test]
     fn test_pid_controller_output() {
-        let mut pid = PidController::new(70.0, 0.3, 0.05, 0.05);
-        let output = pid.compute(80.0, 70.0);
+        let mut pid = PidController::new(70.0, 0.3, 0.05);
+        let output = pid.compute(80.0, 70.0, 0.05);
         // With an error of 10, output should be positive.
         assert!(output > 0.0);
     }
+
+    #[test]
+    fn test_autotune_first_order_plus_dead_time() {
+        use std::collections::VecDeque;
+
+        // K = 1, tau = 1 s, L = 0.5 s. The relay limit cycle of this plant is known
+        // in closed form: a = K·d·(1 - e^(-L/tau)) and tu = 2·tau·ln(2·e^(L/tau) - 1),
+        // so ku ≈ 3.24 and tu ≈ 1.66 s (the describing function sits below the
+        // exact ku ≈ 3.81, which is the usual relay-test bias on dead-time plants).
+        let dt = 0.001;
+        let mut delay = VecDeque::from(vec![0.0; 500]);
+        let mut y = 0.0;
+        let plant = |u: f64| {
+            delay.push_back(u);
+            let delayed = delay.pop_front().unwrap();
+            y += dt * (delayed - y);
+            y
+        };
+        let tuned = PidController::autotune(plant, 0.0, 1.0, dt, 100_000).unwrap();
+        let a = 1.0 - (-0.5f64).exp();
+        let expected_ku = 4.0 / (std::f64::consts::PI * a);
+        let expected_tu = 2.0 * (2.0 * 0.5f64.exp() - 1.0).ln();
+        assert!((tuned.tu - expected_tu).abs() / expected_tu < 0.02, "tu = {}", tuned.tu);
+        assert!((tuned.ku - expected_ku).abs() / expected_ku < 0.02, "ku = {}", tuned.ku);
+        assert!((tuned.controller.kp - 0.6 * tuned.ku).abs() < 1e-12);
+        assert!((tuned.controller.ki - 1.2 * tuned.ku / tuned.tu).abs() < 1e-12);
+
+        assert_eq!(
+            PidController::autotune(|_| 0.0, 1.0, 1.0, dt, 1000).unwrap_err(),
+            BmsError::AutotuneFailed { steps: 1000 }
+        );
+    }
+
+    #[test]
+    fn test_gain_schedule_interpolates_between_breakpoints() {
+        let mut schedule = GainSchedule::new();
+        // Gentle gains near the limiting current, aggressive near zero load.
+        schedule.add(100.0, 0.2, 0.02, 0.0).add(0.0, 1.0, 0.1, 0.01);
+        assert_eq!(schedule.gains_at(-5.0), Some((1.0, 0.1, 0.01)));
+        assert_eq!(schedule.gains_at(500.0), Some((0.2, 0.02, 0.0)));
+        let (kp, ki, kd) = schedule.gains_at(50.0).unwrap();
+        assert!((kp - 0.6).abs() < 1e-12 && (ki - 0.06).abs() < 1e-12 && (kd - 0.005).abs() < 1e-12);
+        assert_eq!(GainSchedule::new().gains_at(1.0), None);
+
+        let mut pid = PidController::with_schedule(schedule);
+        pid.compute_scheduled(1.0, 0.0, 100.0, 0.5);
+        assert_eq!(pid.gains(), (0.2, 0.02, 0.0));
+        // Scheduling on error magnitude: |e| = 1 sits near the aggressive end.
+        pid.compute(1.0, 0.0, 0.5);
+        assert!((pid.gains().0 - 0.992).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_battery_controller_hysteresis_does_not_chatter() {
+        let mut controller = BatteryController::new(65.0, 75.0, 8.0);
+        // Inside the band the mode never changes, whichever way SoC moves.
+        for soc in [70.0, 66.0, 74.0, 65.1, 74.9] {
+            assert!(!controller.update_mode(soc));
+        }
+        assert!(controller.update_mode(65.0));
+        // Hovering just above the lower threshold keeps charging.
+        for soc in [65.0, 65.1, 64.9, 65.2, 74.9] {
+            assert!(controller.update_mode(soc));
+        }
+        assert!(!controller.update_mode(75.0));
+        for soc in [75.0, 74.9, 75.1, 65.1] {
+            assert!(!controller.update_mode(soc));
+        }
+        assert_eq!(controller.charge_command(true), 8.0);
+        assert_eq!(controller.charge_command(false), 0.0);
+    }
+
+    #[test]
+    fn test_last_terms_sum_to_output() {
+        let mut pid = PidController::new(2.0, 0.5, 0.1);
+        pid.compute(1.0, 0.0, 0.5);
+        let output = pid.compute(1.0, 0.5, 0.5);
+        let (p, i, d) = pid.last_terms();
+        assert_eq!(pid.last_error(), 0.5);
+        assert!((p - 1.0).abs() < 1e-12);
+        assert!((i - 0.5 * pid.integral()).abs() < 1e-12);
+        assert!((d - 0.1 * (0.5 - 1.0) / 0.5).abs() < 1e-12);
+        assert!((p + i + d - output).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_air_supply_regulates_excess_ratio() {
+        let mut controller = AirSupplyController::with_excess_ratio(1.0, 0.0, 0.0, 0.21, 2.0);
+        // Starved cathode (λ below setpoint) asks for more torque than a rich one.
+        let starved = controller.compute_motor_torque_for_excess_ratio(1.5, 0.21, 0.5);
+        let rich = controller.compute_motor_torque_for_excess_ratio(2.5, 0.21, 0.5);
+        assert!(starved > 0.0 && rich < 0.0);
+        // Zero load gives λ = ∞; the command stays finite.
+        assert!(controller.compute_motor_torque_for_excess_ratio(f64::INFINITY, 0.21, 0.5).is_finite());
+    }
+
+    #[test]
+    fn test_integration_methods_differ_with_tustin_between_eulers() {
+        let integrate = |method| {
+            let mut pid = PidController::with_method(0.0, 1.0, 0.0, method);
+            // Ramp error: e[k] = k.
+            (1..=10).map(|k| pid.compute(k as f64, 0.0, 0.1)).last().unwrap()
+        };
+        let forward = integrate(IntegrationMethod::ForwardEuler);
+        let backward = integrate(IntegrationMethod::BackwardEuler);
+        let tustin = integrate(IntegrationMethod::Tustin);
+        // Sum of e[k]·dt: forward uses 0..=9, backward 1..=10, Tustin their mean.
+        assert!((forward - 4.5).abs() < 1e-9);
+        assert!((backward - 5.5).abs() < 1e-9);
+        assert!((tustin - 5.0).abs() < 1e-9);
+        assert!(forward < tustin && tustin < backward);
+    }
+
+    #[test]
+    fn test_integral_limits_and_conditional_integration_stop_windup() {
+        let mut clamped = PidController::new(0.0, 1.0, 0.0);
+        clamped.set_integral_limits(-2.0, 2.0);
+        let mut conditional = PidController::new(0.0, 1.0, 0.0);
+        conditional.set_conditional_integration(Some((-5.0, 5.0)));
+        let mut unbounded = PidController::new(0.0, 1.0, 0.0);
+        for _ in 0..100 {
+            clamped.compute(10.0, 0.0, 0.1);
+            conditional.compute(10.0, 0.0, 0.1);
+            unbounded.compute(10.0, 0.0, 0.1);
+        }
+        assert_eq!(clamped.integral(), 2.0);
+        assert!(conditional.integral() <= 5.0 + 1e-9);
+        assert!((unbounded.integral() - 100.0).abs() < 1e-9);
+
+        // Once the error flips, the bounded integrators unwind within a few steps.
+        let recovered = |pid: &mut PidController| (1..=200).find(|_| pid.compute(-10.0, 0.0, 0.1) < 0.0).unwrap();
+        assert!(recovered(&mut clamped) < 5);
+        assert!(recovered(&mut conditional) < 10);
+        assert!(recovered(&mut unbounded) > 90);
+    }
+
+    #[test]
+    fn test_output_limits_clamp_and_do_not_wind_up() {
+        let mut pid = PidController::with_limits(1.0, 1.0, 0.0, -3.0, 3.0);
+        for _ in 0..100 {
+            let output = pid.compute(50.0, 0.0, 0.1);
+            assert!(output <= 3.0);
+        }
+        assert_eq!(pid.compute_adaptive(50.0, 0.0, 0.1), 3.0);
+        assert!(pid.integral() < 3.0 + 0.5, "integrator wound to {}", pid.integral());
+        // Negative side, and the adaptive factor is applied before clamping.
+        assert_eq!(pid.compute_adaptive(-50.0, 0.0, 0.1), -3.0);
+    }
+
+    #[test]
+    fn test_adaptive_factor_counts_towards_anti_windup() {
+        // |error| = 1.2 takes the 1.5 factor: the unscaled output never reaches
+        // the limit, the scaled one does once the integral passes ~1.87.
+        let mut pid = PidController::with_limits(0.4, 0.1, 0.0, -1.0, 1.0);
+        for _ in 0..200 {
+            pid.compute_adaptive(1.2, 0.0, 0.5);
+        }
+        // The integrator holds the output just inside the limit.
+        assert!(pid.compute_adaptive(1.2, 0.0, 0.5) > 0.95);
+        let wound = pid.integral();
+        assert!(wound < 1.9, "integrator wound to {}", wound);
+        pid.compute_adaptive(1.2, 0.0, 0.5);
+        assert_eq!(pid.integral(), wound, "integral keeps growing while saturated");
+    }
+
+    #[test]
+    fn test_set_gains_mid_run_is_bumpless() {
+        let mut pid = PidController::new(0.5, 0.2, 0.0);
+        for _ in 0..20 {
+            pid.compute(1.0, 0.4, 0.5);
+        }
+        let before = pid.compute(1.0, 0.4, 0.5);
+        let integral_term = pid.last_terms().1;
+        pid.set_gains(0.5, 0.05, 0.0);
+        // Same P, same integral contribution: only one more step of integration differs.
+        let after = pid.compute(1.0, 0.4, 0.5);
+        assert!((pid.last_terms().1 - integral_term).abs() < 0.05 * 0.6 * 0.5 + 1e-9);
+        assert!((after - before).abs() < 0.1);
+
+        pid.set_integral_limits(-1.0, 1.0);
+        pid.set_gains(0.5, 0.0, 0.0);
+        assert_eq!(pid.integral(), 0.0);
+    }
+
+    #[test]
+    fn test_reset_clears_stale_state() {
+        let mut pid = PidController::new(1.0, 1.0, 1.0);
+        for _ in 0..10 {
+            pid.compute(5.0, 0.0, 0.5);
+        }
+        assert!(pid.compute(1.0, 1.0, 0.5) != 0.0, "stale integral and derivative should show");
+        pid.reset();
+        assert_eq!(pid.compute(1.0, 1.0, 0.5), 0.0);
+
+        pid.reset_to(2.0, 0.0);
+        assert_eq!(pid.compute(1.0, 1.0, 0.5), 2.0, "seeded integral carries over bumplessly");
+    }
+
+    #[test]
+    fn test_varying_dt_matches_equivalent_fixed_dt() {
+        // A ramp error e(t) = t integrates exactly under Tustin for any step sequence.
+        let run = |steps: &[f64]| {
+            let mut pid = PidController::with_method(0.5, 1.0, 0.2, IntegrationMethod::Tustin);
+            pid.compute(0.0, 0.0, 0.0);
+            let mut t = 0.0;
+            let mut output = 0.0;
+            for &dt in steps {
+                t += dt;
+                output = pid.compute(t, 0.0, dt);
+            }
+            output
+        };
+        let varying = run(&[0.1, 0.4, 0.25, 0.05, 0.2]);
+        let fixed = run(&[0.25; 4]);
+        // P: 0.5·1, I: 1·½·1², D: 0.2·1 (slope of the ramp).
+        assert!((fixed - 1.2).abs() < 1e-9);
+        assert!((varying - fixed).abs() < 1e-9, "{} vs {}", varying, fixed);
+    }
+
+    #[test]
+    fn test_cc_cv_current_tapers_in_cv_phase() {
+        let mut battery = Battery::new();
+        battery.soc = 50.0;
+        let mut charger = CcCvCharger::new(8.0, 52.5, 0.5);
+        let mut cv_currents = Vec::new();
+        for _ in 0..500 {
+            let current = charger.charge_current(&battery);
+            if charger.phase == ChargePhase::Complete {
+                break;
+            }
+            battery.update(current, 0.0, true, 0.5);
+            match charger.phase {
+                ChargePhase::ConstantCurrent => assert_eq!(current, 8.0),
+                _ => {
+                    assert!((battery.voltage - 52.5).abs() < 0.1, "CV phase drifted to {} V", battery.voltage);
+                    cv_currents.push(current);
+                }
+            }
+        }
+        assert_eq!(charger.phase, ChargePhase::Complete);
+        assert!(cv_currents.len() > 3);
+        assert!(cv_currents.windows(2).all(|w| w[1] < w[0]), "CV current must taper: {:?}", cv_currents);
+    }
+
+    #[test]
+    fn test_temperature_balancing_unloads_hot_stack() {
+        let run = |strategy| {
+            let mut array = FuelCellArray::uniform(2);
+            array.stacks[0].temperature = 60.0;
+            array.stacks[1].temperature = 40.0;
+            let controller = LoadSharingController::new(strategy);
+            let first = controller.split(20.0, &array);
+            for _ in 0..100 {
+                let loads = controller.split(20.0, &array);
+                assert!((loads.iter().sum::<f64>() - 20.0).abs() < 1e-9);
+                array.update(&loads, 0.0, 1.0, 0.8, 0.5);
+            }
+            (first, array.temperature_spread())
+        };
+        let (equal_split, equal_spread) = run(LoadSharingStrategy::EqualCurrent);
+        let (balanced_split, balanced_spread) = run(LoadSharingStrategy::TemperatureBalancing { gain: 0.05 });
+        assert_eq!(equal_split, vec![10.0, 10.0]);
+        assert!(balanced_split[0] < balanced_split[1], "hot stack should carry less: {:?}", balanced_split);
+        assert!(balanced_spread < equal_spread, "{} vs {}", balanced_spread, equal_spread);
+        assert!(balanced_spread < 20.0);
+    }
+
+    #[test]
+    fn test_thermal_trip_holds_until_recovery_temp() {
+        let mut trip = ThermalTrip::new(80.0, 65.0);
+        assert!(!trip.update(79.9));
+        assert!(trip.update(80.0), "should trip at trip_temp");
+        // Cooling just below the trip point is not enough.
+        for temperature in [79.9, 75.0, 70.0, 65.1] {
+            assert!(trip.update(temperature), "resumed early at {}", temperature);
+        }
+        assert!(!trip.update(65.0), "should resume at recovery_temp");
+        assert!(!trip.update(79.0));
+        assert_eq!(trip.trip_count, 1);
+    }
+
+    #[test]
+    fn test_thermal_controller_hysteresis_stops_chatter() {
+        let hovering = [44.5, 43.5, 44.2, 43.8, 44.6, 43.4, 44.1, 43.9];
+        let toggles = |controller: &mut ThermalController| {
+            let mut previous = controller.cooling_active;
+            let mut toggles = 0;
+            for _ in 0..5 {
+                for temperature in hovering {
+                    let active = controller.update(temperature, 0.5);
+                    toggles += usize::from(active != previous);
+                    previous = active;
+                }
+            }
+            toggles
+        };
+        // A single threshold flips on nearly every step.
+        assert_eq!(toggles(&mut ThermalController::new(44.0, 44.0)), 40);
+        // A 42-46 band never switches for this signal.
+        assert_eq!(toggles(&mut ThermalController::new(46.0, 42.0)), 0);
+
+        let mut banded = ThermalController::new(46.0, 42.0);
+        assert!(banded.update(46.5, 0.5));
+        assert!(banded.update(43.0, 0.5), "stays on inside the band");
+        assert!(!banded.update(42.0, 0.5));
+    }
+
+    #[test]
+    fn test_proportional_thermal_mode_saturates() {
+        let mut controller = ThermalController::new(46.0, 42.0).with_mode(ThermalMode::Proportional { setpoint: 40.0, gain: 0.1 });
+        assert_eq!(controller.update_duty(38.0, 0.5), 0.0);
+        assert!(!controller.cooling_active);
+        assert!((controller.update_duty(45.0, 0.5) - 0.5).abs() < 1e-12);
+        assert!(controller.update(45.0, 0.5));
+        assert_eq!(controller.update_duty(60.0, 0.5), 1.0);
+    }
+
+    #[test]
+    fn test_thermal_controller_min_on_time() {
+        let mut controller = ThermalController::new(46.0, 42.0).with_min_on_time(2.0);
+        assert!(controller.update(47.0, 0.5));
+        for _ in 0..3 {
+            assert!(controller.update(40.0, 0.5), "switched off before the minimum on-time");
+        }
+        assert!(!controller.update(40.0, 0.5));
+    }
+
+    #[test]
+    fn test_main_contactor_closes_only_after_precharge_threshold() {
+        let battery_voltage = 53.0;
+        let mut precharge = PrechargeController::new(10.0, 0.01, 0.95);
+        let mut contactor = Contactor::new();
+
+        precharge.update(battery_voltage, 0.01, &mut contactor);
+        assert!(!contactor.is_closed(), "idle controller must not close the contactor");
+
+        precharge.start();
+        let mut steps = 0;
+        while precharge.update(battery_voltage, 0.01, &mut contactor) == PrechargePhase::Precharging {
+            assert!(!contactor.is_closed());
+            assert!(precharge.bus_voltage < 0.95 * battery_voltage);
+            steps += 1;
+            assert!(steps < 10_000, "pre-charge never completed");
+        }
+        assert!(contactor.is_closed());
+        assert!(precharge.bus_voltage >= 0.95 * battery_voltage);
+        // RC = 0.1 s, so reaching 95% takes roughly 3 time constants.
+        assert!(steps > 20);
+        assert_eq!(contactor.close_count, 1);
+    }
+
+    #[test]
+    fn test_precharge_at_the_engine_step_never_overshoots() {
+        let battery_voltage = 53.0;
+        // RC = 1 s: 95 % takes ln(20) ≈ 3.0 s, i.e. six 0.5 s steps.
+        let mut precharge = PrechargeController::new(100.0, 0.01, 0.95);
+        let mut contactor = Contactor::new();
+        precharge.start();
+        let mut steps = 0;
+        while precharge.update(battery_voltage, 0.5, &mut contactor) == PrechargePhase::Precharging {
+            assert!(precharge.bus_voltage < battery_voltage);
+            steps += 1;
+        }
+        assert_eq!(steps, 5, "closes on the sixth step");
+        assert!(precharge.bus_voltage <= battery_voltage);
+
+        // With RC = 0.1 s one 0.5 s step charges the bus to just below the battery.
+        let mut fast = PrechargeController::new(10.0, 0.01, 0.95);
+        fast.start();
+        fast.update(battery_voltage, 0.5, &mut Contactor::new());
+        assert!((fast.bus_voltage - battery_voltage * (1.0 - (-5.0f64).exp())).abs() < 1e-9);
+    }
 }
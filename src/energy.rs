@@ -0,0 +1,118 @@
+use serde::{Deserialize, Serialize};
+
+/// Running energy totals for a simulation run [Wh].
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct EnergyLedger {
+    /// Electrical energy produced by the fuel cell.
+    pub fuel_cell_wh: f64,
+    /// Energy stored into the battery.
+    pub battery_charged_wh: f64,
+    /// Energy drawn from the battery.
+    pub battery_discharged_wh: f64,
+    /// Excess energy exported to the grid.
+    pub exported_wh: f64,
+    /// Excess energy with nowhere to go (no export path or above its limit).
+    pub discarded_wh: f64,
+    /// Energy lost in the DC-DC converter.
+    pub converter_loss_wh: f64,
+    /// Energy lost by cell balancing (bleed heat or shuttle inefficiency).
+    pub balancing_loss_wh: f64,
+}
+
+impl EnergyLedger {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Book `power_w` held for `dt` seconds onto the appropriate battery total.
+    pub fn record_battery(&mut self, power_w: f64, dt: f64) {
+        if power_w >= 0.0 {
+            self.battery_charged_wh += power_w * dt / 3600.0;
+        } else {
+            self.battery_discharged_wh += -power_w * dt / 3600.0;
+        }
+    }
+
+    pub fn record_fuel_cell(&mut self, power_w: f64, dt: f64) {
+        self.fuel_cell_wh += power_w.max(0.0) * dt / 3600.0;
+    }
+
+    pub fn record_converter_loss(&mut self, power_w: f64, dt: f64) {
+        self.converter_loss_wh += power_w.max(0.0) * dt / 3600.0;
+    }
+
+    /// Fraction of the fuel cell energy that survived conversion (1 with no converter losses).
+    pub fn conversion_efficiency(&self) -> f64 {
+        if self.fuel_cell_wh <= 0.0 {
+            return 1.0;
+        }
+        1.0 - self.converter_loss_wh / self.fuel_cell_wh
+    }
+
+    pub fn record_balancing_loss(&mut self, energy_wh: f64) {
+        self.balancing_loss_wh += energy_wh.max(0.0);
+    }
+
+    /// Route excess power through the export path (if any) and book the rest
+    /// as discarded. Returns the exported power [W].
+    pub fn record_excess(&mut self, excess_w: f64, dt: f64, export: Option<&ExportSink>) -> f64 {
+        let excess_w = excess_w.max(0.0);
+        let exported_w = export.map_or(0.0, |sink| sink.absorb(excess_w));
+        self.exported_wh += exported_w * dt / 3600.0;
+        self.discarded_wh += (excess_w - exported_w) * dt / 3600.0;
+        exported_w
+    }
+}
+
+/// Grid-tie export path that absorbs excess power up to a limit.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ExportSink {
+    /// Maximum export power [W].
+    pub limit_w: f64,
+}
+
+impl ExportSink {
+    pub fn new(limit_w: f64) -> Self {
+        Self { limit_w }
+    }
+
+    /// Power actually exported for a given excess [W].
+    pub fn absorb(&self, excess_w: f64) -> f64 {
+        excess_w.clamp(0.0, self.limit_w.max(0.0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulation::Battery;
+
+    #[test]
+    fn test_full_battery_excess_is_exported_and_tracked() {
+        let mut battery = Battery::new();
+        let mut ledger = EnergyLedger::new();
+        let export = ExportSink::new(1000.0);
+        for _ in 0..10 {
+            battery.update(8.0, 0.0, true, 0.5);
+            let excess_w = battery.rejected_current * battery.voltage;
+            ledger.record_excess(excess_w, 0.5, Some(&export));
+        }
+        assert_eq!(battery.soc, 100.0);
+        assert!(ledger.exported_wh > 0.0);
+        assert_eq!(ledger.discarded_wh, 0.0);
+    }
+
+    #[test]
+    fn test_excess_above_limit_is_discarded() {
+        let mut ledger = EnergyLedger::new();
+        let exported = ledger.record_excess(500.0, 3600.0, Some(&ExportSink::new(200.0)));
+        assert_eq!(exported, 200.0);
+        assert!((ledger.exported_wh - 200.0).abs() < 1e-9);
+        assert!((ledger.discarded_wh - 300.0).abs() < 1e-9);
+
+        let mut no_export = EnergyLedger::new();
+        no_export.record_excess(500.0, 3600.0, None);
+        assert_eq!(no_export.exported_wh, 0.0);
+        assert!((no_export.discarded_wh - 500.0).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,1358 @@
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+use crate::control::{
+    AirSupplyController, BatteryController, CcCvCharger, CoolingTarget, OxygenController, ThermalController, ThermalTrip,
+};
+use crate::energy::{EnergyLedger, ExportSink};
+use crate::error::BmsError;
+use crate::hal::{ActuatorFault, DigitalOutput, SimulatedActuator, StuckSensorDetector};
+use crate::rng::SimRng;
+use crate::scenario::{DisturbanceShape, Scenario};
+use crate::schedule::ParameterSchedule;
+use crate::snapshot::Checkpointer;
+use crate::sensors::{read_fuel_cell_sensor_noisy, PlausibilityLimits, SampledSensor, SensorFault, STUCK_TEMPERATURE_TOLERANCE};
+use crate::simulation::bus::PowerBus;
+use crate::simulation::cathode::CathodeChannel;
+use crate::simulation::converter::DcDcConverter;
+use crate::simulation::coolant::CoolantLoop;
+use crate::simulation::manifold::saturation_pressure;
+use crate::simulation::{AirSupplySystem, Battery, FuelCell};
+use crate::supervisor::{LoadProtection, Supervisor, SupervisorInputs};
+use crate::telemetry::{Channel, SinkRegistry, StateDerivatives, StepReport, TelemetrySink};
+
+/// Measured plant inputs for digital-twin operation. `None` means "not measured".
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Measurements {
+    /// Measured stack load (A). Replaces the controller-computed load.
+    pub load: Option<f64>,
+    /// Measured fuel cell temperature (°C).
+    pub fuel_cell_temperature: Option<f64>,
+    /// Measured battery temperature (°C).
+    pub battery_temperature: Option<f64>,
+}
+
+/// Observer gains (0-1) used to correct model states toward measurements.
+///
+/// 0 ignores the measurement, 1 replaces the model state with it.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ObserverGains {
+    pub fuel_cell_temperature: f64,
+    pub battery_temperature: f64,
+}
+
+impl Default for ObserverGains {
+    fn default() -> Self {
+        Self {
+            fuel_cell_temperature: 0.3,
+            battery_temperature: 0.3,
+        }
+    }
+}
+
+/// Aggregate results of a headless run.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RunSummary {
+    /// Number of steps executed.
+    pub steps: usize,
+    /// Simulated time reached (s).
+    pub simulated_time: f64,
+    /// Highest fuel cell temperature seen (°C).
+    pub max_fuel_cell_temperature: f64,
+    /// Lowest battery SoC seen (%).
+    pub min_battery_soc: f64,
+    /// Report of the last executed step, if any.
+    pub last_report: Option<StepReport>,
+}
+
+impl RunSummary {
+    fn new() -> Self {
+        Self {
+            steps: 0,
+            simulated_time: 0.0,
+            max_fuel_cell_temperature: f64::NEG_INFINITY,
+            min_battery_soc: f64::INFINITY,
+            last_report: None,
+        }
+    }
+
+    fn record(&mut self, report: StepReport) {
+        self.steps += 1;
+        self.simulated_time = report.time;
+        self.max_fuel_cell_temperature = self.max_fuel_cell_temperature.max(report.fuel_cell_temperature);
+        self.min_battery_soc = self.min_battery_soc.min(report.battery_soc);
+        self.last_report = Some(report);
+    }
+}
+
+/// A run that stopped early, with the summary of what completed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RunAborted {
+    pub error: BmsError,
+    pub summary: RunSummary,
+}
+
+/// Every step report of a [`run_simulation`] call, in order.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct SimulationResults {
+    pub reports: Vec<StepReport>,
+}
+
+impl SimulationResults {
+    pub fn len(&self) -> usize {
+        self.reports.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.reports.is_empty()
+    }
+
+    /// Simulated time at the end of each step (s).
+    pub fn time(&self) -> Vec<f64> {
+        self.reports.iter().map(|report| report.time).collect()
+    }
+
+    /// One numeric channel as a time series.
+    pub fn series(&self, channel: Channel) -> Vec<f64> {
+        self.reports.iter().map(|report| channel.value(report)).collect()
+    }
+
+    /// Push one more step report.
+    pub fn record(&mut self, report: StepReport) {
+        self.reports.push(report);
+    }
+
+    /// The main state columns as CSV, one header line then one row per step.
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from(
+            "time,fuel_cell_voltage,fuel_cell_current,fuel_cell_temperature,membrane_hydration,battery_soc,manifold_pressure,oxygen_concentration\n",
+        );
+        for report in &self.reports {
+            csv.push_str(&format!(
+                "{},{},{},{},{},{},{},{}\n",
+                report.time,
+                report.fuel_cell_voltage,
+                report.fuel_cell_current,
+                report.fuel_cell_temperature,
+                report.membrane_hydration,
+                report.battery_soc,
+                report.manifold_pressure,
+                report.oxygen_concentration,
+            ));
+        }
+        csv
+    }
+}
+
+/// Run `scenario` for `duration` seconds at step `dt` with no sleeping and no
+/// I/O, recording every step. The step count is [`Scenario::steps`], so it
+/// does not depend on floating-point time accumulation.
+pub fn run_simulation(scenario: Scenario, duration: f64, dt: f64) -> SimulationResults {
+    let mut engine = SimulationEngine::new(Scenario { duration, dt, ..scenario });
+    let steps = engine.scenario.steps();
+    SimulationResults { reports: (0..steps).map(|_| engine.step()).collect() }
+}
+
+/// Humidity fed to the membrane hydration model, as the scenario selects it.
+pub(crate) fn membrane_humidity(scenario: &Scenario, air_supply: &AirSupplySystem, fuel_cell: &FuelCell) -> f64 {
+    if scenario.manifold_humidity {
+        air_supply.manifold.relative_humidity()
+    } else if scenario.humidifier {
+        scenario.humidity
+    } else {
+        scenario.humidity.min(fuel_cell.passive_humidity(scenario.ambient_humidity))
+    }
+}
+
+/// Owns the plant models and controllers and advances them one step at a time.
+///
+/// This is the loop the GUI tick and the `main_console` binary used to run
+/// inline. Both entry points now drive this engine and differ only in the
+/// `Scenario` they build and how they present each `StepReport`.
+#[derive(Debug)]
+pub struct SimulationEngine {
+    pub scenario: Scenario,
+    pub fuel_cell: FuelCell,
+    pub battery: Battery,
+    pub air_supply: AirSupplySystem,
+    pub oxygen_controller: OxygenController,
+    pub air_supply_controller: AirSupplyController,
+    pub battery_controller: BatteryController,
+    /// CC-CV charger; `None` charges at the scenario's fixed charging current.
+    pub charger: Option<CcCvCharger>,
+    pub observer_gains: ObserverGains,
+    /// Operating mode and safety interlocks.
+    pub supervisor: Supervisor,
+    /// Over-temperature and over-current load shedding, if enabled.
+    pub protection: Option<LoadProtection>,
+    /// Cooling on/off decision.
+    pub thermal_controller: ThermalController,
+    /// Over-temperature shutdown of the stack; while tripped it carries no load.
+    pub fuel_cell_trip: ThermalTrip,
+    /// Over-temperature shutdown of the battery; while tripped it is disconnected.
+    pub battery_trip: ThermalTrip,
+    /// Random load disturbance, seeded from `Scenario::seed`.
+    pub disturbance_rng: SimRng,
+    pub sensor_rng: SimRng,
+    /// Sample-and-hold on the fuel cell temperature reading.
+    pub temperature_sensor: SampledSensor<f64>,
+    /// Stuck check on fresh temperature samples, if `Scenario::stuck_sensor_window` is set.
+    pub stuck_sensor: Option<StuckSensorDetector>,
+    /// Fault found in this step's fuel cell reading; while set the load is cut and cooling forced on.
+    pub sensor_fault: Option<SensorFault>,
+    /// Energy totals for the run.
+    pub ledger: EnergyLedger,
+    /// Converter between stack and battery; `None` couples their currents directly.
+    pub converter: Option<DcDcConverter>,
+    /// Grid export path for excess power, if enabled.
+    pub export: Option<ExportSink>,
+    /// Telemetry sinks receiving every step report.
+    pub sinks: SinkRegistry,
+    /// Parameter changes applied at scheduled times during the run.
+    pub schedule: ParameterSchedule,
+    /// Automatic checkpoints to disk, if enabled.
+    pub checkpoints: Option<Checkpointer>,
+    /// Cooling fan switched through the HAL, with `Scenario::cooling_fan_fault` injected.
+    pub cooling_fan: ActuatorFault<SimulatedActuator>,
+    pub charging_mode: bool,
+    pub cooling_active: bool,
+    /// Fan duty (0-1) applied in the last step.
+    pub cooling_duty: f64,
+    /// True while the battery current is ramping after a mode switch.
+    pub current_ramp_active: bool,
+    /// Compressor torque commanded in the last step (N·m).
+    pub motor_torque: f64,
+    /// Oxygen concentration the controllers saw in the last step.
+    pub oxygen_measured: f64,
+    /// Measurement the air supply controller acted on in the last step: λ in
+    /// excess-ratio mode, else the oxygen concentration.
+    pub air_measured: f64,
+    /// Temperature the cooling decision used in the last step (°C).
+    pub cooling_measured: f64,
+    /// State derivatives from the last step, if `Scenario::record_derivatives` is set.
+    pub derivatives: Option<StateDerivatives>,
+    /// Elapsed simulation time (s).
+    pub time: f64,
+    /// Steps taken so far; the run length is counted in steps, not accumulated time.
+    pub steps_taken: usize,
+}
+
+impl SimulationEngine {
+    pub fn new(scenario: Scenario) -> Self {
+        let mut fuel_cell = FuelCell::new();
+        fuel_cell.thermal_mass = scenario.fuel_cell_thermal_mass;
+        if scenario.cooling_target == CoolingTarget::Coolant {
+            fuel_cell.coolant = Some(CoolantLoop::new(fuel_cell.temperature));
+        }
+        fuel_cell.cathode = scenario.cathode.map(CathodeChannel::new);
+        let mut battery = Battery::new();
+        battery.capacity_ah = scenario.battery_capacity_ah;
+        let mut air_supply = AirSupplySystem::new();
+        air_supply.flow_delay.dead_time = scenario.compressor_dead_time;
+        air_supply.compressor.min_running_speed = scenario.compressor_min_running_speed;
+        let inlet_vapor_pressure = scenario.ambient_humidity * saturation_pressure(air_supply.inlet_temp - 273.15);
+        air_supply.manifold.inlet_vapor_fraction = inlet_vapor_pressure / air_supply.inlet_pressure;
+        let supervisor = Supervisor::new(
+            scenario.soc_upper_threshold,
+            SupervisorInputs {
+                soc: battery.soc,
+                charge_requested: false,
+                fuel_cell_starting: fuel_cell.is_starting_up(),
+                fuel_cell_tripped: false,
+                battery_tripped: false,
+                sensor_fault: false,
+                protected: false,
+            },
+        );
+        let o2 = scenario.oxygen_gains;
+        let air = scenario.air_supply_gains;
+        Self {
+            fuel_cell,
+            battery,
+            air_supply,
+            oxygen_controller: OxygenController::new(o2.kp, o2.ki, o2.kd),
+            air_supply_controller: match scenario.oxygen_excess_ratio_setpoint {
+                Some(lambda) => AirSupplyController::with_excess_ratio(air.kp, air.ki, air.kd, scenario.desired_oxygen, lambda),
+                None => AirSupplyController::new(air.kp, air.ki, air.kd, scenario.desired_oxygen),
+            },
+            battery_controller: BatteryController::new(scenario.soc_lower_threshold, scenario.soc_upper_threshold, scenario.charging_current),
+            charger: scenario
+                .charge_voltage_limit
+                .map(|limit| CcCvCharger::new(scenario.charging_current, limit, scenario.charge_termination_current)),
+            observer_gains: ObserverGains::default(),
+            supervisor,
+            protection: scenario.protection.map(LoadProtection::new),
+            thermal_controller: ThermalController::new(
+                scenario.cooling_threshold + scenario.cooling_hysteresis / 2.0,
+                scenario.cooling_threshold - scenario.cooling_hysteresis / 2.0,
+            )
+            .with_min_on_time(scenario.cooling_min_on_time)
+            .with_mode(scenario.cooling_mode),
+            fuel_cell_trip: ThermalTrip::new(scenario.fuel_cell_trip_temp, scenario.fuel_cell_recovery_temp),
+            battery_trip: ThermalTrip::new(scenario.battery_trip_temp, scenario.battery_recovery_temp),
+            disturbance_rng: SimRng::for_component(scenario.seed, "load_disturbance"),
+            sensor_rng: SimRng::for_component(scenario.seed, "sensors"),
+            temperature_sensor: SampledSensor::new(scenario.temperature_sample_period),
+            stuck_sensor: scenario.stuck_sensor_window.map(|window| StuckSensorDetector::new(window, STUCK_TEMPERATURE_TOLERANCE)),
+            sensor_fault: None,
+            ledger: EnergyLedger::new(),
+            converter: scenario.dc_dc_efficiency.map(|efficiency| DcDcConverter {
+                efficiency_curve: scenario.dc_dc_efficiency_curve.clone(),
+                command: scenario.dc_dc_command,
+                ..DcDcConverter::new(efficiency, scenario.dc_dc_voltage_ratio)
+            }),
+            export: scenario.grid_export_limit_w.map(ExportSink::new),
+            sinks: SinkRegistry::new(),
+            schedule: ParameterSchedule::new(),
+            checkpoints: None,
+            cooling_fan: ActuatorFault::new(SimulatedActuator::new(), scenario.cooling_fan_fault),
+            charging_mode: false,
+            cooling_active: false,
+            cooling_duty: 0.0,
+            current_ramp_active: false,
+            motor_torque: 0.0,
+            derivatives: None,
+            oxygen_measured: 0.0,
+            air_measured: 0.0,
+            cooling_measured: 0.0,
+            time: 0.0,
+            steps_taken: 0,
+            scenario,
+        }
+    }
+
+    /// Fresh engine for the same scenario with a different master seed.
+    ///
+    /// State, sinks and the energy ledger start over; with the same seed the
+    /// rebuilt engine reproduces the original run step for step.
+    pub fn rebuilt_with_seed(&self, seed: u64) -> Self {
+        Self::new(Scenario { seed, ..self.scenario.clone() })
+    }
+
+    /// Register a telemetry sink; each step report is fanned out to all sinks.
+    pub fn add_sink(&mut self, sink: Box<dyn TelemetrySink>) {
+        self.sinks.add(sink);
+    }
+
+    /// True once the scenario's [`Scenario::steps`] have been taken.
+    pub fn is_finished(&self) -> bool {
+        self.steps_taken >= self.scenario.steps()
+    }
+
+    /// Run headlessly (no sleeping, no I/O) until the scenario duration elapses.
+    ///
+    /// With `max_wall_time` set, the run aborts with
+    /// [`BmsError::WallClockTimeout`] once the real elapsed time exceeds it,
+    /// returning the partial summary. Intended for native builds; `Instant`
+    /// is not available on `wasm32-unknown-unknown`.
+    pub fn run(&mut self, max_wall_time: Option<Duration>) -> Result<RunSummary, Box<RunAborted>> {
+        let started = Instant::now();
+        let mut summary = RunSummary::new();
+        while !self.is_finished() {
+            if let Some(limit) = max_wall_time {
+                if started.elapsed() > limit {
+                    return Err(Box::new(RunAborted {
+                        error: BmsError::WallClockTimeout { limit, simulated_time: self.time },
+                        summary,
+                    }));
+                }
+            }
+            summary.record(self.step());
+        }
+        Ok(summary)
+    }
+
+    /// Advance the free-running model by one time step.
+    pub fn step(&mut self) -> StepReport {
+        self.advance(None);
+        self.publish()
+    }
+
+    /// Advance one step as a digital twin driven by measured inputs.
+    ///
+    /// A measured load replaces the controller-computed load. After the model
+    /// prediction, measured temperatures pull the corresponding states toward
+    /// the measurement by the configured observer gains (a simple Luenberger-style
+    /// correction). Unmeasured states (hydration, manifold pressure, oxygen) are
+    /// filled in by the model.
+    pub fn update_with_measurements(&mut self, measurements: &Measurements) -> StepReport {
+        self.advance(measurements.load);
+
+        if let Some(measured) = measurements.fuel_cell_temperature {
+            let gain = self.observer_gains.fuel_cell_temperature;
+            self.fuel_cell.temperature += gain * (measured - self.fuel_cell.temperature);
+        }
+        if let Some(measured) = measurements.battery_temperature {
+            let gain = self.observer_gains.battery_temperature;
+            self.battery.temperature += gain * (measured - self.battery.temperature);
+        }
+        self.publish()
+    }
+
+    /// Slew the battery current toward `target` after a mode switch.
+    ///
+    /// Outside a transition the target is applied directly; once the ramp
+    /// reaches the target it switches off until the next mode change.
+    fn ramp_battery_current(&mut self, target: f64, dt: f64) -> f64 {
+        let rate = match self.scenario.mode_switch_slew_rate {
+            Some(rate) if self.current_ramp_active => rate,
+            _ => return target,
+        };
+        let previous = self.battery.current;
+        let max_step = rate * dt;
+        let applied = previous + (target - previous).clamp(-max_step, max_step);
+        if applied == target {
+            self.current_ramp_active = false;
+        }
+        applied
+    }
+
+    /// Build the report for the step just taken and send it to the sinks.
+    fn publish(&mut self) -> StepReport {
+        let report = self.report();
+        self.sinks.emit(&report);
+        self.checkpoint_if_due();
+        report
+    }
+
+    fn advance(&mut self, measured_load: Option<f64>) {
+        ParameterSchedule::apply_due(self);
+        let before = self.scenario.record_derivatives.then(|| self.derivative_states());
+        self.advance_models(measured_load);
+        if let Some(before) = before {
+            self.derivatives = Some(self.derivatives_since(&before));
+        }
+    }
+
+    /// State values the derivatives are taken from (ordered as in `StateDerivatives`).
+    fn derivative_states(&self) -> [f64; 5] {
+        [
+            self.fuel_cell.temperature,
+            self.fuel_cell.membrane_hydration,
+            self.air_supply.manifold.pressure,
+            self.air_supply.compressor.speed,
+            self.battery.soc,
+        ]
+    }
+
+    fn derivatives_since(&self, before: &[f64; 5]) -> StateDerivatives {
+        let dt = self.scenario.dt;
+        let after = self.derivative_states();
+        let rate = |i: usize| (after[i] - before[i]) / dt;
+        StateDerivatives {
+            fuel_cell_temperature: rate(0),
+            membrane_hydration: rate(1),
+            manifold_pressure: rate(2),
+            compressor_speed: rate(3),
+            battery_soc: rate(4),
+        }
+    }
+
+    /// Set every component's ambient to `temperature` (°C).
+    fn apply_ambient(&mut self, temperature: f64) {
+        self.fuel_cell.ambient_temp = temperature;
+        self.battery.ambient_temp = temperature;
+        let kelvin = temperature + 273.15;
+        self.air_supply.inlet_temp = kelvin;
+        self.air_supply.manifold.temperature = kelvin;
+        let inlet_vapor_pressure = self.scenario.ambient_humidity * saturation_pressure(temperature);
+        self.air_supply.manifold.inlet_vapor_fraction = inlet_vapor_pressure / self.air_supply.inlet_pressure;
+    }
+
+    fn advance_models(&mut self, measured_load: Option<f64>) {
+        let dt = self.scenario.dt;
+        if let Some(profile) = self.scenario.ambient_profile {
+            self.apply_ambient(profile.at(self.time));
+        }
+        self.time += dt;
+        self.steps_taken += 1;
+
+        // Update battery mode (hysteresis-based).
+        let was_charging = self.charging_mode;
+        self.charging_mode = self.battery_controller.update_mode(self.battery.soc);
+        if self.charging_mode != was_charging {
+            // The load loop only runs in discharge; don't carry its state across regimes.
+            self.oxygen_controller.reset();
+        }
+        let charging_current = match &mut self.charger {
+            Some(charger) if self.charging_mode => {
+                if !was_charging {
+                    charger.reset();
+                }
+                charger.charge_current(&self.battery)
+            }
+            _ => self.battery_controller.charge_command(self.charging_mode),
+        };
+
+        // Read fuel cell sensor data.
+        let mut fc_data = read_fuel_cell_sensor_noisy(&self.fuel_cell, self.time, &self.scenario.sensor_noise, &mut self.sensor_rng);
+        let temperature_fault = self.scenario.temperature_sensor_fault;
+        let mut fresh_temperature = false;
+        fc_data.temperature = self.temperature_sensor.sample(self.time, || {
+            fresh_temperature = true;
+            temperature_fault.apply(fc_data.temperature)
+        });
+        // Held readings repeat by design, so only fresh samples count towards a stuck sensor.
+        let temperature_stuck = match &mut self.stuck_sensor {
+            Some(detector) if fresh_temperature => detector.update_tracking(fc_data.temperature, self.fuel_cell.temperature),
+            Some(detector) => detector.is_stuck(),
+            None => false,
+        };
+        if let Some(adc) = self.scenario.oxygen_adc {
+            fc_data.oxygen_concentration = adc.quantize(fc_data.oxygen_concentration);
+        }
+        let sensor_fault = fc_data.validate(&PlausibilityLimits::for_fuel_cell(&self.fuel_cell)).err().or(
+            temperature_stuck.then_some(SensorFault::Stuck { field: "temperature", value: fc_data.temperature }),
+        );
+        if let (Some(fault), None) = (sensor_fault, self.sensor_fault) {
+            log::warn!("Fuel cell sensor fault at t={:.1}s: {}; cutting load", self.time, fault);
+        }
+        self.sensor_fault = sensor_fault;
+        self.oxygen_measured = fc_data.oxygen_concentration;
+
+        // Compute compressor motor torque from AirSupplyController.
+        // λ is measured from the previous step's air flow and current.
+        self.air_measured = match self.scenario.oxygen_excess_ratio_setpoint {
+            Some(_) => self.fuel_cell.oxygen_excess_ratio,
+            None => fc_data.oxygen_concentration,
+        };
+        let motor_torque = self.air_supply_controller.compute_motor_torque_for_excess_ratio(
+            self.fuel_cell.oxygen_excess_ratio,
+            fc_data.oxygen_concentration,
+            dt,
+        );
+        self.motor_torque = motor_torque;
+
+        // Estimate mass flow out and update air supply.
+        let mass_flow_out = self.fuel_cell.cathode_exhaust_flow();
+        let is_discharging = !self.charging_mode;
+        self.air_supply.update(motor_torque, dt, mass_flow_out, is_discharging);
+
+        // Compute oxygen concentration from updated manifold pressure.
+        let oxygen_concentration = self.fuel_cell.compute_oxygen_concentration_from(self.air_supply.manifold.pressure);
+
+        // Determine load using oxygen controller and disturbance.
+        let load = match measured_load {
+            Some(load) => load,
+            None if self.charging_mode => charging_current,
+            None => {
+                let noise = self.scenario.load_disturbance_noise;
+                let shape = match self.scenario.load_disturbance_shape {
+                    DisturbanceShape::Constant => 1.0,
+                    // Evaluated at the start of the step, as the console loop did.
+                    DisturbanceShape::RectifiedSine { angular_frequency } => (angular_frequency * (self.time - dt)).sin().abs(),
+                };
+                let disturbance = self.scenario.load_disturbance * shape + self.disturbance_rng.uniform(-noise, noise);
+                self.oxygen_controller.regulate_adaptive(self.scenario.oxygen_setpoint, fc_data.oxygen_concentration, dt)
+                    + disturbance
+            }
+        };
+
+        // Over-temperature trips shut the unit down until it cools to its recovery temperature.
+        let fuel_cell_tripped = self.fuel_cell_trip.update(self.fuel_cell.temperature);
+        let battery_tripped = self.battery_trip.update(self.battery.temperature);
+        let load = match &mut self.protection {
+            Some(protection) => {
+                let was_active = protection.is_active();
+                let (shed, event) = protection.apply(load, fc_data.temperature);
+                if let Some(event) = event {
+                    log::warn!("Protection at t={:.1}s: {}; shedding load to {:.2} A", self.time, event, shed);
+                } else if was_active && !protection.is_active() {
+                    log::info!("Protection cleared at t={:.1}s", self.time);
+                }
+                shed
+            }
+            None => load,
+        };
+        let protected = self.protection.as_ref().is_some_and(LoadProtection::is_active);
+        let commands = self.supervisor.update(
+            SupervisorInputs {
+                soc: self.battery.soc,
+                charge_requested: self.charging_mode,
+                fuel_cell_starting: self.fuel_cell.is_starting_up(),
+                fuel_cell_tripped,
+                battery_tripped,
+                sensor_fault: self.sensor_fault.is_some(),
+                protected,
+            },
+            dt,
+        );
+        let load = if commands.fuel_cell_enabled { load } else { 0.0 };
+
+        // With a power bus the stack and battery share the demand by power balance.
+        let split = self.scenario.power_split.map(|policy| {
+            let demand = if self.charging_mode { 0.0 } else { load };
+            let charge_request = if self.charging_mode { charging_current } else { 0.0 };
+            let limit = match &self.protection {
+                _ if !commands.fuel_cell_enabled => 0.0,
+                Some(protection) => protection.current_limit(),
+                None => f64::MAX,
+            };
+            let bus = PowerBus::new(policy);
+            bus.split(demand, charge_request, self.battery.voltage, self.fuel_cell.voltage, limit, self.converter.as_ref())
+        });
+        let load = split.map_or(load, |split| split.fuel_cell_current);
+
+        // Set cooling based on the measured temperature.
+        let cooling_temperature = match &self.fuel_cell.coolant {
+            Some(coolant) => coolant.temperature,
+            None => fc_data.temperature,
+        };
+        self.cooling_measured = cooling_temperature;
+        let duty = self.thermal_controller.update_duty(cooling_temperature, dt);
+        let duty = if commands.force_cooling { 1.0 } else { duty };
+        // The fan is switched through the HAL; the stack sees the duty only while it runs.
+        if duty > 0.0 {
+            self.cooling_fan.set_high();
+        } else {
+            self.cooling_fan.set_low();
+        }
+        self.cooling_active = self.cooling_fan.get_state();
+        // A fan stuck on runs flat out; a degraded one delivers part of its duty.
+        let commanded = if duty > 0.0 { duty } else { 1.0 };
+        self.cooling_duty = commanded * self.cooling_fan.effective_output();
+
+        // Update fuel cell state.
+        let humidity = membrane_humidity(&self.scenario, &self.air_supply, &self.fuel_cell);
+        self.fuel_cell.update(load, self.cooling_duty, oxygen_concentration, humidity, dt);
+        self.fuel_cell.update_air_ratios(self.air_supply.cathode_air_flow);
+
+        // Update battery state (signed net current, positive = charging).
+        let target_current = if !commands.battery_connected {
+            0.0
+        } else if let Some(split) = split {
+            split.battery_current
+        } else if self.charging_mode {
+            // Through a converter the battery receives the stack power less the conversion loss.
+            match &self.converter {
+                Some(converter) => converter.output_current(self.fuel_cell.voltage, self.fuel_cell.current),
+                None => charging_current,
+            }
+        } else {
+            -load * self.scenario.battery_load_share
+        };
+        if was_charging != self.charging_mode && self.scenario.mode_switch_slew_rate.is_some() {
+            self.current_ramp_active = true;
+        }
+        let applied_current = self.ramp_battery_current(target_current, dt);
+        self.battery.update(0.0, -applied_current, false, dt);
+
+        // Conversion loss is booked now and heats the stack on its next update.
+        let converting = split.is_some() || self.charging_mode;
+        self.fuel_cell.auxiliary_heat = match &self.converter {
+            Some(converter) if converting => {
+                let stack_power = self.fuel_cell.voltage * self.fuel_cell.current;
+                self.ledger.record_converter_loss(converter.loss(stack_power), dt);
+                converter.heat(stack_power)
+            }
+            _ => 0.0,
+        };
+
+        // Power balance: whatever the full battery could not absorb is exported or discarded.
+        self.ledger.record_fuel_cell(self.fuel_cell.voltage * self.fuel_cell.current, dt);
+        // The battery is booked only for what it actually absorbed or delivered.
+        let absorbed_current = self.battery.current - self.battery.rejected_current;
+        self.ledger.record_battery(self.battery.voltage * absorbed_current, dt);
+        let excess_w = self.battery.rejected_current.max(0.0) * self.battery.voltage;
+        self.ledger.record_excess(excess_w, dt, self.export.as_ref());
+    }
+
+    /// Build a report of the current state.
+    pub fn report(&self) -> StepReport {
+        let fuel_cell_power = self.fuel_cell.voltage * self.fuel_cell.current;
+        let battery_power = self.battery.voltage * self.battery.current;
+        let compressor_power = self.motor_torque * self.air_supply.compressor.speed;
+        StepReport {
+            time: self.time,
+            fuel_cell_voltage: self.fuel_cell.voltage,
+            fuel_cell_current: self.fuel_cell.current,
+            fuel_cell_temperature: self.fuel_cell.temperature,
+            hydrogen_flow: self.fuel_cell.hydrogen_flow,
+            membrane_hydration: self.fuel_cell.membrane_hydration,
+            oxygen_concentration: self.fuel_cell.oxygen_concentration,
+            oxygen_excess_ratio: self.fuel_cell.oxygen_excess_ratio,
+            air_stoichiometry: self.fuel_cell.air_stoichiometry,
+            manifold_pressure: self.air_supply.manifold.pressure,
+            compressor_speed: self.air_supply.compressor.speed,
+            battery_soc: self.battery.soc,
+            battery_voltage: self.battery.voltage,
+            battery_current: self.battery.current,
+            battery_temperature: self.battery.temperature,
+            fuel_cell_power,
+            battery_power,
+            compressor_power,
+            net_power: fuel_cell_power - battery_power - compressor_power,
+            oxygen_setpoint: self.scenario.oxygen_setpoint,
+            oxygen_measured: self.oxygen_measured,
+            air_setpoint: self.scenario.oxygen_excess_ratio_setpoint.unwrap_or(self.scenario.desired_oxygen),
+            air_measured: self.air_measured,
+            cooling_setpoint: self.scenario.cooling_threshold,
+            cooling_measured: self.cooling_measured,
+            charging_mode: self.charging_mode,
+            cooling_active: self.cooling_active,
+            derivatives: self.derivatives,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sensors::SensorConfig;
+
+    #[test]
+    fn test_engine_advances_time() {
+        let mut engine = SimulationEngine::new(Scenario::default());
+        let report = engine.step();
+        assert_eq!(report.time, 0.5);
+        assert!(!engine.is_finished());
+    }
+
+    #[test]
+    fn test_run_takes_the_scenario_step_count() {
+        // 0.1 s does not add up to exactly 1.0 s in floating point.
+        let scenario = Scenario { duration: 1.0, dt: 0.1, ..Scenario::default() };
+        assert_eq!(scenario.steps(), 10);
+        let mut engine = SimulationEngine::new(scenario);
+        engine.run(None).unwrap();
+        assert_eq!(engine.steps_taken, 10);
+    }
+
+    #[test]
+    fn test_report_powers_follow_sign_conventions() {
+        let mut engine = SimulationEngine::new(Scenario::default());
+        let report = engine.step();
+        assert_eq!(report.fuel_cell_power, report.fuel_cell_voltage * report.fuel_cell_current);
+        assert_eq!(report.battery_power, report.battery_voltage * report.battery_current);
+        let expected_net = report.fuel_cell_power - report.battery_power - report.compressor_power;
+        assert!((report.net_power - expected_net).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_report_pairs_setpoints_with_measurements() {
+        let mut engine = SimulationEngine::new(Scenario::default());
+        engine.step();
+        let sensor = crate::sensors::read_fuel_cell_sensor(&engine.fuel_cell, engine.time);
+        let temperature_before = engine.fuel_cell.temperature;
+        let report = engine.step();
+        assert_eq!(report.oxygen_setpoint, engine.scenario.oxygen_setpoint);
+        assert_eq!(report.oxygen_measured, sensor.oxygen_concentration);
+        assert_eq!(report.air_setpoint, engine.scenario.desired_oxygen);
+        assert_eq!(report.air_measured, sensor.oxygen_concentration);
+        assert_eq!(report.cooling_setpoint, engine.scenario.cooling_threshold);
+        assert_eq!(report.cooling_measured, temperature_before);
+
+        // In λ mode the air loop's pair is the excess ratio.
+        let mut engine = SimulationEngine::new(Scenario { oxygen_excess_ratio_setpoint: Some(2.0), ..Scenario::default() });
+        engine.step();
+        let lambda_before = engine.fuel_cell.oxygen_excess_ratio;
+        let report = engine.step();
+        assert_eq!(report.air_setpoint, 2.0);
+        assert_eq!(report.air_measured, lambda_before);
+    }
+
+    #[test]
+    fn test_run_completes_scenario() {
+        let mut engine = SimulationEngine::new(Scenario { duration: 10.0, ..Scenario::default() });
+        let summary = engine.run(None).unwrap();
+        assert_eq!(summary.steps, 20);
+        assert_eq!(summary.simulated_time, 10.0);
+    }
+
+    #[test]
+    fn test_run_aborts_on_wall_clock_timeout() {
+        let scenario = Scenario { dt: 1e-6, duration: 1e9, ..Scenario::default() };
+        let mut engine = SimulationEngine::new(scenario);
+        let limit = Duration::from_millis(20);
+        let aborted = engine.run(Some(limit)).unwrap_err();
+        assert!(matches!(aborted.error, BmsError::WallClockTimeout { limit: l, .. } if l == limit));
+        assert!(aborted.summary.steps > 0);
+        assert!(aborted.summary.simulated_time < 1e9);
+    }
+
+    #[test]
+    fn test_same_seed_gives_identical_traces() {
+        let scenario = Scenario { seed: 1234, load_disturbance_noise: 3.0, ..Scenario::default() };
+        let mut a = SimulationEngine::new(scenario.clone());
+        let mut b = SimulationEngine::new(scenario.clone());
+        let mut c = SimulationEngine::new(Scenario { seed: 4321, ..scenario });
+        let trace_a: Vec<StepReport> = (0..100).map(|_| a.step()).collect();
+        let trace_b: Vec<StepReport> = (0..100).map(|_| b.step()).collect();
+        let trace_c: Vec<StepReport> = (0..100).map(|_| c.step()).collect();
+        assert_eq!(trace_a, trace_b);
+        assert_ne!(trace_a, trace_c);
+    }
+
+    struct CountingSink {
+        received: std::rc::Rc<std::cell::Cell<usize>>,
+        fail: bool,
+    }
+
+    impl TelemetrySink for CountingSink {
+        fn name(&self) -> &str {
+            if self.fail { "failing" } else { "counting" }
+        }
+
+        fn emit(&mut self, _report: &StepReport) -> Result<(), BmsError> {
+            self.received.set(self.received.get() + 1);
+            if self.fail {
+                Err(BmsError::Sink { sink: self.name().to_string(), message: "disk full".to_string() })
+            } else {
+                Ok(())
+            }
+        }
+    }
+
+    #[test]
+    fn test_all_sinks_receive_reports_despite_failures() {
+        let failing_count = std::rc::Rc::new(std::cell::Cell::new(0));
+        let healthy_count = std::rc::Rc::new(std::cell::Cell::new(0));
+        let mut engine = SimulationEngine::new(Scenario::default());
+        engine.add_sink(Box::new(CountingSink { received: failing_count.clone(), fail: true }));
+        engine.add_sink(Box::new(CountingSink { received: healthy_count.clone(), fail: false }));
+        for _ in 0..10 {
+            engine.step();
+        }
+        assert_eq!(failing_count.get(), 10);
+        assert_eq!(healthy_count.get(), 10);
+        assert_eq!(engine.sinks.failures(), &[10, 0]);
+        assert!(matches!(engine.sinks.last_error(), Some(BmsError::Sink { .. })));
+    }
+
+    #[test]
+    fn test_measured_temperature_pulls_model_faster_than_free_running() {
+        let measured_temp = 30.0;
+        let mut free = SimulationEngine::new(Scenario::default());
+        let mut twin = SimulationEngine::new(Scenario::default());
+        let measurements = Measurements {
+            fuel_cell_temperature: Some(measured_temp),
+            ..Measurements::default()
+        };
+        for _ in 0..5 {
+            free.step();
+            twin.update_with_measurements(&measurements);
+        }
+        let free_error = (free.fuel_cell.temperature - measured_temp).abs();
+        let twin_error = (twin.fuel_cell.temperature - measured_temp).abs();
+        assert!(twin_error < free_error, "twin {} vs free {}", twin_error, free_error);
+    }
+
+    #[test]
+    fn test_tripped_stack_stays_down_until_recovery_temp() {
+        let mut engine = SimulationEngine::new(Scenario::default());
+        let step_at = |engine: &mut SimulationEngine, temperature: f64| {
+            engine.fuel_cell.temperature = temperature;
+            engine.step().fuel_cell_current
+        };
+        assert!(step_at(&mut engine, 50.0) > 0.0);
+        assert_eq!(step_at(&mut engine, 81.0), 0.0);
+        assert_eq!(step_at(&mut engine, 70.0), 0.0, "restarted above recovery_temp");
+        assert!(step_at(&mut engine, 60.0) > 0.0);
+        assert_eq!(engine.fuel_cell_trip.trip_count, 1);
+    }
+
+    #[test]
+    fn test_cooling_regulates_coolant_node() {
+        let scenario = Scenario {
+            cooling_target: CoolingTarget::Coolant,
+            cooling_threshold: 40.0,
+            ..Scenario::default()
+        };
+        let mut engine = SimulationEngine::new(scenario);
+        for _ in 0..1200 {
+            engine.fuel_cell.startup_elapsed = None;
+            engine.update_with_measurements(&Measurements { load: Some(10.0), ..Measurements::default() });
+        }
+        let coolant = engine.fuel_cell.coolant.as_ref().unwrap().temperature;
+        assert!((coolant - 40.0).abs() < 1.0, "coolant at {}", coolant);
+        assert!(engine.fuel_cell.temperature > coolant + 5.0, "stack should run hotter than coolant");
+    }
+
+    #[test]
+    fn test_rebuild_from_seed_reproduces_first_steps() {
+        let scenario = Scenario { load_disturbance_noise: 3.0, seed: 11, ..Scenario::default() };
+        let mut original = SimulationEngine::new(scenario);
+        let first: Vec<StepReport> = (0..5).map(|_| original.step()).collect();
+
+        let mut rebuilt = original.rebuilt_with_seed(11);
+        assert_eq!(rebuilt.time, 0.0);
+        let again: Vec<StepReport> = (0..5).map(|_| rebuilt.step()).collect();
+        assert_eq!(first, again);
+
+        let mut other = original.rebuilt_with_seed(12);
+        let different: Vec<StepReport> = (0..5).map(|_| other.step()).collect();
+        assert_ne!(first, different);
+    }
+
+    #[test]
+    fn test_scheduled_resistance_step_changes_voltage_only_afterwards() {
+        let mut baseline = SimulationEngine::new(Scenario::default());
+        let mut stepped = SimulationEngine::new(Scenario::default());
+        stepped.schedule.push(20.0, "r_internal x3", |engine| engine.fuel_cell.r_internal *= 3.0);
+        for _ in 0..60 {
+            let expected = baseline.step();
+            let report = stepped.step();
+            if report.time <= 20.0 {
+                assert_eq!(report, expected, "diverged before the change at t = {}", report.time);
+            } else {
+                assert!(report.fuel_cell_voltage < expected.fuel_cell_voltage, "no effect at t = {}", report.time);
+            }
+        }
+        assert_eq!(stepped.schedule.pending(), 0);
+        assert_eq!(stepped.schedule.applied(), &[(20.0, "r_internal x3".to_string())]);
+    }
+
+    #[test]
+    fn test_converter_loss_is_booked_while_charging() {
+        let scenario = Scenario { dc_dc_efficiency: Some(0.9), ..Scenario::default() };
+        let mut engine = SimulationEngine::new(scenario);
+        engine.battery.soc = 50.0;
+        for _ in 0..10 {
+            let report = engine.step();
+            assert!(report.charging_mode);
+            let expected_current = 0.9 * report.fuel_cell_current / engine.scenario.dc_dc_voltage_ratio;
+            assert!(engine.fuel_cell.auxiliary_heat > 0.0);
+            assert!((report.battery_current - expected_current).abs() < 1e-9);
+        }
+        let ledger = &engine.ledger;
+        assert!(ledger.converter_loss_wh > 0.0);
+        assert!((ledger.converter_loss_wh - 0.1 * ledger.fuel_cell_wh).abs() < 1e-9);
+        assert!((ledger.conversion_efficiency() - 0.9).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_mode_switch_ramps_battery_current() {
+        let scenario = Scenario { mode_switch_slew_rate: Some(4.0), ..Scenario::default() };
+        let mut engine = SimulationEngine::new(scenario);
+        engine.battery.soc = 66.0;
+        let mut previous = engine.step();
+        assert!(!previous.charging_mode);
+        let mut ramp_steps = 0;
+        for _ in 0..40 {
+            let report = engine.step();
+            if report.charging_mode != previous.charging_mode || ramp_steps > 0 {
+                if !engine.current_ramp_active {
+                    break;
+                }
+                let step = (report.battery_current - previous.battery_current).abs();
+                assert!((step - 4.0 * 0.5).abs() < 1e-9, "current jumped by {}", step);
+                ramp_steps += 1;
+            }
+            previous = report;
+        }
+        assert!(ramp_steps > 3, "expected a gradual switch into charging");
+        assert_eq!(engine.battery.current, engine.scenario.charging_current);
+    }
+
+    #[test]
+    fn test_dry_ambient_lowers_hydration_without_humidifier() {
+        let steady_hydration = |ambient_humidity: f64| {
+            let scenario = Scenario { manifold_humidity: false, humidifier: false, ambient_humidity, ..Scenario::default() };
+            let mut engine = SimulationEngine::new(scenario);
+            for _ in 0..200 {
+                engine.update_with_measurements(&Measurements { load: Some(10.0), ..Measurements::default() });
+            }
+            engine.fuel_cell.membrane_hydration
+        };
+        let humid = steady_hydration(0.9);
+        let dry = steady_hydration(0.3);
+        assert!(dry < humid, "dry {} vs humid {}", dry, humid);
+        assert!(humid < Scenario::default().humidity, "passive hydration should stay below the humidified target");
+    }
+
+
+    #[test]
+    fn test_temperature_derivative_matches_heat_balance() {
+        let scenario = Scenario { record_derivatives: true, ..Scenario::default() };
+        let mut engine = SimulationEngine::new(scenario);
+        let fc = &engine.fuel_cell;
+        let (temperature, ambient, mass) = (fc.temperature, fc.ambient_temp, fc.thermal_mass);
+        let report = engine.update_with_measurements(&Measurements { load: Some(10.0), ..Measurements::default() });
+        let rate = if report.cooling_active { engine.fuel_cell.cooling_efficiency } else { 0.7 };
+        let expected = (10.0 * 2.5 - rate * (temperature - ambient)) / mass;
+        let derivatives = report.derivatives.expect("derivatives requested");
+        assert!((derivatives.fuel_cell_temperature - expected).abs() < 1e-9);
+        assert!(SimulationEngine::new(Scenario::default()).step().derivatives.is_none());
+    }
+
+    #[test]
+    fn test_fine_time_step_tracks_coarse_run() {
+        let run = |dt: f64| {
+            let mut engine = SimulationEngine::new(Scenario { dt, ..Scenario::default() });
+            for _ in 0..(5.0 / dt).round() as usize {
+                engine.update_with_measurements(&Measurements { load: Some(10.0), ..Measurements::default() });
+            }
+            (engine.time, engine.fuel_cell.temperature, engine.battery.soc)
+        };
+        let (coarse_time, coarse_temp, coarse_soc) = run(0.5);
+        let (fine_time, fine_temp, fine_soc) = run(0.05);
+        assert!((coarse_time - fine_time).abs() < 1e-9);
+        assert!((coarse_temp - fine_temp).abs() < 0.5, "temperature {} vs {}", coarse_temp, fine_temp);
+        assert!((coarse_soc - fine_soc).abs() < 0.5, "soc {} vs {}", coarse_soc, fine_soc);
+    }
+
+    #[test]
+    fn test_manifold_humidity_drives_membrane_hydration() {
+        let scenario = Scenario { ambient_humidity: 0.05, ..Scenario::default() };
+        let mut engine = SimulationEngine::new(scenario);
+        for _ in 0..120 {
+            engine.step();
+        }
+        let rh = engine.air_supply.manifold.relative_humidity();
+        // Compression concentrates the vapor, but dry ambient air stays below the humidifier target.
+        assert!(rh > 0.05 && rh < Scenario::default().humidity, "manifold RH {}", rh);
+        assert!((engine.fuel_cell.membrane_hydration - rh).abs() < 0.1);
+    }
+
+    #[test]
+    fn test_component_temperatures_track_ramped_ambient() {
+        use crate::ambient::AmbientProfile;
+
+        let ramp = AmbientProfile::Ramp { start: 20.0, rate: -0.2 };
+        let run = |ambient_profile: Option<AmbientProfile>| {
+            let mut engine = SimulationEngine::new(Scenario { ambient_profile, ..Scenario::default() });
+            engine.battery.thermal_mass = 50.0;
+            for _ in 0..100 {
+                engine.update_with_measurements(&Measurements { load: Some(0.0), ..Measurements::default() });
+            }
+            engine
+        };
+        let ramped = run(Some(ramp));
+        let fixed = run(None);
+        let ambient = ramp.at(ramped.time - ramped.scenario.dt);
+        assert_eq!(ramped.fuel_cell.ambient_temp, ambient);
+        assert_eq!(ramped.air_supply.inlet_temp, ambient + 273.15);
+        // A falling ambient pulls both components below the fixed-ambient run.
+        assert!(ramped.fuel_cell.temperature < fixed.fuel_cell.temperature);
+        assert!(ramped.battery.temperature < fixed.battery.temperature);
+    }
+
+    #[test]
+    fn test_air_supply_holds_excess_ratio_setpoint() {
+        let scenario = Scenario {
+            oxygen_excess_ratio_setpoint: Some(2.0),
+            air_supply_gains: crate::scenario::PidGains::new(0.002, 0.0004, 0.0),
+            ..Scenario::default()
+        };
+        let mut engine = SimulationEngine::new(scenario);
+        let mut report = engine.step();
+        for _ in 0..400 {
+            report = engine.update_with_measurements(&Measurements { load: Some(1.0), ..Measurements::default() });
+        }
+        assert!((report.oxygen_excess_ratio - 2.0).abs() < 0.2, "lambda {}", report.oxygen_excess_ratio);
+    }
+
+    #[test]
+    fn test_power_bus_shares_load_by_power_balance() {
+        use crate::simulation::bus::SplitPolicy;
+
+        let policy = SplitPolicy::Proportional { fuel_cell_share: 0.5 };
+        let mut engine = SimulationEngine::new(Scenario { power_split: Some(policy), ..Scenario::default() });
+        engine.step();
+        let (fc_voltage, bus_voltage) = (engine.fuel_cell.voltage, engine.battery.voltage);
+        let report = engine.update_with_measurements(&Measurements { load: Some(1.0), ..Measurements::default() });
+        // Half of the 1 A demand power comes from each source.
+        assert!((report.fuel_cell_current * fc_voltage - 0.5 * bus_voltage).abs() < 1e-9);
+        assert!((report.battery_current + 0.5).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_run_simulation_records_every_step() {
+        let results = run_simulation(Scenario::default(), 60.0, 0.5);
+        assert_eq!(results.len(), 120);
+        let time = results.time();
+        assert!((time[119] - 60.0).abs() < 1e-9);
+        assert_eq!(results.series(Channel::BatterySoc).len(), 120);
+    }
+
+    #[test]
+    fn test_run_simulation_is_bit_identical_for_a_seed() {
+        let scenario = Scenario { seed: 99, load_disturbance_noise: 2.0, ..Scenario::default() };
+        let a = run_simulation(scenario.clone(), 30.0, 0.5);
+        let b = run_simulation(scenario, 30.0, 0.5);
+        let bits = |results: &SimulationResults| -> Vec<u64> {
+            results.series(Channel::FuelCellVoltage).iter().chain(&results.series(Channel::BatterySoc)).map(|v| v.to_bits()).collect()
+        };
+        assert_eq!(bits(&a), bits(&b));
+        assert_eq!(serde_json::to_string(&a).unwrap(), serde_json::to_string(&b).unwrap());
+    }
+
+    #[test]
+    fn test_results_export_as_csv() {
+        let results = run_simulation(Scenario::default(), 1.5, 0.5);
+        let csv = results.to_csv();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines.len(), 4);
+        assert!(lines[0].starts_with("time,fuel_cell_voltage,"));
+        let row: Vec<f64> = lines[3].split(',').map(|v| v.parse().unwrap()).collect();
+        assert_eq!(row.len(), 8);
+        assert_eq!(row[0], results.reports[2].time);
+        assert_eq!(row[5], results.reports[2].battery_soc);
+    }
+
+    #[test]
+    fn test_temperature_sensor_bias_starts_cooling_early() {
+        use crate::sensors::Calibration;
+
+        let first_cooling_step = |sensor_noise: SensorConfig| {
+            let mut engine = SimulationEngine::new(Scenario { sensor_noise, cooling_threshold: 46.0, ..Scenario::default() });
+            (0..200).position(|_| engine.step().cooling_active).expect("cooling starts")
+        };
+        let exact = first_cooling_step(SensorConfig::default());
+        let biased = first_cooling_step(SensorConfig { temp_calibration: Calibration::new(2.0, 1.0), ..SensorConfig::default() });
+        assert!(biased < exact, "biased {} vs exact {}", biased, exact);
+    }
+
+    #[test]
+    fn test_voltage_scale_error_leaves_the_air_loops_settled() {
+        use crate::sensors::Calibration;
+
+        let run = |voltage_calibration: Calibration| {
+            let sensor_noise = SensorConfig { voltage_calibration, ..SensorConfig::default() };
+            let mut engine = SimulationEngine::new(Scenario { sensor_noise, ..Scenario::default() });
+            let reports: Vec<StepReport> = (0..240).map(|_| engine.step()).collect();
+            assert!(engine.sensor_fault.is_none(), "a 5 % scale error is still plausible");
+            reports
+        };
+        let exact = run(Calibration::default());
+        let scaled = run(Calibration::new(0.0, 0.95));
+
+        // Voltage only feeds the plausibility check, so the oxygen and air loops
+        // follow the exact-sensor run and the compressor has settled by the end.
+        for (exact, scaled) in exact.iter().zip(&scaled) {
+            assert!((exact.oxygen_measured - scaled.oxygen_measured).abs() < 1e-9);
+            assert!((exact.compressor_speed - scaled.compressor_speed).abs() < 1e-6);
+        }
+        let tail: Vec<f64> = scaled[200..].iter().map(|report| report.compressor_speed).collect();
+        let (min, max) = tail.iter().fold((f64::INFINITY, 0.0f64), |(min, max), &speed| (min.min(speed), max.max(speed)));
+        assert!(max - min < 0.02 * max, "compressor speed still moving: {:.1}..{:.1}", min, max);
+    }
+
+    #[test]
+    fn test_sensor_fault_cuts_load_and_forces_cooling() {
+        use crate::sensors::Calibration;
+
+        // A +100 °C bias reads far above any plausible stack temperature.
+        let sensor_noise = SensorConfig { temp_calibration: Calibration::new(100.0, 1.0), ..SensorConfig::default() };
+        let mut engine = SimulationEngine::new(Scenario { sensor_noise, ..Scenario::default() });
+        let report = engine.step();
+        assert!(matches!(engine.sensor_fault, Some(SensorFault::OverTemperature { .. })));
+        assert_eq!(report.fuel_cell_current, 0.0);
+        assert!(report.cooling_active);
+    }
+
+    #[test]
+    fn test_stuck_low_temperature_sensor_faults_the_supervisor() {
+        use crate::hal::FaultMode;
+        use crate::supervisor::SystemState;
+
+        // Stuck at 20 °C the reading is plausible and never asks for cooling.
+        let scenario = Scenario { temperature_sensor_fault: FaultMode::StuckAt(20.0), ..Scenario::default() };
+        let mut unchecked = SimulationEngine::new(scenario.clone());
+        assert!((0..40).all(|_| !unchecked.step().cooling_active));
+
+        let mut engine = SimulationEngine::new(Scenario { stuck_sensor_window: Some(10), ..scenario });
+        let reports: Vec<StepReport> = (0..12).map(|_| engine.step()).collect();
+        assert!(reports[..10].iter().all(|report| report.fuel_cell_current > 0.0));
+        assert!(matches!(engine.sensor_fault, Some(SensorFault::Stuck { field: "temperature", .. })));
+        assert_eq!(engine.supervisor.state, SystemState::Fault);
+        let last = reports.last().unwrap();
+        assert_eq!(last.fuel_cell_current, 0.0);
+        assert!(last.cooling_active);
+    }
+
+    #[test]
+    fn test_slow_temperature_sensor_holds_cooling_input() {
+        let mut engine = SimulationEngine::new(Scenario { temperature_sample_period: 2.0, ..Scenario::default() });
+        let measured: Vec<f64> = (0..8)
+            .map(|_| {
+                engine.step();
+                engine.cooling_measured
+            })
+            .collect();
+        // dt = 0.5 s, so each reading is held for four steps.
+        assert!(measured[..4].iter().all(|&t| t == measured[0]));
+        assert!(measured[4..].iter().all(|&t| t == measured[4]));
+        assert_ne!(measured[0], measured[4]);
+    }
+
+    #[test]
+    fn test_oxygen_adc_quantizes_the_controller_input() {
+        use crate::hal::AdcRange;
+
+        let adc = AdcRange::new(0.0, 1.0, 4);
+        let mut engine = SimulationEngine::new(Scenario { oxygen_adc: Some(adc), ..Scenario::default() });
+        for _ in 0..10 {
+            engine.step();
+            assert_eq!(adc.quantize(engine.oxygen_measured), engine.oxygen_measured);
+        }
+        let invalid = Scenario { oxygen_adc: Some(AdcRange::new(1.0, 1.0, 20)), ..Scenario::default() };
+        assert_eq!(invalid.validate().unwrap_err().len(), 2);
+    }
+
+    #[test]
+    fn test_cooling_hysteresis_reduces_fan_toggling() {
+        let toggles = |cooling_hysteresis: f64| {
+            let mut engine = SimulationEngine::new(Scenario { cooling_hysteresis, ..Scenario::default() });
+            let states: Vec<bool> = (0..600).map(|_| engine.step().cooling_active).collect();
+            states.windows(2).filter(|pair| pair[0] != pair[1]).count()
+        };
+        let (single, banded) = (toggles(0.0), toggles(4.0));
+        assert!(banded < single, "banded {} vs single threshold {}", banded, single);
+    }
+
+    #[test]
+    fn test_proportional_cooling_modulates_the_fan() {
+        use crate::control::ThermalMode;
+
+        let cooling_mode = ThermalMode::Proportional { setpoint: 42.0, gain: 0.5 };
+        let mut engine = SimulationEngine::new(Scenario { cooling_mode, ..Scenario::default() });
+        let duties: Vec<f64> = (0..800)
+            .map(|_| {
+                engine.step();
+                engine.cooling_duty
+            })
+            .collect();
+        // Once settled the fan runs part-speed and its command moves smoothly,
+        // where bang-bang would jump between 0 and 1.
+        let late = &duties[400..];
+        assert!(late.iter().all(|&duty| duty > 0.0 && duty < 1.0));
+        let largest_step = late.windows(2).map(|pair| (pair[1] - pair[0]).abs()).fold(0.0, f64::max);
+        assert!(largest_step < 0.05, "duty jumped by {}", largest_step);
+        assert!((engine.fuel_cell.temperature - 43.2).abs() < 0.5);
+    }
+
+    #[test]
+    fn test_supervisor_tracks_the_run() {
+        use crate::supervisor::SystemState;
+
+        let mut engine = SimulationEngine::new(Scenario::default());
+        let mut states = Vec::new();
+        for _ in 0..600 {
+            engine.step();
+            if states.last() != Some(&engine.supervisor.state) {
+                states.push(engine.supervisor.state);
+            }
+        }
+        assert_eq!(&states[..3], [SystemState::Normal, SystemState::Discharging, SystemState::Charging]);
+
+        let mut engine = SimulationEngine::new(Scenario::default());
+        engine.fuel_cell.temperature = 90.0;
+        assert_eq!(engine.step().fuel_cell_current, 0.0);
+        assert_eq!(engine.supervisor.state, SystemState::Fault);
+    }
+
+    #[test]
+    fn test_protection_sheds_load_then_recovers() {
+        use crate::supervisor::{ProtectionLimits, SystemState};
+
+        let mut engine = SimulationEngine::new(Scenario { protection: Some(ProtectionLimits::default()), ..Scenario::default() });
+        engine.fuel_cell.temperature = 72.0;
+        let report = engine.step();
+        assert_eq!(engine.supervisor.state, SystemState::Protected);
+        assert!(report.cooling_active);
+        let unprotected = SimulationEngine::new(Scenario::default()).step();
+        assert!(report.fuel_cell_current < unprotected.fuel_cell_current);
+
+        let recovered = (0..2000).find(|_| {
+            engine.step();
+            engine.supervisor.state != SystemState::Protected
+        });
+        assert!(recovered.is_some(), "never left protection");
+        assert!(engine.fuel_cell.temperature <= 65.0 + 1.0);
+        assert_eq!(engine.protection.as_ref().unwrap().events, 1);
+    }
+
+    #[test]
+    fn test_over_current_protection_caps_the_stack_current() {
+        use crate::supervisor::ProtectionLimits;
+
+        let protection = ProtectionLimits { max_current: 6.0, ..ProtectionLimits::default() };
+        let mut engine = SimulationEngine::new(Scenario { protection: Some(protection), ..Scenario::default() });
+        for _ in 0..50 {
+            assert!(engine.step().fuel_cell_current <= 6.0 + 1e-9);
+        }
+        assert_eq!(engine.supervisor.state, crate::supervisor::SystemState::Protected);
+    }
+
+    #[test]
+    fn test_default_protection_limits_stay_inactive_in_normal_operation() {
+        use crate::supervisor::{ProtectionLimits, SystemState};
+
+        let mut protected = SimulationEngine::new(Scenario { protection: Some(ProtectionLimits::default()), ..Scenario::default() });
+        let mut unprotected = SimulationEngine::new(Scenario::default());
+        for _ in 0..600 {
+            assert_eq!(protected.step(), unprotected.step());
+            assert_ne!(protected.supervisor.state, SystemState::Protected);
+        }
+        assert_eq!(protected.protection.as_ref().unwrap().events, 0);
+    }
+
+    #[test]
+    fn test_cathode_channel_lags_and_depletes_the_measured_oxygen() {
+        use crate::simulation::cathode::CathodeConfig;
+
+        let mut instant = SimulationEngine::new(Scenario::default());
+        let mut channel = SimulationEngine::new(Scenario { cathode: Some(CathodeConfig::default()), ..Scenario::default() });
+        instant.step();
+        channel.step();
+        // One step in, the channel has not yet depleted to its steady state.
+        let cathode = channel.fuel_cell.cathode.as_ref().unwrap();
+        let inlet = channel.fuel_cell.compute_oxygen_concentration_from(channel.air_supply.manifold.pressure);
+        assert!(cathode.concentration > cathode.steady_state(inlet, channel.fuel_cell.current));
+        for _ in 0..100 {
+            instant.step();
+            channel.step();
+        }
+        assert!(channel.fuel_cell.oxygen_concentration < instant.fuel_cell.oxygen_concentration);
+        assert!(channel.oxygen_measured < instant.oxygen_measured);
+
+        let invalid = Scenario { cathode: Some(CathodeConfig { time_constant: -1.0, ..CathodeConfig::default() }), ..Scenario::default() };
+        assert_eq!(invalid.validate().unwrap_err().len(), 1);
+    }
+
+    #[test]
+    fn test_console_preset_reproduces_the_console_loop() {
+        let mut console = SimulationEngine::new(Scenario::console());
+        let mut default = SimulationEngine::new(Scenario::default());
+        // sin(0) = 0: the first step carries no disturbance at all.
+        let first = console.step();
+        assert!((default.step().fuel_cell_current - first.fuel_cell_current - 10.0).abs() < 1e-9);
+        assert_eq!(first.battery_current, -0.5 * first.fuel_cell_current);
+        let second = console.step();
+        let expected = 10.0 * (1.0f64).sin().abs();
+        assert!((second.fuel_cell_current - first.fuel_cell_current - expected).abs() < 0.5, "{:?}", second);
+    }
+
+    #[test]
+    fn test_ledger_balances_at_full_charge() {
+        let mut engine = SimulationEngine::new(Scenario::default());
+        engine.battery.soc = 100.0;
+        // Keep charging a full battery.
+        engine.battery_controller = BatteryController::new(100.0, f64::INFINITY, 8.0);
+        let mut commanded_wh = 0.0;
+        for _ in 0..20 {
+            let report = engine.step();
+            assert!(report.charging_mode);
+            commanded_wh += report.battery_power * engine.scenario.dt / 3600.0;
+        }
+        let ledger = &engine.ledger;
+        assert_eq!(engine.battery.soc, 100.0);
+        assert!(ledger.battery_charged_wh.abs() < 1e-12, "{:?}", ledger);
+        let booked_wh = ledger.battery_charged_wh + ledger.exported_wh + ledger.discarded_wh;
+        assert!(commanded_wh > 0.0 && (booked_wh - commanded_wh).abs() < 1e-9, "{} vs {}", booked_wh, commanded_wh);
+    }
+
+
+    #[test]
+    fn test_steady_noise_free_temperature_is_not_stuck() {
+        let mut engine = SimulationEngine::new(Scenario { stuck_sensor_window: Some(10), ..Scenario::default() });
+        for _ in 0..40 {
+            // Pin the stack at a steady temperature; a healthy noise-free reading then never changes.
+            engine.fuel_cell.temperature = 40.0;
+            engine.step();
+        }
+        assert_eq!(engine.sensor_fault, None);
+    }
+}
@@ -0,0 +1,72 @@
+use std::fmt;
+use std::time::Duration;
+
+/// Errors reported by the simulator (configuration, runtime and I/O).
+#[derive(Debug, Clone, PartialEq)]
+pub enum BmsError {
+    /// A parameter that must be strictly positive is zero or negative.
+    NonPositive { field: &'static str, value: f64 },
+    /// A lower/upper threshold pair where the lower bound exceeds the upper one.
+    InvertedThresholds { field: &'static str, lower: f64, upper: f64 },
+    /// A parameter outside its allowed range.
+    OutOfRange { field: &'static str, value: f64, min: f64, max: f64 },
+    /// A headless run exceeded its wall-clock budget.
+    WallClockTimeout { limit: Duration, simulated_time: f64 },
+    /// A command-line argument that could not be understood.
+    InvalidArgument { argument: String, reason: &'static str },
+    /// A telemetry sink failed to accept a report.
+    Sink { sink: String, message: String },
+    /// A snapshot or checkpoint could not be written or read.
+    Persistence { path: String, message: String },
+    /// A config file could not be read or parsed.
+    Config { path: String, message: String },
+    /// State could not be encoded to or decoded from JSON.
+    Serialization { message: String },
+    /// A lookup table whose axes or data are malformed.
+    InvalidTable { table: &'static str, reason: &'static str },
+    /// A relay autotune experiment did not settle into a sustained oscillation.
+    AutotuneFailed { steps: usize },
+    /// No sensor or actuator is registered under this name.
+    UnknownDevice { name: String },
+}
+
+impl fmt::Display for BmsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BmsError::NonPositive { field, value } => {
+                write!(f, "{} must be positive (got {})", field, value)
+            }
+            BmsError::InvertedThresholds { field, lower, upper } => {
+                write!(f, "{}: lower threshold {} is above upper threshold {}", field, lower, upper)
+            }
+            BmsError::OutOfRange { field, value, min, max } => {
+                write!(f, "{} = {} is outside [{}, {}]", field, value, min, max)
+            }
+            BmsError::WallClockTimeout { limit, simulated_time } => {
+                write!(f, "run aborted after exceeding wall-clock limit of {:?} (simulated {:.2} s)", limit, simulated_time)
+            }
+            BmsError::InvalidArgument { argument, reason } => {
+                write!(f, "invalid argument `{}`: {}", argument, reason)
+            }
+            BmsError::Sink { sink, message } => {
+                write!(f, "telemetry sink `{}` failed: {}", sink, message)
+            }
+            BmsError::Persistence { path, message } => {
+                write!(f, "snapshot `{}`: {}", path, message)
+            }
+            BmsError::Config { path, message } => write!(f, "config `{}`: {}", path, message),
+            BmsError::Serialization { message } => {
+                write!(f, "serialization failed: {}", message)
+            }
+            BmsError::InvalidTable { table, reason } => {
+                write!(f, "invalid {} table: {}", table, reason)
+            }
+            BmsError::AutotuneFailed { steps } => {
+                write!(f, "relay autotune found no sustained oscillation within {} steps", steps)
+            }
+            BmsError::UnknownDevice { name } => write!(f, "no device named `{}`", name),
+        }
+    }
+}
+
+impl std::error::Error for BmsError {}
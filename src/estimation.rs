@@ -0,0 +1,110 @@
+use crate::simulation::Battery;
+
+/// SoC estimate by integrating the measured battery current.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CoulombCounter {
+    /// Estimated SoC (%).
+    pub soc: f64,
+    pub capacity_ah: f64,
+    /// Offset added to every current reading (A), e.g. a sensor calibration error.
+    pub current_bias: f64,
+}
+
+impl CoulombCounter {
+    pub fn new(initial_soc: f64, capacity_ah: f64) -> Self {
+        Self {
+            soc: initial_soc,
+            capacity_ah,
+            current_bias: 0.0,
+        }
+    }
+
+    /// Integrate a current reading (A, positive = charging) over `dt` seconds.
+    pub fn update(&mut self, current: f64, dt: f64) -> f64 {
+        let measured = current + self.current_bias;
+        self.soc = (self.soc + measured * dt / (self.capacity_ah * 3600.0) * 100.0).clamp(0.0, 100.0);
+        self.soc
+    }
+}
+
+/// Flags a SoC estimator that has drifted away from the voltage-implied SoC.
+///
+/// The warning trips once the discrepancy has exceeded `threshold` for
+/// `hold_time` seconds without interruption, so transients (where the IR
+/// correction is poor) do not trigger a recalibration.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DivergenceDetector {
+    /// Allowed |estimated − voltage-implied| SoC gap (percentage points).
+    pub threshold: f64,
+    /// Time the gap must persist before warning (s).
+    pub hold_time: f64,
+    /// Time the gap has currently been above the threshold (s).
+    pub exceeded_for: f64,
+    /// Latched until `reset`.
+    pub diverged: bool,
+}
+
+impl DivergenceDetector {
+    pub fn new(threshold: f64, hold_time: f64) -> Self {
+        Self {
+            threshold,
+            hold_time,
+            exceeded_for: 0.0,
+            diverged: false,
+        }
+    }
+
+    /// Compare an estimate with `battery`'s voltage-implied SoC; returns true once diverged.
+    pub fn update(&mut self, estimated_soc: f64, battery: &Battery, dt: f64) -> bool {
+        let gap = (estimated_soc - battery.voltage_implied_soc()).abs();
+        if gap > self.threshold {
+            self.exceeded_for += dt;
+        } else {
+            self.exceeded_for = 0.0;
+        }
+        if !self.diverged && self.exceeded_for >= self.hold_time {
+            self.diverged = true;
+            log::warn!("SoC estimate diverged from voltage-implied SoC by {:.1} points; recalibrate", gap);
+        }
+        self.diverged
+    }
+
+    /// Clear the warning, e.g. after recalibrating the estimator.
+    pub fn reset(&mut self) {
+        self.exceeded_for = 0.0;
+        self.diverged = false;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(bias: f64) -> (DivergenceDetector, usize) {
+        let dt = 0.5;
+        let mut battery = Battery::new();
+        battery.soc = 90.0;
+        let mut counter = CoulombCounter::new(battery.soc, battery.capacity_ah);
+        counter.current_bias = bias;
+        let mut detector = DivergenceDetector::new(5.0, 5.0);
+        for step in 0..300 {
+            battery.update(0.0, 1.0, false, 0.5);
+            let estimate = counter.update(battery.current, dt);
+            if detector.update(estimate, &battery, dt) {
+                return (detector, step);
+            }
+        }
+        (detector, 300)
+    }
+
+    #[test]
+    fn test_biased_counter_trips_divergence_warning() {
+        let (unbiased, _) = run(0.0);
+        assert!(!unbiased.diverged, "an accurate counter must not trip");
+
+        let (biased, step) = run(1.0);
+        assert!(biased.diverged);
+        // 1 A of bias drifts 0.1 points per step: ~50 steps to 5 points, plus the 10-step hold.
+        assert!(step >= 55, "tripped too early at step {}", step);
+    }
+}
@@ -1,3 +1,12 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::BmsError;
+
+#[cfg(feature = "embedded-hal")]
+pub mod embedded;
+
 // Define a generic sensor trait.
 pub trait Sensor {
     type Output;
@@ -11,12 +20,126 @@ pub trait DigitalOutput {
     fn get_state(&self) -> bool;
 }
 
+// Define a trait for analog outputs (e.g. a PWM-driven fan).
+pub trait AnalogOutput {
+    /// Command a duty cycle (0-1).
+    fn set_duty(&mut self, duty: f64);
+    fn get_duty(&self) -> f64;
+}
+
+/// Input span and resolution of an analog-to-digital converter.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct AdcRange {
+    /// Value read as raw code 0.
+    pub min: f64,
+    /// Value read as the full-scale code.
+    pub max: f64,
+    /// Resolution in bits (1-16).
+    pub bits: u8,
+}
+
+impl AdcRange {
+    pub fn new(min: f64, max: f64, bits: u8) -> Self {
+        Self { min, max, bits }
+    }
+
+    /// Highest raw code, `2^bits - 1`.
+    pub fn full_scale(&self) -> u16 {
+        ((1u32 << self.bits.clamp(1, 16)) - 1) as u16
+    }
+
+    /// Nearest raw code for `value`, saturating at the range ends; NaN reads as 0.
+    pub fn to_raw(&self, value: f64) -> u16 {
+        let fraction = ((value - self.min) / (self.max - self.min)).clamp(0.0, 1.0);
+        if fraction.is_nan() {
+            return 0;
+        }
+        (fraction * self.full_scale() as f64).round() as u16
+    }
+
+    /// Engineering value of a raw code.
+    pub fn to_scaled(&self, raw: u16) -> f64 {
+        self.min + raw as f64 / self.full_scale() as f64 * (self.max - self.min)
+    }
+
+    /// `value` as it reads after conversion.
+    pub fn quantize(&self, value: f64) -> f64 {
+        self.to_scaled(self.to_raw(value))
+    }
+}
+
+// Define a trait for analog inputs read through an ADC.
+pub trait AnalogInput {
+    fn range(&self) -> AdcRange;
+    fn read_raw(&self) -> u16;
+    fn read_scaled(&self) -> f64 {
+        self.range().to_scaled(self.read_raw())
+    }
+}
+
+// A simulated analog sensor quantizing a closure's value.
+pub struct SimulatedAnalogInput<F>
+where
+    F: Fn() -> f64,
+{
+    pub read_fn: F,
+    pub range: AdcRange,
+}
+
+impl<F> AnalogInput for SimulatedAnalogInput<F>
+where
+    F: Fn() -> f64,
+{
+    fn range(&self) -> AdcRange {
+        self.range
+    }
+    fn read_raw(&self) -> u16 {
+        self.range.to_raw((self.read_fn)())
+    }
+}
+
+/// Fault injected into a simulated sensor at runtime. Actuator faults go
+/// through an [`ActuatorFault`] wrapper instead.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub enum FaultMode {
+    #[default]
+    Healthy,
+    /// Reading frozen at this value.
+    StuckAt(f64),
+    /// Broken wiring: the reading is NaN.
+    OpenCircuit,
+    /// Reading this much high.
+    Offset(f64),
+}
+
+impl FaultMode {
+    /// What a sensor with this fault reports when the true value is `reading`.
+    pub fn apply(self, reading: f64) -> f64 {
+        match self {
+            FaultMode::Healthy => reading,
+            FaultMode::StuckAt(value) => value,
+            FaultMode::OpenCircuit => f64::NAN,
+            FaultMode::Offset(offset) => reading + offset,
+        }
+    }
+}
+
 // A simulated temperature sensor using a closure.
 pub struct SimulatedTemperatureSensor<F>
 where
     F: Fn() -> f64,
 {
     pub read_fn: F,
+    pub fault: FaultMode,
+}
+
+impl<F> SimulatedTemperatureSensor<F>
+where
+    F: Fn() -> f64,
+{
+    pub fn new(read_fn: F) -> Self {
+        Self { read_fn, fault: FaultMode::Healthy }
+    }
 }
 
 impl<F> Sensor for SimulatedTemperatureSensor<F>
@@ -25,12 +148,14 @@ where
 {
     type Output = f64;
     fn read(&self) -> Self::Output {
-        (self.read_fn)()
+        self.fault.apply((self.read_fn)())
     }
 }
 
 // A simulated digital actuator (e.g., a cooling fan).
+#[derive(Debug, Clone, PartialEq)]
 pub struct SimulatedActuator {
+    /// Last commanded state.
     pub state: bool,
 }
 
@@ -40,21 +165,204 @@ impl SimulatedActuator {
     }
 }
 
+impl Default for SimulatedActuator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl DigitalOutput for SimulatedActuator {
     fn set_high(&mut self) {
         self.state = true;
-        println!("Actuator set to HIGH");
+        log::debug!("Actuator set to HIGH");
     }
     fn set_low(&mut self) {
         self.state = false;
-        println!("Actuator set to LOW");
+        log::debug!("Actuator set to LOW");
     }
     fn get_state(&self) -> bool {
         self.state
     }
 }
 
-// A higher-level hardware interface combining a sensor and an actuator.
+/// Flags a sensor whose reading is NaN or has not moved by more than
+/// `tolerance` for `window` consecutive reads.
+///
+/// A live sensor on a running stack always shows some change; a frozen one is
+/// stuck or disconnected. Fed through [`update_tracking`](Self::update_tracking),
+/// a frozen read only counts while the true value has moved away from it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StuckSensorDetector {
+    pub window: usize,
+    pub tolerance: f64,
+    reference: Option<f64>,
+    /// True value when `reference` was read, if tracked.
+    #[serde(default)]
+    actual_reference: Option<f64>,
+    unchanged: usize,
+}
+
+impl StuckSensorDetector {
+    pub fn new(window: usize, tolerance: f64) -> Self {
+        Self { window, tolerance, reference: None, actual_reference: None, unchanged: 0 }
+    }
+
+    /// Feed one reading; true once the sensor is judged faulty.
+    pub fn update(&mut self, reading: f64) -> bool {
+        self.observe(reading, None)
+    }
+
+    /// Feed one reading with the value it should show. A frozen reading only
+    /// counts while `actual` is more than `tolerance` from where it stood when
+    /// the reading froze, so a healthy sensor on a steady value never trips.
+    pub fn update_tracking(&mut self, reading: f64, actual: f64) -> bool {
+        self.observe(reading, Some(actual))
+    }
+
+    fn observe(&mut self, reading: f64, actual: Option<f64>) -> bool {
+        if reading.is_nan() {
+            return true;
+        }
+        match self.reference {
+            Some(reference) if (reading - reference).abs() <= self.tolerance => {
+                let moved = match (actual, self.actual_reference) {
+                    (Some(actual), Some(start)) => (actual - start).abs() > self.tolerance,
+                    _ => true,
+                };
+                if moved {
+                    self.unchanged += 1;
+                }
+            }
+            _ => {
+                self.reference = Some(reading);
+                self.actual_reference = actual;
+                self.unchanged = 0;
+            }
+        }
+        self.is_stuck()
+    }
+
+    /// Whether the readings fed so far have stayed put for `window` reads.
+    pub fn is_stuck(&self) -> bool {
+        self.unchanged >= self.window
+    }
+}
+
+// A simulated PWM actuator (e.g., a variable-speed cooling fan).
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SimulatedPwmActuator {
+    pub duty: f64,
+}
+
+impl SimulatedPwmActuator {
+    pub fn new() -> Self {
+        Self { duty: 0.0 }
+    }
+}
+
+impl AnalogOutput for SimulatedPwmActuator {
+    /// Duty outside 0-1 is clamped; NaN is treated as off.
+    fn set_duty(&mut self, duty: f64) {
+        self.duty = if duty.is_nan() { 0.0 } else { duty.clamp(0.0, 1.0) };
+    }
+    fn get_duty(&self) -> f64 {
+        self.duty
+    }
+}
+
+/// Fault injected by an [`ActuatorFault`] wrapper.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub enum ActuatorFaultMode {
+    /// Commands pass through unchanged.
+    #[default]
+    Healthy,
+    /// Output stays on whatever is commanded.
+    StuckOn,
+    /// Output stays off whatever is commanded.
+    StuckOff,
+    /// Commands pass through but only a fraction (0-1) of the actuation is delivered.
+    Degraded { effectiveness: f64 },
+}
+
+// Wraps an actuator and applies a fault to the commands it receives (for FMEA testing).
+#[derive(Debug, Clone, PartialEq)]
+pub struct ActuatorFault<A: DigitalOutput> {
+    pub inner: A,
+    pub mode: ActuatorFaultMode,
+}
+
+impl<A: DigitalOutput> ActuatorFault<A> {
+    pub fn new(inner: A, mode: ActuatorFaultMode) -> Self {
+        Self { inner, mode }
+    }
+
+    /// Actuation actually delivered: 0 when off, 1 when fully on, less when degraded.
+    pub fn effective_output(&self) -> f64 {
+        if !self.get_state() {
+            return 0.0;
+        }
+        match self.mode {
+            ActuatorFaultMode::Degraded { effectiveness } => effectiveness.clamp(0.0, 1.0),
+            _ => 1.0,
+        }
+    }
+}
+
+impl<A: DigitalOutput> DigitalOutput for ActuatorFault<A> {
+    fn set_high(&mut self) {
+        match self.mode {
+            ActuatorFaultMode::StuckOn | ActuatorFaultMode::StuckOff => {}
+            _ => self.inner.set_high(),
+        }
+    }
+    fn set_low(&mut self) {
+        match self.mode {
+            ActuatorFaultMode::StuckOn | ActuatorFaultMode::StuckOff => {}
+            _ => self.inner.set_low(),
+        }
+    }
+    fn get_state(&self) -> bool {
+        match self.mode {
+            ActuatorFaultMode::StuckOn => true,
+            ActuatorFaultMode::StuckOff => false,
+            _ => self.inner.get_state(),
+        }
+    }
+}
+
+// Plausible temperature range (°C) for the power-on self-test.
+pub const SELF_TEST_TEMPERATURE_RANGE: (f64, f64) = (-40.0, 150.0);
+
+// Result of one power-on self-test check.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SelfTestCheck {
+    pub component: &'static str,
+    pub passed: bool,
+    pub detail: String,
+}
+
+// Pass/fail per component from `HardwareInterface::self_test`.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct SelfTestReport {
+    pub checks: Vec<SelfTestCheck>,
+}
+
+impl SelfTestReport {
+    pub fn passed(&self) -> bool {
+        self.checks.iter().all(|c| c.passed)
+    }
+
+    pub fn failures(&self) -> impl Iterator<Item = &SelfTestCheck> {
+        self.checks.iter().filter(|c| !c.passed)
+    }
+
+    fn record(&mut self, component: &'static str, passed: bool, detail: String) {
+        self.checks.push(SelfTestCheck { component, passed, detail });
+    }
+}
+
+// A higher-level hardware interface: the stack temperature sensor and cooling
+// actuator, plus any further devices registered by name.
 pub struct HardwareInterface<T, U>
 where
     T: Sensor<Output = f64>,
@@ -62,6 +370,8 @@ where
 {
     pub temperature_sensor: T,
     pub actuator: U,
+    sensors: BTreeMap<String, Box<dyn Sensor<Output = f64>>>,
+    actuators: BTreeMap<String, Box<dyn DigitalOutput>>,
 }
 
 impl<T, U> HardwareInterface<T, U>
@@ -69,6 +379,49 @@ where
     T: Sensor<Output = f64>,
     U: DigitalOutput,
 {
+    pub fn new(temperature_sensor: T, actuator: U) -> Self {
+        Self { temperature_sensor, actuator, sensors: BTreeMap::new(), actuators: BTreeMap::new() }
+    }
+
+    /// Register a sensor under `name`, replacing any previous one.
+    pub fn add_sensor(&mut self, name: &str, sensor: impl Sensor<Output = f64> + 'static) {
+        self.sensors.insert(name.to_string(), Box::new(sensor));
+    }
+
+    /// Register an actuator under `name`, replacing any previous one.
+    pub fn add_actuator(&mut self, name: &str, actuator: impl DigitalOutput + 'static) {
+        self.actuators.insert(name.to_string(), Box::new(actuator));
+    }
+
+    pub fn read(&self, name: &str) -> Result<f64, BmsError> {
+        self.sensors.get(name).map(|sensor| sensor.read()).ok_or_else(|| unknown_device(name))
+    }
+
+    /// Switch the named actuator on or off.
+    pub fn set(&mut self, name: &str, on: bool) -> Result<(), BmsError> {
+        let actuator = self.actuators.get_mut(name).ok_or_else(|| unknown_device(name))?;
+        if on {
+            actuator.set_high();
+        } else {
+            actuator.set_low();
+        }
+        Ok(())
+    }
+
+    pub fn state(&self, name: &str) -> Result<bool, BmsError> {
+        self.actuators.get(name).map(|actuator| actuator.get_state()).ok_or_else(|| unknown_device(name))
+    }
+
+    /// Names of the registered sensors, in order.
+    pub fn sensor_names(&self) -> impl Iterator<Item = &str> {
+        self.sensors.keys().map(String::as_str)
+    }
+
+    /// Names of the registered actuators, in order.
+    pub fn actuator_names(&self) -> impl Iterator<Item = &str> {
+        self.actuators.keys().map(String::as_str)
+    }
+
     pub fn read_temperature(&self) -> f64 {
         self.temperature_sensor.read()
     }
@@ -81,6 +434,40 @@ where
     pub fn get_actuator_state(&self) -> bool {
         self.actuator.get_state()
     }
+
+    /// Power-on self-test: toggle the actuator and check it follows, then read
+    /// the temperature sensor and check the value is plausible. The actuator is
+    /// left in its original state.
+    pub fn self_test(&mut self) -> SelfTestReport {
+        let mut report = SelfTestReport::default();
+
+        let original = self.actuator.get_state();
+        self.actuator.set_high();
+        let high = self.actuator.get_state();
+        self.actuator.set_low();
+        let low = self.actuator.get_state();
+        if original {
+            self.actuator.set_high();
+        }
+        let passed = high && !low;
+        let detail = if passed {
+            "responds to set_high/set_low".to_string()
+        } else {
+            format!("state after set_high = {}, after set_low = {}", high, low)
+        };
+        report.record("actuator", passed, detail);
+
+        let temperature = self.read_temperature();
+        let (min, max) = SELF_TEST_TEMPERATURE_RANGE;
+        let passed = temperature.is_finite() && (min..=max).contains(&temperature);
+        report.record("temperature_sensor", passed, format!("read {} °C (expected {}..{})", temperature, min, max));
+
+        report
+    }
+}
+
+fn unknown_device(name: &str) -> BmsError {
+    BmsError::UnknownDevice { name: name.to_string() }
 }
 
 #[cfg(test)]
@@ -89,7 +476,7 @@ mod tests {
 
     #[test]
     fn test_simulated_temperature_sensor() {
-        let sensor = SimulatedTemperatureSensor { read_fn: || 42.0 };
+        let sensor = SimulatedTemperatureSensor::new(|| 42.0);
         assert_eq!(sensor.read(), 42.0);
     }
 
@@ -102,4 +489,153 @@ mod tests {
         actuator.set_low();
         assert!(!actuator.get_state());
     }
+
+    #[test]
+    fn test_analog_input_quantizes_to_resolution() {
+        let sensor = |bits| SimulatedAnalogInput { read_fn: || 0.2137, range: AdcRange::new(0.0, 1.0, bits) };
+        let ten = sensor(10);
+        assert_eq!(ten.read_raw(), 219);
+        assert_eq!(ten.read_scaled(), 219.0 / 1023.0);
+        let twelve = sensor(12);
+        assert_eq!(twelve.read_raw(), 875);
+        // Finer resolution, smaller error.
+        assert!((twelve.read_scaled() - 0.2137).abs() < (ten.read_scaled() - 0.2137).abs());
+        // Out-of-range values saturate.
+        assert_eq!(AdcRange::new(0.0, 1.0, 10).to_raw(1.7), 1023);
+        assert_eq!(AdcRange::new(0.0, 1.0, 10).to_raw(-0.5), 0);
+    }
+
+    #[test]
+    fn test_pwm_actuator_clamps_duty() {
+        let mut fan = SimulatedPwmActuator::new();
+        fan.set_duty(0.35);
+        assert_eq!(fan.get_duty(), 0.35);
+        fan.set_duty(1.5);
+        assert_eq!(fan.get_duty(), 1.0);
+        fan.set_duty(f64::NAN);
+        assert_eq!(fan.get_duty(), 0.0);
+    }
+
+    #[test]
+    fn test_stuck_off_actuator_ignores_set_high() {
+        let mut actuator = ActuatorFault::new(SimulatedActuator::new(), ActuatorFaultMode::StuckOff);
+        actuator.set_high();
+        assert!(!actuator.get_state());
+        assert_eq!(actuator.effective_output(), 0.0);
+    }
+
+    #[test]
+    fn test_stuck_on_actuator_ignores_set_low() {
+        let mut actuator = ActuatorFault::new(SimulatedActuator::new(), ActuatorFaultMode::StuckOn);
+        actuator.set_low();
+        assert!(actuator.get_state());
+    }
+
+    #[test]
+    fn test_degraded_actuator_delivers_partial_output() {
+        let mut actuator = ActuatorFault::new(SimulatedActuator::new(), ActuatorFaultMode::Degraded { effectiveness: 0.4 });
+        actuator.set_high();
+        assert!(actuator.get_state());
+        assert_eq!(actuator.effective_output(), 0.4);
+        actuator.set_low();
+        assert_eq!(actuator.effective_output(), 0.0);
+    }
+
+    #[test]
+    fn test_self_test_passes_with_healthy_hardware() {
+        let mut hw = HardwareInterface::new(SimulatedTemperatureSensor::new(|| 25.0), SimulatedActuator::new());
+        let report = hw.self_test();
+        assert!(report.passed(), "{:?}", report);
+        assert_eq!(report.checks.len(), 2);
+        assert!(!hw.get_actuator_state());
+    }
+
+    #[test]
+    fn test_self_test_reports_stuck_actuator() {
+        let mut hw = HardwareInterface::new(
+            SimulatedTemperatureSensor::new(|| 25.0),
+            ActuatorFault::new(SimulatedActuator::new(), ActuatorFaultMode::StuckOff),
+        );
+        let report = hw.self_test();
+        assert!(!report.passed());
+        let failed: Vec<&str> = report.failures().map(|c| c.component).collect();
+        assert_eq!(failed, vec!["actuator"]);
+    }
+
+    #[test]
+    fn test_self_test_reports_dead_sensor() {
+        let mut hw = HardwareInterface::new(SimulatedTemperatureSensor::new(|| f64::NAN), SimulatedActuator::new());
+        let failed: Vec<&str> = hw.self_test().failures().map(|c| c.component).collect();
+        assert_eq!(failed, vec!["temperature_sensor"]);
+    }
+
+    #[test]
+    fn test_fault_modes_propagate_through_the_interface() {
+        let actuator = ActuatorFault::new(SimulatedActuator::new(), ActuatorFaultMode::StuckOff);
+        let mut hw = HardwareInterface::new(SimulatedTemperatureSensor::new(|| 30.0), actuator);
+        hw.temperature_sensor.fault = FaultMode::Offset(5.0);
+        assert_eq!(hw.read_temperature(), 35.0);
+        hw.temperature_sensor.fault = FaultMode::OpenCircuit;
+        assert!(hw.read_temperature().is_nan());
+
+        hw.activate_actuator();
+        assert!(!hw.get_actuator_state());
+        hw.actuator.mode = ActuatorFaultMode::Healthy;
+        hw.activate_actuator();
+        assert!(hw.get_actuator_state());
+        hw.actuator.mode = ActuatorFaultMode::StuckOff;
+        assert!(!hw.get_actuator_state());
+    }
+
+    #[test]
+    fn test_stuck_low_temperature_sensor_is_caught_and_cooling_forced() {
+        use crate::simulation::FuelCell;
+        use std::cell::Cell;
+        use std::rc::Rc;
+
+        let stack_temperature = Rc::new(Cell::new(25.0));
+        let probe = Rc::clone(&stack_temperature);
+        let mut sensor = SimulatedTemperatureSensor::new(move || probe.get());
+        sensor.fault = FaultMode::StuckAt(20.0);
+        let mut hw = HardwareInterface::new(sensor, SimulatedActuator::new());
+        let mut detector = StuckSensorDetector::new(10, 1e-6);
+        let mut fuel_cell = FuelCell::new();
+        let mut fail_safe_at = None;
+        for step in 0..200 {
+            let reading = hw.read_temperature();
+            if detector.update(reading) {
+                fail_safe_at.get_or_insert(step);
+                hw.activate_actuator();
+            } else if reading > 44.0 {
+                hw.activate_actuator();
+            } else {
+                hw.deactivate_actuator();
+            }
+            let cooling_duty = if hw.get_actuator_state() { 1.0 } else { 0.0 };
+            fuel_cell.update(30.0, cooling_duty, 1.0, 0.8, 0.5);
+            stack_temperature.set(fuel_cell.temperature);
+        }
+        // The reading alone never asks for cooling; the detector does.
+        assert_eq!(fail_safe_at, Some(10));
+        assert!(hw.get_actuator_state());
+    }
+
+    #[test]
+    fn test_named_sensors_and_actuators() {
+        let mut hw = HardwareInterface::new(SimulatedTemperatureSensor::new(|| 40.0), SimulatedActuator::new());
+        hw.add_sensor("manifold_pressure", SimulatedTemperatureSensor::new(|| 120_000.0));
+        hw.add_sensor("stack_voltage", SimulatedTemperatureSensor::new(|| 55.0));
+        hw.add_actuator("vent_valve", SimulatedActuator::new());
+        hw.add_actuator("contactor", SimulatedActuator::new());
+
+        assert_eq!(hw.read("stack_voltage"), Ok(55.0));
+        hw.set("contactor", true).unwrap();
+        assert_eq!(hw.state("contactor"), Ok(true));
+        assert_eq!(hw.state("vent_valve"), Ok(false));
+        assert!(!hw.get_actuator_state());
+        assert_eq!(hw.sensor_names().collect::<Vec<_>>(), ["manifold_pressure", "stack_voltage"]);
+        assert_eq!(hw.actuator_names().collect::<Vec<_>>(), ["contactor", "vent_valve"]);
+        assert_eq!(hw.read("coolant_flow"), Err(BmsError::UnknownDevice { name: "coolant_flow".to_string() }));
+        assert!(hw.set("heater", true).is_err());
+    }
 }
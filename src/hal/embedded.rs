@@ -0,0 +1,184 @@
+//! Adapters between the HAL traits and `embedded-hal` 0.2.
+//!
+//! `GpioActuator` and `AdcSensor` wrap real peripherals so they plug into
+//! `HardwareInterface` in place of the simulated devices. `EmbeddedOutput` and
+//! `SimulatedAdc` go the other way, exposing simulated devices to driver code
+//! written against `embedded-hal`.
+
+use std::cell::RefCell;
+use std::convert::Infallible;
+use std::marker::PhantomData;
+
+use embedded_hal::adc::{Channel, OneShot};
+use embedded_hal::digital::v2::{OutputPin, StatefulOutputPin};
+
+use super::{AdcRange, AnalogInput, DigitalOutput, Sensor};
+
+/// An on/off actuator driven by a GPIO pin, assumed low at construction.
+///
+/// A failed write leaves the reported state unchanged, so `get_state` (and the
+/// self-test) shows what the pin last accepted.
+pub struct GpioActuator<P: OutputPin> {
+    pin: P,
+    state: bool,
+}
+
+impl<P: OutputPin> GpioActuator<P> {
+    pub fn new(pin: P) -> Self {
+        Self { pin, state: false }
+    }
+
+    pub fn into_inner(self) -> P {
+        self.pin
+    }
+}
+
+impl<P: OutputPin> DigitalOutput for GpioActuator<P> {
+    fn set_high(&mut self) {
+        match self.pin.set_high() {
+            Ok(()) => self.state = true,
+            Err(_) => log::warn!("GPIO set_high failed; actuator left {}", if self.state { "on" } else { "off" }),
+        }
+    }
+    fn set_low(&mut self) {
+        match self.pin.set_low() {
+            Ok(()) => self.state = false,
+            Err(_) => log::warn!("GPIO set_low failed; actuator left {}", if self.state { "on" } else { "off" }),
+        }
+    }
+    fn get_state(&self) -> bool {
+        self.state
+    }
+}
+
+/// A sensor read through a one-shot ADC channel and scaled by `range`.
+///
+/// A failed conversion reads as NaN, which the plausibility checks flag.
+pub struct AdcSensor<ADC, A, P> {
+    adc: RefCell<A>,
+    pin: RefCell<P>,
+    range: AdcRange,
+    _adc: PhantomData<ADC>,
+}
+
+impl<ADC, A, P> AdcSensor<ADC, A, P>
+where
+    A: OneShot<ADC, u16, P>,
+    P: Channel<ADC>,
+{
+    pub fn new(adc: A, pin: P, range: AdcRange) -> Self {
+        Self { adc: RefCell::new(adc), pin: RefCell::new(pin), range, _adc: PhantomData }
+    }
+
+    /// Block until the conversion completes; `None` on an ADC error.
+    fn convert(&self) -> Option<u16> {
+        let mut adc = self.adc.borrow_mut();
+        let mut pin = self.pin.borrow_mut();
+        nb::block!(adc.read(&mut *pin)).ok()
+    }
+}
+
+impl<ADC, A, P> Sensor for AdcSensor<ADC, A, P>
+where
+    A: OneShot<ADC, u16, P>,
+    P: Channel<ADC>,
+{
+    type Output = f64;
+    fn read(&self) -> Self::Output {
+        self.convert().map_or(f64::NAN, |raw| self.range.to_scaled(raw))
+    }
+}
+
+impl<ADC, A, P> AnalogInput for AdcSensor<ADC, A, P>
+where
+    A: OneShot<ADC, u16, P>,
+    P: Channel<ADC>,
+{
+    fn range(&self) -> AdcRange {
+        self.range
+    }
+    /// 0 on an ADC error; use `Sensor::read` to tell a failure from a zero reading.
+    fn read_raw(&self) -> u16 {
+        self.convert().unwrap_or(0)
+    }
+}
+
+/// Any `DigitalOutput` exposed as an `embedded-hal` output pin.
+pub struct EmbeddedOutput<A: DigitalOutput>(pub A);
+
+impl<A: DigitalOutput> OutputPin for EmbeddedOutput<A> {
+    type Error = Infallible;
+    fn set_low(&mut self) -> Result<(), Self::Error> {
+        self.0.set_low();
+        Ok(())
+    }
+    fn set_high(&mut self) -> Result<(), Self::Error> {
+        self.0.set_high();
+        Ok(())
+    }
+}
+
+impl<A: DigitalOutput> StatefulOutputPin for EmbeddedOutput<A> {
+    fn is_set_high(&self) -> Result<bool, Self::Error> {
+        Ok(self.0.get_state())
+    }
+    fn is_set_low(&self) -> Result<bool, Self::Error> {
+        Ok(!self.0.get_state())
+    }
+}
+
+/// A simulated one-shot ADC; each `AnalogChannel` is one of its inputs.
+#[derive(Debug, Default)]
+pub struct SimulatedAdc;
+
+/// Any `AnalogInput` exposed as a channel of `SimulatedAdc`.
+pub struct AnalogChannel<S: AnalogInput>(pub S);
+
+impl<S: AnalogInput> Channel<SimulatedAdc> for AnalogChannel<S> {
+    type ID = ();
+    fn channel() -> Self::ID {}
+}
+
+impl<S: AnalogInput> OneShot<SimulatedAdc, u16, AnalogChannel<S>> for SimulatedAdc {
+    type Error = Infallible;
+    fn read(&mut self, pin: &mut AnalogChannel<S>) -> nb::Result<u16, Self::Error> {
+        Ok(pin.0.read_raw())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hal::{HardwareInterface, SimulatedActuator, SimulatedAnalogInput};
+
+    #[test]
+    fn test_gpio_actuator_drops_into_hardware_interface() {
+        let range = AdcRange::new(-40.0, 150.0, 12);
+        let channel = AnalogChannel(SimulatedAnalogInput { read_fn: || 25.0, range });
+        let mut hw = HardwareInterface::new(
+            AdcSensor::new(SimulatedAdc, channel, range),
+            GpioActuator::new(EmbeddedOutput(SimulatedActuator::new())),
+        );
+        assert!(hw.self_test().passed());
+        assert!((hw.read_temperature() - 25.0).abs() <= (range.max - range.min) / range.full_scale() as f64);
+        hw.activate_actuator();
+        assert!(hw.get_actuator_state());
+        assert_eq!(hw.actuator.into_inner().is_set_high(), Ok(true));
+    }
+
+    #[test]
+    fn test_failed_conversion_reads_nan() {
+        struct FailingAdc;
+        impl OneShot<SimulatedAdc, u16, AnalogChannel<SimulatedAnalogInput<fn() -> f64>>> for FailingAdc {
+            type Error = ();
+            fn read(&mut self, _pin: &mut AnalogChannel<SimulatedAnalogInput<fn() -> f64>>) -> nb::Result<u16, ()> {
+                Err(nb::Error::Other(()))
+            }
+        }
+        let range = AdcRange::new(0.0, 1.0, 10);
+        let channel = AnalogChannel(SimulatedAnalogInput { read_fn: (|| 0.5) as fn() -> f64, range });
+        let sensor = AdcSensor::new(FailingAdc, channel, range);
+        assert!(sensor.read().is_nan());
+        assert_eq!(sensor.read_raw(), 0);
+    }
+}
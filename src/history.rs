@@ -0,0 +1,148 @@
+use std::collections::VecDeque;
+use std::mem;
+
+use crate::error::BmsError;
+use crate::telemetry::{Channel, StepReport, TelemetrySink};
+
+/// Storage precision for recorded samples. The simulation itself always runs in `f64`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Precision {
+    #[default]
+    F64,
+    /// Half the memory; values keep about 7 significant digits.
+    F32,
+}
+
+#[derive(Debug, Clone)]
+enum Samples {
+    F64(VecDeque<f64>),
+    F32(VecDeque<f32>),
+}
+
+/// Fixed-capacity ring buffer of one telemetry channel; the oldest sample is
+/// dropped once full.
+#[derive(Debug, Clone)]
+pub struct History {
+    pub channel: Channel,
+    capacity: usize,
+    samples: Samples,
+}
+
+impl History {
+    pub fn new(channel: Channel, capacity: usize, precision: Precision) -> Self {
+        let samples = match precision {
+            Precision::F64 => Samples::F64(VecDeque::with_capacity(capacity)),
+            Precision::F32 => Samples::F32(VecDeque::with_capacity(capacity)),
+        };
+        Self { channel, capacity, samples }
+    }
+
+    pub fn precision(&self) -> Precision {
+        match self.samples {
+            Samples::F64(_) => Precision::F64,
+            Samples::F32(_) => Precision::F32,
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn push(&mut self, value: f64) {
+        if self.capacity == 0 {
+            return;
+        }
+        match &mut self.samples {
+            Samples::F64(buffer) => push_bounded(buffer, value, self.capacity),
+            Samples::F32(buffer) => push_bounded(buffer, value as f32, self.capacity),
+        }
+    }
+
+    /// Record this history's channel from a step report.
+    pub fn record(&mut self, report: &StepReport) {
+        self.push(self.channel.value(report));
+    }
+
+    pub fn len(&self) -> usize {
+        match &self.samples {
+            Samples::F64(buffer) => buffer.len(),
+            Samples::F32(buffer) => buffer.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Sample `index` (0 = oldest retained), widened back to `f64`.
+    pub fn get(&self, index: usize) -> Option<f64> {
+        match &self.samples {
+            Samples::F64(buffer) => buffer.get(index).copied(),
+            Samples::F32(buffer) => buffer.get(index).map(|&v| v as f64),
+        }
+    }
+
+    /// All retained samples, oldest first.
+    pub fn values(&self) -> Vec<f64> {
+        (0..self.len()).filter_map(|i| self.get(i)).collect()
+    }
+
+    /// Bytes reserved for sample storage.
+    pub fn memory_bytes(&self) -> usize {
+        match &self.samples {
+            Samples::F64(buffer) => buffer.capacity() * mem::size_of::<f64>(),
+            Samples::F32(buffer) => buffer.capacity() * mem::size_of::<f32>(),
+        }
+    }
+}
+
+fn push_bounded<T>(buffer: &mut VecDeque<T>, value: T, capacity: usize) {
+    if buffer.len() == capacity {
+        buffer.pop_front();
+    }
+    buffer.push_back(value);
+}
+
+impl TelemetrySink for History {
+    fn name(&self) -> &str {
+        "history"
+    }
+
+    fn emit(&mut self, report: &StepReport) -> Result<(), BmsError> {
+        self.record(report);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ring_buffer_drops_oldest() {
+        let mut history = History::new(Channel::BatterySoc, 3, Precision::F64);
+        for v in 1..=5 {
+            history.push(v as f64);
+        }
+        assert_eq!(history.values(), vec![3.0, 4.0, 5.0]);
+    }
+
+    #[test]
+    fn test_f32_storage_round_trips_and_halves_memory() {
+        let capacity = 100_000;
+        let mut wide = History::new(Channel::FuelCellVoltage, capacity, Precision::F64);
+        let mut narrow = History::new(Channel::FuelCellVoltage, capacity, Precision::F32);
+        for i in 0..capacity {
+            let value = 40.0 + (i as f64 * 0.001).sin() * 12.345_678_9;
+            wide.push(value);
+            narrow.push(value);
+        }
+        for i in (0..capacity).step_by(997) {
+            let exact = wide.get(i).unwrap();
+            let stored = narrow.get(i).unwrap();
+            assert!((stored - exact).abs() <= exact.abs() * f32::EPSILON as f64);
+        }
+        assert!(narrow.memory_bytes() * 2 <= wide.memory_bytes() + mem::size_of::<f64>());
+        assert!(narrow.memory_bytes() < wide.memory_bytes());
+    }
+}
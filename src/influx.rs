@@ -0,0 +1,391 @@
+use serde::{Deserialize, Serialize};
+
+use crate::telemetry::StepReport;
+
+/// InfluxDB write API generation.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub enum InfluxApi {
+    /// 1.x `/write?db=` endpoint without authentication, using `database`
+    /// and `retention_policy`.
+    #[default]
+    V1,
+    /// 2.x `/api/v2/write?org=&bucket=` endpoint with token authentication.
+    /// The token is read from config but never written out (e.g. into snapshots).
+    V2 {
+        org: String,
+        bucket: String,
+        #[serde(skip_serializing, default)]
+        token: String,
+    },
+}
+
+/// How a metrics POST ended, which decides what happens to the batch.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WriteOutcome {
+    /// The server stored the points.
+    Accepted,
+    /// The server refused the points (a 4xx other than 429); resending the
+    /// same batch would be refused again, so it is dropped.
+    Rejected,
+    /// Network error, 5xx or 429: the batch is kept and retried.
+    Retry,
+}
+
+/// Where the GUI writes its metrics.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct InfluxConfig {
+    /// Server base URL, without the endpoint path.
+    pub base_url: String,
+    pub database: String,
+    /// Retention policy to write into; `None` uses the database default.
+    pub retention_policy: Option<String>,
+    pub api: InfluxApi,
+    /// Buffered lines that trigger a flush without waiting for the interval.
+    pub batch_size: usize,
+    /// Ticks between flushes of whatever is buffered.
+    pub flush_interval_ticks: usize,
+    /// Lines kept while the server is unreachable; the oldest are dropped beyond this.
+    pub max_buffered_lines: usize,
+    /// Longest wait between retries after failed writes (ticks).
+    pub max_backoff_ticks: usize,
+}
+
+impl InfluxConfig {
+    /// Full write endpoint, e.g. `http://localhost:8086/write?db=bms_db`.
+    pub fn write_url(&self) -> String {
+        let base_url = self.base_url.trim_end_matches('/');
+        match &self.api {
+            InfluxApi::V1 => {
+                let mut url = format!("{}/write?db={}", base_url, encode_query(&self.database));
+                if let Some(rp) = &self.retention_policy {
+                    url.push_str("&rp=");
+                    url.push_str(&encode_query(rp));
+                }
+                url
+            }
+            // Line timestamps are in nanoseconds, the v2 default, but say so explicitly.
+            InfluxApi::V2 { org, bucket, .. } => format!(
+                "{}/api/v2/write?org={}&bucket={}&precision=ns",
+                base_url,
+                encode_query(org),
+                encode_query(bucket)
+            ),
+        }
+    }
+
+    /// `Authorization` header value, if the API needs one.
+    pub fn authorization(&self) -> Option<String> {
+        match &self.api {
+            InfluxApi::V1 => None,
+            InfluxApi::V2 { token, .. } => Some(format!("Token {}", token)),
+        }
+    }
+
+    /// Whether a write response status means the points were accepted.
+    ///
+    /// Both APIs answer a successful write with 204 No Content. A 1.x server
+    /// (or a proxy in front of it) may also answer 200; 2.x never does for writes.
+    pub fn is_success(&self, status: u16) -> bool {
+        match self.api {
+            InfluxApi::V1 => status == 204 || status == 200,
+            InfluxApi::V2 { .. } => status == 204,
+        }
+    }
+
+    /// What a write response status means for the batch (see [`WriteOutcome`]).
+    pub fn outcome(&self, status: u16) -> WriteOutcome {
+        if self.is_success(status) {
+            WriteOutcome::Accepted
+        } else if status == 429 || (500..600).contains(&status) {
+            WriteOutcome::Retry
+        } else {
+            WriteOutcome::Rejected
+        }
+    }
+}
+
+/// Metrics waiting to be written, sent as one newline-joined POST per flush.
+///
+/// A flush marks the buffered lines in flight; they are only dropped once the
+/// write is accepted or rejected. After a retryable failure the whole buffer
+/// is retried with exponential backoff: 1, 2, 4, ... ticks, capped at
+/// `max_backoff_ticks`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MetricsBuffer {
+    lines: Vec<String>,
+    /// Number of leading `lines` in the current POST; 0 when none is in flight.
+    in_flight: usize,
+    ticks_since_flush: usize,
+    batch_size: usize,
+    flush_interval_ticks: usize,
+    max_lines: usize,
+    consecutive_failures: u32,
+    max_backoff_ticks: usize,
+}
+
+impl MetricsBuffer {
+    pub fn new(config: &InfluxConfig) -> Self {
+        Self {
+            lines: Vec::new(),
+            in_flight: 0,
+            ticks_since_flush: 0,
+            batch_size: config.batch_size.max(1),
+            flush_interval_ticks: config.flush_interval_ticks.max(1),
+            max_lines: config.max_buffered_lines.max(1),
+            consecutive_failures: 0,
+            max_backoff_ticks: config.max_backoff_ticks.max(1),
+        }
+    }
+
+    /// Writes that have failed in a row; 0 while the server is reachable.
+    pub fn consecutive_failures(&self) -> u32 {
+        self.consecutive_failures
+    }
+
+    /// Ticks to wait before retrying after the current run of failures.
+    pub fn backoff_ticks(&self) -> usize {
+        let exponent = self.consecutive_failures.saturating_sub(1).min(usize::BITS - 1);
+        (1usize << exponent).min(self.max_backoff_ticks)
+    }
+
+    pub fn len(&self) -> usize {
+        self.lines.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lines.is_empty()
+    }
+
+    /// Buffer one line, dropping the oldest line not in flight once full.
+    pub fn push(&mut self, line: String) {
+        self.lines.push(line);
+        if self.lines.len() > self.max_lines {
+            self.lines.remove(self.in_flight.min(self.lines.len() - 1));
+        }
+    }
+
+    /// Advance one tick; returns the POST body when a flush is due.
+    pub fn tick(&mut self) -> Option<String> {
+        self.ticks_since_flush += 1;
+        if self.in_flight > 0 || self.lines.is_empty() {
+            return None;
+        }
+        let due = if self.consecutive_failures > 0 {
+            self.ticks_since_flush >= self.backoff_ticks()
+        } else {
+            self.lines.len() >= self.batch_size || self.ticks_since_flush >= self.flush_interval_ticks
+        };
+        if !due {
+            return None;
+        }
+        self.ticks_since_flush = 0;
+        self.in_flight = self.lines.len();
+        Some(self.lines.join("\n"))
+    }
+
+    /// Record the outcome of the in-flight POST.
+    pub fn finish(&mut self, outcome: WriteOutcome) {
+        match outcome {
+            WriteOutcome::Accepted | WriteOutcome::Rejected => {
+                self.lines.drain(..self.in_flight);
+                self.consecutive_failures = 0;
+            }
+            WriteOutcome::Retry => self.consecutive_failures += 1,
+        }
+        self.in_flight = 0;
+    }
+}
+
+/// One report as an InfluxDB line-protocol point.
+///
+/// Line protocol has no representation for infinity or NaN, so non-finite
+/// fields (e.g. λ at zero current) are left out of the point.
+pub fn line_protocol(report: &StepReport, timestamp_ns: i64) -> String {
+    // Booleans as 1/0 float fields: without an `i` suffix line protocol reads
+    // them as floats, the field type these series have always had.
+    let flag = |on: bool| if on { 1.0 } else { 0.0 };
+    let fields = [
+        ("voltage", report.fuel_cell_voltage),
+        ("current", report.fuel_cell_current),
+        ("fuel_cell_temperature", report.fuel_cell_temperature),
+        ("hydration", report.membrane_hydration),
+        ("oxygen", report.oxygen_concentration),
+        ("oxygen_excess_ratio", report.oxygen_excess_ratio),
+        ("air_stoichiometry", report.air_stoichiometry),
+        ("soc", report.battery_soc),
+        ("battery_voltage", report.battery_voltage),
+        ("battery_current", report.battery_current),
+        ("battery_temp", report.battery_temperature),
+        ("manifold_pressure", report.manifold_pressure),
+        ("compressor_speed", report.compressor_speed),
+        ("fuel_cell_power", report.fuel_cell_power),
+        ("battery_power", report.battery_power),
+        ("compressor_power", report.compressor_power),
+        ("net_power", report.net_power),
+        ("oxygen_setpoint", report.oxygen_setpoint),
+        ("oxygen_measured", report.oxygen_measured),
+        ("air_setpoint", report.air_setpoint),
+        ("air_measured", report.air_measured),
+        ("cooling_setpoint", report.cooling_setpoint),
+        ("cooling_measured", report.cooling_measured),
+        ("charging_mode", flag(report.charging_mode)),
+        ("cooling_active", flag(report.cooling_active)),
+    ];
+    let fields: Vec<String> = fields
+        .iter()
+        .filter(|(_, value)| value.is_finite())
+        .map(|(name, value)| format!("{}={}", name, value))
+        .collect();
+    format!("bms_metrics,sim_id=1 {} {}", fields.join(","), timestamp_ns)
+}
+
+/// Percent-encode a query-string value (anything but unreserved characters).
+fn encode_query(value: &str) -> String {
+    let mut encoded = String::with_capacity(value.len());
+    for byte in value.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => encoded.push(byte as char),
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+impl Default for InfluxConfig {
+    fn default() -> Self {
+        Self {
+            base_url: "http://localhost:8086".to_string(),
+            database: "bms_db".to_string(),
+            retention_policy: None,
+            api: InfluxApi::V1,
+            batch_size: 20,
+            flush_interval_ticks: 10,
+            max_buffered_lines: 10_000,
+            max_backoff_ticks: 64,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_write_url_from_config() {
+        assert_eq!(InfluxConfig::default().write_url(), "http://localhost:8086/write?db=bms_db");
+        let shared = InfluxConfig {
+            base_url: "https://tsdb.example.net:8086/".to_string(),
+            database: "fleet".to_string(),
+            retention_policy: Some("one_week".to_string()),
+            ..InfluxConfig::default()
+        };
+        assert_eq!(shared.write_url(), "https://tsdb.example.net:8086/write?db=fleet&rp=one_week");
+    }
+
+    #[test]
+    fn test_v2_url_auth_and_success_codes() {
+        let config = InfluxConfig {
+            api: InfluxApi::V2 { org: "Acme Labs".to_string(), bucket: "bms".to_string(), token: "s3cret".to_string() },
+            ..InfluxConfig::default()
+        };
+        assert_eq!(config.write_url(), "http://localhost:8086/api/v2/write?org=Acme%20Labs&bucket=bms&precision=ns");
+        assert_eq!(config.authorization().as_deref(), Some("Token s3cret"));
+        assert!(config.is_success(204));
+        assert!(!config.is_success(200));
+
+        let v1 = InfluxConfig::default();
+        assert_eq!(v1.authorization(), None);
+        assert!(v1.is_success(200) && v1.is_success(204));
+        assert!(!serde_json::to_string(&config).unwrap().contains("s3cret"));
+
+        assert_eq!(v1.outcome(204), WriteOutcome::Accepted);
+        assert_eq!(config.outcome(200), WriteOutcome::Rejected);
+        for status in [400, 401, 404, 413] {
+            assert_eq!(v1.outcome(status), WriteOutcome::Rejected);
+        }
+        for status in [429, 500, 503] {
+            assert_eq!(v1.outcome(status), WriteOutcome::Retry);
+        }
+    }
+
+    #[test]
+    fn test_buffer_flushes_in_batches_and_keeps_failed_lines() {
+        let config = InfluxConfig { batch_size: 3, flush_interval_ticks: 5, ..InfluxConfig::default() };
+        let mut buffer = MetricsBuffer::new(&config);
+        buffer.push("a".to_string());
+        assert_eq!(buffer.tick(), None);
+        buffer.push("b".to_string());
+        assert_eq!(buffer.tick(), None);
+        buffer.push("c".to_string());
+        assert_eq!(buffer.tick().as_deref(), Some("a\nb\nc"));
+
+        // Lines that arrive while a POST is in flight wait for the next flush.
+        buffer.push("d".to_string());
+        assert_eq!(buffer.tick(), None);
+        buffer.finish(WriteOutcome::Retry);
+        assert_eq!(buffer.len(), 4);
+        // The first retry goes out on the next tick.
+        assert_eq!(buffer.tick().as_deref(), Some("a\nb\nc\nd"));
+        buffer.finish(WriteOutcome::Accepted);
+        assert!(buffer.is_empty());
+
+        // A partial batch still goes out after the flush interval.
+        buffer.push("e".to_string());
+        let flushes: Vec<_> = (0..5).filter_map(|_| buffer.tick()).collect();
+        assert_eq!(flushes, ["e"]);
+    }
+
+    #[test]
+    fn test_failed_writes_back_off_exponentially() {
+        let config = InfluxConfig { batch_size: 1, max_backoff_ticks: 4, ..InfluxConfig::default() };
+        let mut buffer = MetricsBuffer::new(&config);
+        buffer.push("a".to_string());
+        let mut gaps = Vec::new();
+        let mut since_send = 0;
+        for _ in 0..40 {
+            since_send += 1;
+            if buffer.tick().is_some() {
+                gaps.push(since_send);
+                since_send = 0;
+                buffer.finish(WriteOutcome::Retry);
+            }
+        }
+        assert_eq!(&gaps[..6], &[1, 1, 2, 4, 4, 4]);
+        assert_eq!(buffer.consecutive_failures() as usize, gaps.len());
+
+        while buffer.tick().is_none() {}
+        buffer.finish(WriteOutcome::Accepted);
+        assert_eq!(buffer.consecutive_failures(), 0);
+        assert!(buffer.is_empty());
+    }
+
+    #[test]
+    fn test_rejected_batch_is_dropped_and_later_lines_flow() {
+        let config = InfluxConfig { batch_size: 1, ..InfluxConfig::default() };
+        let mut buffer = MetricsBuffer::new(&config);
+        buffer.push("bad".to_string());
+        assert_eq!(buffer.tick().as_deref(), Some("bad"));
+        buffer.push("good".to_string());
+        buffer.finish(WriteOutcome::Rejected);
+        assert_eq!(buffer.consecutive_failures(), 0);
+        assert_eq!(buffer.tick().as_deref(), Some("good"));
+    }
+
+    #[test]
+    fn test_line_protocol_omits_non_finite_fields() {
+        use crate::engine::SimulationEngine;
+        use crate::scenario::Scenario;
+
+        let mut engine = SimulationEngine::new(Scenario::default());
+        engine.fuel_cell.temperature = 90.0;
+        let report = engine.step();
+        assert_eq!(report.fuel_cell_current, 0.0, "stack should be tripped");
+        assert!(report.oxygen_excess_ratio.is_infinite());
+
+        let line = line_protocol(&report, 42);
+        assert!(!line.contains("inf") && !line.contains("NaN"), "{}", line);
+        assert!(!line.contains("oxygen_excess_ratio=") && line.contains(",current=0,"));
+        assert!(line.ends_with("cooling_active=1 42"), "{}", line);
+    }
+}
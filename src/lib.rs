@@ -1,93 +1,196 @@
-mod simulation;
-mod sensors;
-mod control;
-mod hal;
-
-use simulation::{FuelCell, Battery, AirSupplySystem};
-use sensors::{read_fuel_cell_sensor, read_battery_sensor};
-use control::{OxygenController, AirSupplyController, BatteryController}; // Removed unused PidController import
+pub mod simulation;
+pub mod sensors;
+pub mod control;
+pub mod hal;
+pub mod error;
+pub mod scenario;
+pub mod config;
+pub mod engine;
+pub mod telemetry;
+pub mod rng;
+pub mod energy;
+pub mod linearize;
+pub mod history;
+pub mod schedule;
+pub mod validation;
+pub mod estimation;
+pub mod snapshot;
+pub mod ambient;
+pub mod influx;
+pub mod chart;
+pub mod supervisor;
+pub mod console;
+
+use ambient::AmbientProfile;
+use config::SimulationConfig;
+use engine::{SimulationEngine, SimulationResults};
+use influx::{line_protocol, InfluxConfig, MetricsBuffer, WriteOutcome};
+use scenario::{PidGains, Scenario};
+use sensors::read_system;
+use telemetry::{Channel, Smoother, StepReport};
 use wasm_bindgen::prelude::*; // for #[wasm_bindgen(start)]
+use web_sys::HtmlInputElement;
 use yew::prelude::*;          // for Yew components
+use gloo::file::{Blob, ObjectUrl};
 use gloo::timers::callback::Interval; // for periodic updates
-use wasm_bindgen_futures::spawn_local;
-use gloo_net::http::Request;
-use js_sys::Date;
-use wasm_bindgen::prelude::*;
-use yew::prelude::*;
+use wasm_bindgen::JsCast;
 use log::Level;
 
 /// The main GUI model for our simulation.
 struct Model {
-    fuel_cell: FuelCell,
-    battery: Battery,
-    air_supply: AirSupplySystem,
-    oxygen_controller: OxygenController,
-    air_supply_controller: AirSupplyController, // our new controller
-    battery_controller: BatteryController,       // battery SoC controller
-    charging_mode: bool,
-    cooling_active: bool,
+    engine: SimulationEngine,
+    smoother: Smoother, // Telemetry-only smoothing (the engine sees raw values)
     interval: Option<Interval>,
     debug_log: Vec<String>, // Accumulated debug output
-    simulation_time: f64,   // Elapsed simulation time in seconds
-    simulation_duration: f64, // Total simulation duration (e.g., 60 seconds)
+    seed_input: String,     // Contents of the seed field, applied on reset
+    duration_input: String, // Contents of the duration field, applied on reset
+    dt_input: String,       // Contents of the tick period field, applied on reset
+    influx: InfluxConfig,   // Metrics endpoint
+    metrics: MetricsBuffer, // Lines waiting for the next batched write
+    recorded: SimulationResults, // Raw per-tick state for CSV export
+    csv_url: Option<ObjectUrl>,  // Last export; kept alive until the next one
 }
 
 impl Model {
-    /// Sends simulation metrics to InfluxDB using InfluxDB line protocol.
-    fn send_metrics(&self) {
+    /// A stopped model for `config`; `create` starts its tick interval.
+    fn new(config: SimulationConfig) -> Self {
+        let SimulationConfig { scenario, influx } = config;
+        let mut debug_log = Vec::new();
+        if let Err(errors) = scenario.validate() {
+            for error in &errors {
+                log::error!("Invalid scenario: {}", error);
+                debug_log.push(format!("Invalid scenario: {}", error));
+            }
+        }
+
+        Self {
+            seed_input: scenario.seed.to_string(),
+            duration_input: scenario.duration.to_string(),
+            dt_input: scenario.dt.to_string(),
+            metrics: MetricsBuffer::new(&influx),
+            recorded: SimulationResults::default(),
+            csv_url: None,
+            influx,
+            engine: SimulationEngine::new(scenario),
+            smoother: Smoother::new(),
+            interval: None,
+            debug_log,
+        }
+    }
+
+    /// Step the engine, record the raw report and return the smoothed one the view shows.
+    fn advance(&mut self) -> StepReport {
+        let report = self.engine.step();
+        self.recorded.record(report.clone());
+        self.smoother.apply(&report)
+    }
+
+    /// Buffer this tick's metrics and send a batch to InfluxDB when one is due.
+    fn queue_metrics(&mut self, ctx: &Context<Self>, report: &StepReport) {
         // Get current time in nanoseconds.
         let timestamp_ns = (js_sys::Date::now() * 1_000_000.0) as i64;
-        
-        // Convert booleans to integers (1 for true, 0 for false).
-        let charging = if self.charging_mode { 1 } else { 0 };
-        let cooling = if self.cooling_active { 1 } else { 0 };
-    
-        // Corrected line protocol: each field has a key.
-        let line = format!(
-            "bms_metrics,sim_id=1 voltage={},current={},fuel_cell_temperature={},hydration={},oxygen={},soc={},battery_voltage={},battery_current={},battery_temp={},manifold_pressure={},compressor_speed={},charging_mode={},cooling_active={} {}",
-            self.fuel_cell.voltage,
-            self.fuel_cell.current,
-            self.fuel_cell.temperature,
-            self.fuel_cell.membrane_hydration,
-            self.fuel_cell.oxygen_concentration,
-            self.battery.soc,
-            self.battery.voltage,
-            self.battery.current,
-            self.battery.temperature,
-            self.air_supply.manifold.pressure,
-            self.air_supply.compressor.speed,
-            charging,
-            cooling,
-            timestamp_ns
-        );
-        
-        log::debug!("Sending data to InfluxDB: {}", line);
+        self.metrics.push(line_protocol(report, timestamp_ns));
+        if let Some(body) = self.metrics.tick() {
+            self.send_batch(ctx, body);
+        }
+    }
+
+    /// Line chart of one channel over the last `CHART_WINDOW` seconds.
+    fn chart(&self, title: &str, channel: Channel) -> Html {
+        let (width, height) = (280.0, 100.0);
+        let series = self.recorded.series(channel);
+        let window = (CHART_WINDOW / self.engine.scenario.dt).ceil() as usize;
+        let recent = &series[series.len().saturating_sub(window)..];
+        let (min, max) = chart::value_range(recent);
+        html! {
+            <figure style="margin: 0 1em 1em 0;">
+                <figcaption>{ format!("{} [{:.2} – {:.2}]", title, min, max) }</figcaption>
+                <svg width={width.to_string()} height={height.to_string()} style="background-color: #f0f0f0;">
+                    <polyline fill="none" stroke="steelblue" stroke-width="1.5"
+                        points={chart::polyline_points(recent, width, height)} />
+                </svg>
+            </figure>
+        }
+    }
+
+    /// POST one batch of line-protocol points; the outcome comes back as `Msg::MetricsSent`.
+    fn send_batch(&self, ctx: &Context<Self>, body: String) {
+        log::debug!("Sending data to InfluxDB: {}", body);
+        let influx = self.influx.clone();
+        let link = ctx.link().clone();
     
         // Use spawn_local to send the HTTP POST asynchronously.
         wasm_bindgen_futures::spawn_local(async move {
-            let result = gloo_net::http::Request::post("http://localhost:8086/write?db=bms_db")
-                .body(line)
-                .send()
-                .await;
+            let mut request = gloo_net::http::Request::post(&influx.write_url());
+            if let Some(authorization) = influx.authorization() {
+                request = request.header("Authorization", &authorization);
+            }
+            let result = request.body(body).send().await;
         
-            match result {
+            let outcome = match result {
                 Ok(response) => {
-                    if response.ok() {
-                        log::debug!("Metrics sent successfully.");
-                    } else {
-                        log::error!("InfluxDB responded with error: {} {}", response.status(), response.text().await.unwrap_or_default());
+                    let outcome = influx.outcome(response.status());
+                    match outcome {
+                        WriteOutcome::Accepted => log::debug!("Metrics sent successfully."),
+                        WriteOutcome::Rejected => log::error!(
+                            "InfluxDB rejected the batch, dropping it: {} {}",
+                            response.status(),
+                            response.text().await.unwrap_or_default()
+                        ),
+                        WriteOutcome::Retry => log::error!(
+                            "InfluxDB responded with error, will retry: {} {}",
+                            response.status(),
+                            response.text().await.unwrap_or_default()
+                        ),
                     }
+                    outcome
                 }
                 Err(err) => {
                     log::error!("Failed to send metrics: {:?}", err);
+                    WriteOutcome::Retry
                 }
-            }
+            };
+            link.send_message(Msg::MetricsSent(outcome));
         });
     }
 }
+/// Simulated time shown by the live charts (s).
+const CHART_WINDOW: f64 = 30.0;
+
+/// Controller loop tuned from the GUI.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum TunedLoop {
+    Oxygen,
+    AirSupply,
+}
+
+/// One PID gain.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Gain {
+    Kp,
+    Ki,
+    Kd,
+}
+
 /// Messages for our Yew component.
 enum Msg {
     Tick,
+    /// The seed field was edited.
+    SeedInput(String),
+    /// The duration field was edited.
+    DurationInput(String),
+    /// The tick period field was edited.
+    DtInput(String),
+    /// Rebuild the engine with the seed, duration and dt in the fields and restart the run.
+    Reset,
+    /// Switch a sinusoidal ambient swing (one period per run) on or off.
+    ToggleAmbientSwing,
+    /// A batched metrics write finished.
+    MetricsSent(WriteOutcome),
+    /// Save the recorded run as a CSV file.
+    DownloadCsv,
+    /// A gain slider moved; the controller is retuned live without a bump.
+    SetGain(TunedLoop, Gain, f64),
 }
 
 impl Component for Model {
@@ -95,133 +198,214 @@ impl Component for Model {
     type Properties = ();
 
     fn create(ctx: &Context<Self>) -> Self {
-        // Create simulation components.
-        let fuel_cell = FuelCell::new();
-        let battery = Battery::new();
-        let air_supply = AirSupplySystem::new();
-        let oxygen_controller = OxygenController::new(0.5, 0.1, 0.01, 0.5);
-        let air_supply_controller = AirSupplyController::new(0.5, 0.05, 0.05, 0.5, 0.21);
-        let battery_controller = BatteryController::new(65.0, 75.0);
-        let charging_mode = false;
-        let cooling_active = false;
-        let debug_log = Vec::new();
-        let simulation_time = 0.0;
-        let simulation_duration = 60.0; // run for 60 seconds
-
-        let link = ctx.link().clone();
-        let interval = Interval::new(500, move || {
-            link.send_message(Msg::Tick);
-        });
-
-        Self {
-            fuel_cell,
-            battery,
-            air_supply,
-            oxygen_controller,
-            air_supply_controller,
-            battery_controller,
-            charging_mode,
-            cooling_active,
-            interval: Some(interval),
-            debug_log,
-            simulation_time,
-            simulation_duration,
-        }
+        let mut model = Self::new(SimulationConfig::default());
+        model.interval = Some(start_interval(ctx, model.engine.scenario.dt));
+        model
     }
 
-    fn update(&mut self, _ctx: &Context<Self>, msg: Self::Message) -> bool {
+    fn update(&mut self, ctx: &Context<Self>, msg: Self::Message) -> bool {
         match msg {
             Msg::Tick => {
-                let dt = 0.5;
-                self.simulation_time += dt;
-                
-                // Stop simulation after the fixed duration.
-                if self.simulation_time >= self.simulation_duration {
+                // Stop simulation once the scenario's steps have all been taken.
+                if self.engine.is_finished() {
                     // Take ownership and cancel the interval.
                     if let Some(interval) = self.interval.take() {
                         interval.cancel();
                     }
-                    self.debug_log.push(format!("Simulation ended at {:.2} seconds.", self.simulation_time));
+                    self.debug_log.push(format!("Simulation ended at {:.2} seconds.", self.engine.time));
                     return true;
                 }
-                
-                // Update battery mode (hysteresis-based).
-                self.charging_mode = self.battery_controller.update_mode(self.battery.soc);
-
-                // Read fuel cell sensor data.
-                let fc_data = read_fuel_cell_sensor(&self.fuel_cell);
-
-                // Compute compressor motor torque from AirSupplyController.
-                let motor_torque = self.air_supply_controller.compute_motor_torque(fc_data.oxygen_concentration);
-
-                // Estimate mass flow out and update air supply.
-                let mass_flow_out = self.fuel_cell.hydrogen_flow * 0.05;
-                let is_discharging = !self.charging_mode;
-                self.air_supply.update(motor_torque, dt, mass_flow_out, is_discharging);
-
-                // Compute oxygen concentration from updated manifold pressure.
-                let oxygen_concentration = self.fuel_cell.compute_oxygen_concentration_from(self.air_supply.manifold.pressure);
-
-                // Determine load using oxygen controller and disturbance.
-                let disturbance = 10.0;
-                let load = if self.charging_mode {
-                    8.0 // fixed charging current
-                } else {
-                    self.oxygen_controller.regulate_adaptive(2.0, fc_data.oxygen_concentration) + disturbance
-                };
-
-                // Set cooling based on temperature.
-                self.cooling_active = self.fuel_cell.temperature > 44.0;
 
-                // Update fuel cell state.
-                let humidity = 0.8; // Base humidity value
-                self.fuel_cell.update(load, self.cooling_active, oxygen_concentration, humidity);
-
-                // Update battery state.
-                if self.charging_mode {
-                    self.battery.update(8.0, 0.0, true);
-                } else {
-                    self.battery.update(0.0, load, false);
-                }
+                let report = self.advance();
+                let (p_term, i_term, d_term) = self.engine.oxygen_controller.pid().last_terms();
 
                 // Append a debug log entry.
                 let log_entry = format!(
-                    "t: {:.1}s | V: {:.2} V, I: {:.2} A, Temp: {:.2} °C, Hydration: {:.2}, SOC: {:.2}%, MPress: {:.2} Pa, O2: {:.2}",
-                    self.simulation_time,
-                    self.fuel_cell.voltage,
-                    self.fuel_cell.current,
-                    self.fuel_cell.temperature,
-                    self.fuel_cell.membrane_hydration,
-                    self.battery.soc,
-                    self.air_supply.manifold.pressure,
-                    oxygen_concentration,
+                    "t: {:.1}s | V: {:.2} V, I: {:.2} A, Temp: {:.2} °C, Hydration: {:.2}, SOC: {:.2}%, MPress: {:.2} Pa, O2: {:.2}, O2 PID P/I/D: {:.3}/{:.3}/{:.3}",
+                    report.time,
+                    report.fuel_cell_voltage,
+                    report.fuel_cell_current,
+                    report.fuel_cell_temperature,
+                    report.membrane_hydration,
+                    report.battery_soc,
+                    report.manifold_pressure,
+                    report.oxygen_concentration,
+                    p_term,
+                    i_term,
+                    d_term,
                 );
                 self.debug_log.push(log_entry);
                 if self.debug_log.len() > 120 {
                     self.debug_log.drain(0..(self.debug_log.len() - 120));
                 }
-                self.send_metrics();
+                self.queue_metrics(ctx, &report);
+                true
+            }
+            Msg::SeedInput(value) => {
+                self.seed_input = value;
+                false
+            }
+            Msg::DurationInput(value) => {
+                self.duration_input = value;
+                false
+            }
+            Msg::DtInput(value) => {
+                self.dt_input = value;
+                false
+            }
+            Msg::Reset => {
+                let seed = self.seed_input.trim().parse::<u64>();
+                let duration = self.duration_input.trim().parse::<f64>();
+                let dt = self.dt_input.trim().parse::<f64>();
+                let (Ok(seed), Ok(duration), Ok(dt)) = (seed, duration, dt) else {
+                    self.debug_log.push(format!(
+                        "Invalid reset fields: seed `{}` (non-negative integer), duration `{}` and dt `{}` (seconds).",
+                        self.seed_input, self.duration_input, self.dt_input
+                    ));
+                    return true;
+                };
+                let scenario = Scenario { seed, duration, dt, ..self.engine.scenario.clone() };
+                if let Err(errors) = scenario.validate() {
+                    for error in &errors {
+                        self.debug_log.push(format!("Invalid scenario: {}", error));
+                    }
+                    return true;
+                }
+                let warnings = scenario.timing_warnings();
+                self.engine = SimulationEngine::new(scenario);
+                self.smoother = Smoother::new();
+                self.recorded = SimulationResults::default();
+                self.debug_log.clear();
+                self.debug_log.push(format!("Restarted with seed {}: {} s at dt {} s.", seed, duration, dt));
+                for warning in &warnings {
+                    self.debug_log.push(format!("Warning: {}", warning));
+                }
+                // The tick period follows dt, so the interval is always restarted.
+                if let Some(interval) = self.interval.take() {
+                    interval.cancel();
+                }
+                self.interval = Some(start_interval(ctx, dt));
+                true
+            }
+            Msg::ToggleAmbientSwing => {
+                let scenario = &mut self.engine.scenario;
+                scenario.ambient_profile = match scenario.ambient_profile {
+                    Some(_) => None,
+                    None => Some(AmbientProfile::Sinusoid { mean: 20.0, amplitude: 10.0, period: scenario.duration }),
+                };
+                true
+            }
+            Msg::DownloadCsv => {
+                let blob = Blob::new_with_options(self.recorded.to_csv().as_str(), Some("text/csv"));
+                let url = ObjectUrl::from(blob);
+                let anchor = gloo::utils::document()
+                    .create_element("a")
+                    .ok()
+                    .and_then(|element| element.dyn_into::<web_sys::HtmlAnchorElement>().ok());
+                match anchor {
+                    Some(anchor) => {
+                        anchor.set_href(&url);
+                        anchor.set_download(&format!("bms_run_seed_{}.csv", self.engine.scenario.seed));
+                        anchor.click();
+                        self.csv_url = Some(url);
+                    }
+                    None => self.debug_log.push("CSV export failed: could not create a download link.".to_string()),
+                }
+                true
+            }
+            Msg::SetGain(tuned, gain, value) => {
+                let engine = &mut self.engine;
+                // Keep the scenario in step so a restart uses the tuned gains.
+                let gains = match tuned {
+                    TunedLoop::Oxygen => &mut engine.scenario.oxygen_gains,
+                    TunedLoop::AirSupply => &mut engine.scenario.air_supply_gains,
+                };
+                match gain {
+                    Gain::Kp => gains.kp = value,
+                    Gain::Ki => gains.ki = value,
+                    Gain::Kd => gains.kd = value,
+                }
+                let PidGains { kp, ki, kd } = *gains;
+                match tuned {
+                    TunedLoop::Oxygen => engine.oxygen_controller.set_gains(kp, ki, kd),
+                    TunedLoop::AirSupply => engine.air_supply_controller.set_gains(kp, ki, kd),
+                }
                 true
             }
+            Msg::MetricsSent(outcome) => {
+                let was_offline = self.metrics.consecutive_failures() > 0;
+                self.metrics.finish(outcome);
+                // Re-render only when the offline indicator changes.
+                was_offline || outcome == WriteOutcome::Retry
+            }
         }
     }
 
-    fn view(&self, _ctx: &Context<Self>) -> Html {
+    fn view(&self, ctx: &Context<Self>) -> Html {
         let debug_text = self.debug_log.join("\n");
+        let engine = &self.engine;
+        let readings = read_system(&engine.fuel_cell, &engine.battery, &engine.air_supply, engine.time);
+        let on_seed_input = ctx.link().callback(|e: InputEvent| {
+            Msg::SeedInput(e.target_unchecked_into::<HtmlInputElement>().value())
+        });
+        let on_duration_input = ctx.link().callback(|e: InputEvent| {
+            Msg::DurationInput(e.target_unchecked_into::<HtmlInputElement>().value())
+        });
+        let on_dt_input = ctx.link().callback(|e: InputEvent| {
+            Msg::DtInput(e.target_unchecked_into::<HtmlInputElement>().value())
+        });
+        let on_reset = ctx.link().callback(|_| Msg::Reset);
+        let on_toggle_ambient = ctx.link().callback(|_| Msg::ToggleAmbientSwing);
+        let on_download_csv = ctx.link().callback(|_| Msg::DownloadCsv);
         html! {
             <div style="font-family: sans-serif;">
                 <h1>{ "BMS Simulation (Web) - Debug Output" }</h1>
-                <p>{ format!("Simulation Time: {:.1} s / {:.1} s", self.simulation_time, self.simulation_duration) }</p>
+                <p>
+                    { format!("Active seed: {} ", engine.scenario.seed) }
+                    <input type="text" value={self.seed_input.clone()} oninput={on_seed_input} />
+                    { " Duration (s) " }
+                    <input type="number" min="0" value={self.duration_input.clone()} oninput={on_duration_input} />
+                    { " dt (s) " }
+                    <input type="number" min="0" step="0.05" value={self.dt_input.clone()} oninput={on_dt_input} />
+                    <button onclick={on_reset}>{ "Reset" }</button>
+                </p>
+                <p>{ format!("Simulation Time: {:.1} s / {:.1} s", engine.time, engine.scenario.duration) }</p>
+                <p>
+                    { format!("Ambient: {:.1} °C ", engine.fuel_cell.ambient_temp) }
+                    <button onclick={on_toggle_ambient}>
+                        { if engine.scenario.ambient_profile.is_some() { "Fixed ambient" } else { "Ambient swing" } }
+                    </button>
+                </p>
                 <p>{ format!("FuelCell -> V: {:.2} V, I: {:.2} A, Temp: {:.2} °C",
-                    self.fuel_cell.voltage, self.fuel_cell.current, self.fuel_cell.temperature) }</p>
-                <p>{ format!("Membrane Hydration: {:.2}", self.fuel_cell.membrane_hydration) }</p>
-                <p>{ format!("Manifold Pressure: {:.2} Pa", self.air_supply.manifold.pressure) }</p>
-                <p>{ format!("Oxygen Concentration: {:.2}", self.fuel_cell.oxygen_concentration) }</p>
+                    readings.fuel_cell.voltage, readings.fuel_cell.current, readings.fuel_cell.temperature) }</p>
+                <p>{ format!("Membrane Hydration: {:.2}", engine.fuel_cell.membrane_hydration) }</p>
+                <p>{ format!("Manifold Pressure: {:.2} Pa, Compressor: {:.0} rad/s",
+                    readings.manifold_pressure, readings.compressor_speed) }</p>
+                <p>{ format!("Oxygen Concentration: {:.2}", readings.fuel_cell.oxygen_concentration) }</p>
                 <p>{ format!("Battery -> SoC: {:.2} %, V: {:.2} V, I: {:.2} A",
-                    self.battery.soc, self.battery.voltage, self.battery.current) }</p>
-                <p>{ format!("Charging Mode: {}", if self.charging_mode { "Yes" } else { "No" }) }</p>
-                <p>{ format!("Cooling Active: {}", if self.cooling_active { "Yes" } else { "No" }) }</p>
+                    readings.battery.soc, readings.battery.voltage, readings.battery.current) }</p>
+                <p>{ format!("State: {}", engine.supervisor.state) }</p>
+                <p>{ format!("Charging Mode: {}", if engine.charging_mode { "Yes" } else { "No" }) }</p>
+                <p>{ format!("Cooling Active: {}", if engine.cooling_active { "Yes" } else { "No" }) }</p>
+                <p>{ match self.metrics.consecutive_failures() {
+                    0 => format!("InfluxDB: online, {} lines buffered", self.metrics.len()),
+                    failures => format!("InfluxDB: OFFLINE ({} failed writes, retry in {} ticks, {} lines buffered)",
+                        failures, self.metrics.backoff_ticks(), self.metrics.len()),
+                } }</p>
+                <h2>{ format!("Last {:.0} s", CHART_WINDOW) }</h2>
+                <div style="display: flex; flex-wrap: wrap;">
+                    { for [
+                        ("Fuel cell voltage (V)", Channel::FuelCellVoltage),
+                        ("Battery SoC (%)", Channel::BatterySoc),
+                        ("Fuel cell temperature (°C)", Channel::FuelCellTemperature),
+                        ("Manifold pressure (Pa)", Channel::ManifoldPressure),
+                    ].into_iter().map(|(title, channel)| self.chart(title, channel)) }
+                </div>
+                <h2>{ "Controller Tuning" }</h2>
+                { gain_sliders(ctx, "Oxygen", TunedLoop::Oxygen, &engine.scenario.oxygen_gains) }
+                { gain_sliders(ctx, "Air supply", TunedLoop::AirSupply, &engine.scenario.air_supply_gains) }
                 <h2>{ "Debug Log:" }</h2>
+                <button onclick={on_download_csv}>{ format!("Download CSV ({} steps)", self.recorded.len()) }</button>
                 <pre style="background-color: #f0f0f0; padding: 10px; max-height: 300px; overflow-y: scroll;">
                     { debug_text }
                 </pre>
@@ -230,6 +414,41 @@ impl Component for Model {
     }
 }
 
+/// Range sliders for one controller's gains, each with its current value.
+fn gain_sliders(ctx: &Context<Model>, label: &str, tuned: TunedLoop, gains: &PidGains) -> Html {
+    let slider = |name: &str, gain: Gain, value: f64, max: f64| {
+        let oninput = ctx.link().callback(move |e: InputEvent| {
+            let value = e.target_unchecked_into::<HtmlInputElement>().value_as_number();
+            Msg::SetGain(tuned, gain, value)
+        });
+        html! {
+            <label style="margin-right: 1em;">
+                { format!("{} ", name) }
+                <input type="range" min="0" max={max.to_string()} step={(max / 200.0).to_string()}
+                    value={value.to_string()} {oninput} />
+                { format!(" {:.3}", value) }
+            </label>
+        }
+    };
+    html! {
+        <p>
+            <strong>{ format!("{}: ", label) }</strong>
+            { slider("Kp", Gain::Kp, gains.kp, 2.0) }
+            { slider("Ki", Gain::Ki, gains.ki, 1.0) }
+            { slider("Kd", Gain::Kd, gains.kd, 0.5) }
+        </p>
+    }
+}
+
+/// Start the periodic simulation tick, one tick per `dt` of simulated time.
+fn start_interval(ctx: &Context<Model>, dt: f64) -> Interval {
+    let link = ctx.link().clone();
+    let period_ms = (dt * 1000.0).round().max(1.0) as u32;
+    Interval::new(period_ms, move || {
+        link.send_message(Msg::Tick);
+    })
+}
+
 #[wasm_bindgen(start)]
 pub fn run_app() {
     console_log::init_with_level(Level::Debug).expect("error initializing logger");
@@ -0,0 +1,170 @@
+use crate::engine::{membrane_humidity, SimulationEngine};
+
+/// States perturbed by [`SimulationEngine::linearize`], in matrix order.
+pub const STATE_NAMES: [&str; 5] = [
+    "fuel_cell_temperature",
+    "membrane_hydration",
+    "manifold_pressure",
+    "compressor_speed",
+    "battery_soc",
+];
+
+/// Inputs perturbed by [`SimulationEngine::linearize`], in matrix order.
+pub const INPUT_NAMES: [&str; 2] = ["load", "motor_torque"];
+
+/// Discrete-time linear model x[k+1] ≈ A·x[k] + B·u[k] around an operating point.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LinearModel {
+    /// State Jacobian, `a[i][j] = ∂x_i[k+1] / ∂x_j[k]`.
+    pub a: Vec<Vec<f64>>,
+    /// Input Jacobian, `b[i][j] = ∂x_i[k+1] / ∂u_j[k]`.
+    pub b: Vec<Vec<f64>>,
+    /// Sample time of the discrete model (s).
+    pub dt: f64,
+}
+
+impl LinearModel {
+    /// Row/column index of a named state.
+    pub fn state_index(name: &str) -> Option<usize> {
+        STATE_NAMES.iter().position(|n| *n == name)
+    }
+
+    /// Column index of a named input.
+    pub fn input_index(name: &str) -> Option<usize> {
+        INPUT_NAMES.iter().position(|n| *n == name)
+    }
+}
+
+impl SimulationEngine {
+    /// Numerically linearize the plant around the current operating point.
+    ///
+    /// Each state and input is perturbed by a relative step of `eps` (central
+    /// differences) and one plant step is taken on a copy of the models, so the
+    /// engine itself is not modified. Controllers are bypassed: the load and
+    /// compressor torque are the inputs. Jacobians are inaccurate where a state
+    /// sits on a hard clamp (e.g. hydration at 1.0).
+    pub fn linearize(&self, eps: f64) -> LinearModel {
+        let x0 = self.plant_state();
+        let u0 = [self.fuel_cell.current, self.motor_torque];
+
+        let perturbation = |value: f64| eps * value.abs().max(1.0);
+        let mut a = vec![vec![0.0; x0.len()]; x0.len()];
+        for j in 0..x0.len() {
+            let h = perturbation(x0[j]);
+            let (mut plus, mut minus) = (x0, x0);
+            plus[j] += h;
+            minus[j] -= h;
+            let (fp, fm) = (self.plant_step(&plus, &u0), self.plant_step(&minus, &u0));
+            for i in 0..x0.len() {
+                a[i][j] = (fp[i] - fm[i]) / (2.0 * h);
+            }
+        }
+
+        let mut b = vec![vec![0.0; u0.len()]; x0.len()];
+        for j in 0..u0.len() {
+            let h = perturbation(u0[j]);
+            let (mut plus, mut minus) = (u0, u0);
+            plus[j] += h;
+            minus[j] -= h;
+            let (fp, fm) = (self.plant_step(&x0, &plus), self.plant_step(&x0, &minus));
+            for i in 0..x0.len() {
+                b[i][j] = (fp[i] - fm[i]) / (2.0 * h);
+            }
+        }
+
+        LinearModel { a, b, dt: self.scenario.dt }
+    }
+
+    fn plant_state(&self) -> [f64; 5] {
+        [
+            self.fuel_cell.temperature,
+            self.fuel_cell.membrane_hydration,
+            self.air_supply.manifold.pressure,
+            self.air_supply.compressor.speed,
+            self.battery.soc,
+        ]
+    }
+
+    /// One open-loop plant step from state `x` with inputs `u`, on copies of the models.
+    fn plant_step(&self, x: &[f64; 5], u: &[f64; 2]) -> [f64; 5] {
+        let mut fuel_cell = self.fuel_cell.clone();
+        let mut battery = self.battery.clone();
+        let mut air_supply = self.air_supply.clone();
+        fuel_cell.temperature = x[0];
+        fuel_cell.membrane_hydration = x[1];
+        air_supply.manifold.pressure = x[2];
+        air_supply.compressor.speed = x[3];
+        battery.soc = x[4];
+        let (load, motor_torque) = (u[0], u[1]);
+
+        let dt = self.scenario.dt;
+        let mass_flow_out = fuel_cell.cathode_exhaust_flow();
+        air_supply.update(motor_torque, dt, mass_flow_out, !self.charging_mode);
+        let oxygen_concentration = fuel_cell.compute_oxygen_concentration_from(air_supply.manifold.pressure);
+        // Humidity and fan duty as the engine applies them, so A/B describe the plant that runs.
+        let humidity = membrane_humidity(&self.scenario, &air_supply, &fuel_cell);
+        fuel_cell.update(load, self.cooling_duty, oxygen_concentration, humidity, dt);
+        if self.charging_mode {
+            battery.update(self.battery_controller.charge_command(true), 0.0, true, dt);
+        } else {
+            battery.update(0.0, load, false, dt);
+        }
+
+        [
+            fuel_cell.temperature,
+            fuel_cell.membrane_hydration,
+            air_supply.manifold.pressure,
+            air_supply.compressor.speed,
+            battery.soc,
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scenario::Scenario;
+
+    #[test]
+    fn test_compressor_speed_jacobian_matches_analytic() {
+        let mut engine = SimulationEngine::new(Scenario::default());
+        for _ in 0..10 {
+            engine.step();
+        }
+        let model = engine.linearize(1e-6);
+
+        // speed[k+1] = speed + dt·(T − c·k·speed·e^{−(PR−1)}) / J
+        let compressor = &engine.air_supply.compressor;
+        let pressure_ratio = engine.air_supply.manifold.pressure / engine.air_supply.inlet_pressure;
+        let dt = engine.scenario.dt;
+        let d_load_torque = 50.0 * 0.001 * (-(pressure_ratio - 1.0)).exp();
+        let expected_a = 1.0 - dt * d_load_torque / compressor.inertia;
+        let expected_b = dt / compressor.inertia;
+
+        let speed = LinearModel::state_index("compressor_speed").unwrap();
+        let torque = LinearModel::input_index("motor_torque").unwrap();
+        assert!((model.a[speed][speed] - expected_a).abs() < 1e-6, "{} vs {}", model.a[speed][speed], expected_a);
+        assert!((model.b[speed][torque] - expected_b).abs() < 1e-6);
+        // Speed does not depend on the stack load.
+        let load = LinearModel::input_index("load").unwrap();
+        assert!(model.b[speed][load].abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_hydration_row_follows_the_manifold_humidity() {
+        let model_for = |scenario: Scenario| {
+            let mut engine = SimulationEngine::new(scenario);
+            for _ in 0..10 {
+                engine.step();
+            }
+            engine.linearize(1e-6)
+        };
+        let hydration = LinearModel::state_index("membrane_hydration").unwrap();
+        let pressure = LinearModel::state_index("manifold_pressure").unwrap();
+        // The default engine feeds the membrane the manifold RH, which moves with pressure.
+        let manifold = model_for(Scenario::default());
+        assert!(manifold.a[hydration][pressure].abs() > 1e-9, "{}", manifold.a[hydration][pressure]);
+        let fixed = model_for(Scenario { manifold_humidity: false, ..Scenario::default() });
+        assert!(fixed.a[hydration][pressure].abs() < 1e-12);
+    }
+}
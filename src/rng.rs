@@ -0,0 +1,86 @@
+use serde::{Deserialize, Serialize};
+
+/// Small deterministic PRNG (SplitMix64) used for noise and disturbances.
+///
+/// Not cryptographic; chosen because it is tiny, fast, and produces identical
+/// sequences on every platform, including wasm.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SimRng {
+    state: u64,
+}
+
+impl SimRng {
+    pub fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    /// RNG for a named component, derived from the run's master seed.
+    pub fn for_component(master_seed: u64, component: &str) -> Self {
+        Self::new(derive_seed(master_seed, component))
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        mix(self.state)
+    }
+
+    /// Uniform sample in [0, 1).
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// Uniform sample in [low, high).
+    pub fn uniform(&mut self, low: f64, high: f64) -> f64 {
+        low + (high - low) * self.next_f64()
+    }
+
+    /// Normally distributed sample (Box-Muller).
+    pub fn gaussian(&mut self, mean: f64, std_dev: f64) -> f64 {
+        // 1 - u keeps the log argument in (0, 1].
+        let u1 = 1.0 - self.next_f64();
+        let u2 = self.next_f64();
+        let z = (-2.0 * u1.ln()).sqrt() * (2.0 * std::f64::consts::PI * u2).cos();
+        mean + std_dev * z
+    }
+}
+
+/// Derive a per-component seed from a master seed and a component name.
+///
+/// The name is hashed (FNV-1a) and mixed with the master seed, so adding a new
+/// stochastic component never shifts the sequences of existing ones.
+pub fn derive_seed(master_seed: u64, component: &str) -> u64 {
+    let mut hash: u64 = 0xCBF2_9CE4_8422_2325;
+    for byte in component.bytes() {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x0100_0000_01B3);
+    }
+    mix(master_seed ^ mix(hash))
+}
+
+/// SplitMix64 finalizer.
+fn mix(mut z: u64) -> u64 {
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_component_seeds_differ() {
+        assert_ne!(derive_seed(7, "disturbance"), derive_seed(7, "sensor_noise"));
+        assert_ne!(derive_seed(7, "disturbance"), derive_seed(8, "disturbance"));
+        assert_eq!(derive_seed(7, "disturbance"), derive_seed(7, "disturbance"));
+    }
+
+    #[test]
+    fn test_uniform_range() {
+        let mut rng = SimRng::new(1);
+        for _ in 0..1000 {
+            let x = rng.uniform(-2.0, 3.0);
+            assert!((-2.0..3.0).contains(&x));
+        }
+    }
+}
@@ -0,0 +1,484 @@
+use std::fmt;
+use serde::{Deserialize, Serialize};
+
+use crate::ambient::AmbientProfile;
+use crate::control::{CoolingTarget, ThermalMode};
+use crate::error::BmsError;
+use crate::hal::{ActuatorFaultMode, AdcRange, FaultMode};
+use crate::sensors::SensorConfig;
+use crate::simulation::bus::SplitPolicy;
+use crate::simulation::cathode::CathodeConfig;
+use crate::supervisor::ProtectionLimits;
+use crate::simulation::converter::{check_efficiency_curve, ConverterCommand};
+
+/// PID gains for one controller.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct PidGains {
+    pub kp: f64,
+    pub ki: f64,
+    pub kd: f64,
+}
+
+impl PidGains {
+    pub fn new(kp: f64, ki: f64, kd: f64) -> Self {
+        Self { kp, ki, kd }
+    }
+}
+
+/// Time profile of the discharge-mode load disturbance.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub enum DisturbanceShape {
+    /// `load_disturbance` throughout.
+    #[default]
+    Constant,
+    /// `load_disturbance · |sin(ω·t)|` with ω in rad/s.
+    RectifiedSine { angular_frequency: f64 },
+}
+
+/// Interval below which browsers clamp repeating timers (ms).
+pub const BROWSER_TIMER_RESOLUTION_MS: f64 = 4.0;
+
+/// Run-timing problems that do not make a scenario invalid.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TimingWarning {
+    /// `duration / dt` is not a whole number of steps; the run ends on the last whole step.
+    UnevenSteps { steps: f64 },
+    /// One GUI tick per `dt` is faster than browser timers fire, so the run lags real time.
+    BelowTimerResolution { period_ms: f64 },
+}
+
+impl fmt::Display for TimingWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TimingWarning::UnevenSteps { steps } => {
+                write!(f, "duration is {:.3} steps of dt; the run ends on the last whole step", steps)
+            }
+            TimingWarning::BelowTimerResolution { period_ms } => write!(
+                f,
+                "tick period {:.1} ms is below the {} ms browser timer resolution; the run will lag real time",
+                period_ms, BROWSER_TIMER_RESOLUTION_MS
+            ),
+        }
+    }
+}
+
+/// Upper bound accepted for any single PID gain.
+const MAX_GAIN: f64 = 1000.0;
+
+/// Parameters describing one simulation run.
+///
+/// The defaults reproduce the values the GUI and console loops used to hardcode,
+/// except that the membrane humidity comes from the manifold, not a fixed 0.8.
+/// Missing fields deserialize to their defaults, so a config file only needs
+/// the parameters it changes.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Scenario {
+    /// Simulation time step (s).
+    pub dt: f64,
+    /// Total simulated duration (s).
+    pub duration: f64,
+    /// Fuel cell thermal capacity (J/K, model units).
+    pub fuel_cell_thermal_mass: f64,
+    /// Battery nominal capacity (Ah).
+    pub battery_capacity_ah: f64,
+    /// Battery charging current used in charging mode (A).
+    pub charging_current: f64,
+    /// Battery current slew limit after a charge/discharge mode switch (A/s); `None` steps instantly.
+    pub mode_switch_slew_rate: Option<f64>,
+    /// CC-CV charge voltage limit (V); `None` charges at a fixed current.
+    pub charge_voltage_limit: Option<f64>,
+    /// Current (A) at which a CC-CV charge terminates.
+    pub charge_termination_current: f64,
+    /// SoC (%) below which charging starts.
+    pub soc_lower_threshold: f64,
+    /// SoC (%) above which charging stops.
+    pub soc_upper_threshold: f64,
+    /// Temperature (°C) of the cooling target above which cooling is switched on.
+    pub cooling_threshold: f64,
+    /// Width (°C) of the cooling on/off band centred on `cooling_threshold`;
+    /// 0 switches at the threshold itself.
+    pub cooling_hysteresis: f64,
+    /// Shortest time (s) cooling stays on once switched on.
+    pub cooling_min_on_time: f64,
+    /// On/off (using the threshold and band above) or proportional fan control.
+    pub cooling_mode: ThermalMode,
+    /// Whether cooling regulates the stack body or a separate coolant node.
+    pub cooling_target: CoolingTarget,
+    /// Load shedding on over-temperature or over-current; `None` disables it.
+    pub protection: Option<ProtectionLimits>,
+    /// Fuel cell temperature (°C) that trips an over-temperature shutdown.
+    pub fuel_cell_trip_temp: f64,
+    /// Fuel cell temperature (°C) below which a tripped stack may restart.
+    pub fuel_cell_recovery_temp: f64,
+    /// Battery temperature (°C) that trips an over-temperature shutdown.
+    pub battery_trip_temp: f64,
+    /// Battery temperature (°C) below which a tripped battery may reconnect.
+    pub battery_recovery_temp: f64,
+    /// Oxygen controller gains.
+    pub oxygen_gains: PidGains,
+    /// Air supply (compressor torque) controller gains.
+    pub air_supply_gains: PidGains,
+    /// Desired oxygen concentration for the air supply controller (0-1).
+    pub desired_oxygen: f64,
+    /// Oxygen excess ratio λ regulated by the air supply controller; `None`
+    /// regulates `desired_oxygen` concentration instead. The default air supply
+    /// gains are tuned for concentration and far too aggressive for λ.
+    pub oxygen_excess_ratio_setpoint: Option<f64>,
+    /// Setpoint passed to the oxygen controller when computing load.
+    pub oxygen_setpoint: f64,
+    /// Load disturbance added in discharge mode (A), shaped by `load_disturbance_shape`.
+    pub load_disturbance: f64,
+    pub load_disturbance_shape: DisturbanceShape,
+    /// Amplitude of the random load disturbance added on top (A, uniform ±).
+    pub load_disturbance_noise: f64,
+    /// Fraction (0-1) of the stack load the battery carries in discharge mode
+    /// without a `power_split`.
+    pub battery_load_share: f64,
+    /// Hybrid power split between stack and battery; `None` keeps the mode-based
+    /// logic (stack charges the battery in charging mode, both carry the load otherwise).
+    pub power_split: Option<SplitPolicy>,
+    /// DC-DC converter efficiency between stack and battery; `None` couples them directly.
+    pub dc_dc_efficiency: Option<f64>,
+    /// DC-DC converter output/input voltage ratio.
+    pub dc_dc_voltage_ratio: f64,
+    /// Converter efficiency against input power as `(watts, efficiency)`
+    /// breakpoints; empty uses `dc_dc_efficiency` at every load.
+    pub dc_dc_efficiency_curve: Vec<(f64, f64)>,
+    /// Commanded converter output voltage or current; `None` follows the voltage ratio.
+    pub dc_dc_command: Option<ConverterCommand>,
+    /// Grid export limit (W) for excess power; `None` disables export.
+    pub grid_export_limit_w: Option<f64>,
+    /// Transport dead-time from compressor outlet to manifold (s).
+    pub compressor_dead_time: f64,
+    /// Minimum compressor speed while running (rad/s); 0 disables the floor.
+    pub compressor_min_running_speed: f64,
+    /// Cathode channel with transport lag and oxygen depletion; `None` feeds
+    /// the stack the manifold-derived concentration instantly.
+    pub cathode: Option<CathodeConfig>,
+    /// Humidity fed to the membrane hydration model (0-1) when `manifold_humidity` is off.
+    pub humidity: f64,
+    /// Ambient temperature over time, applied to the stack, battery and air inlet;
+    /// `None` leaves each component at its own fixed ambient.
+    pub ambient_profile: Option<AmbientProfile>,
+    /// Relative humidity of the ambient air (0-1).
+    pub ambient_humidity: f64,
+    /// Active humidifier fitted. Without one, the hydration target is capped by
+    /// what ambient air can supply at stack temperature.
+    pub humidifier: bool,
+    /// Feed the membrane the manifold's computed relative humidity (the default)
+    /// instead of the fixed `humidity`.
+    pub manifold_humidity: bool,
+    /// Gaussian noise on the sensor readings the controllers act on.
+    pub sensor_noise: SensorConfig,
+    /// Time between fresh fuel cell temperature readings (s); the last reading
+    /// is held in between. 0 samples every step.
+    pub temperature_sample_period: f64,
+    /// Fault injected into the fuel cell temperature reading.
+    pub temperature_sensor_fault: FaultMode,
+    /// Fresh temperature samples without change, while the stack temperature
+    /// moves, after which the sensor is declared stuck, cutting the load and
+    /// forcing cooling; `None` disables the check.
+    pub stuck_sensor_window: Option<usize>,
+    /// Fault injected into the cooling fan.
+    pub cooling_fan_fault: ActuatorFaultMode,
+    /// ADC the oxygen sensor is read through; `None` reads it exactly.
+    pub oxygen_adc: Option<AdcRange>,
+    /// Record per-step state derivatives in the telemetry (debugging aid).
+    pub record_derivatives: bool,
+    /// Master seed from which every stochastic component derives its own seed.
+    pub seed: u64,
+}
+
+impl Scenario {
+    pub fn new() -> Self {
+        Self {
+            dt: 0.5,
+            duration: 60.0,
+            fuel_cell_thermal_mass: 120.0,
+            battery_capacity_ah: 0.5 * 100.0 / (0.1 * 3600.0),
+            charging_current: 8.0,
+            mode_switch_slew_rate: None,
+            charge_voltage_limit: None,
+            charge_termination_current: 0.5,
+            soc_lower_threshold: 65.0,
+            soc_upper_threshold: 75.0,
+            cooling_threshold: 44.0,
+            cooling_hysteresis: 0.0,
+            cooling_min_on_time: 0.0,
+            cooling_mode: ThermalMode::BangBang,
+            cooling_target: CoolingTarget::FuelCell,
+            protection: None,
+            fuel_cell_trip_temp: 80.0,
+            fuel_cell_recovery_temp: 65.0,
+            battery_trip_temp: 55.0,
+            battery_recovery_temp: 45.0,
+            oxygen_gains: PidGains::new(0.5, 0.1, 0.01),
+            air_supply_gains: PidGains::new(0.5, 0.05, 0.05),
+            desired_oxygen: 0.21,
+            oxygen_excess_ratio_setpoint: None,
+            oxygen_setpoint: 2.0,
+            load_disturbance: 10.0,
+            load_disturbance_shape: DisturbanceShape::Constant,
+            load_disturbance_noise: 0.0,
+            battery_load_share: 1.0,
+            power_split: None,
+            dc_dc_efficiency: None,
+            dc_dc_voltage_ratio: 53.0 / 60.0,
+            dc_dc_efficiency_curve: Vec::new(),
+            dc_dc_command: None,
+            grid_export_limit_w: None,
+            compressor_dead_time: 0.0,
+            compressor_min_running_speed: 0.0,
+            cathode: None,
+            humidity: 0.8,
+            ambient_profile: None,
+            ambient_humidity: 0.5,
+            humidifier: true,
+            manifold_humidity: true,
+            sensor_noise: SensorConfig::default(),
+            temperature_sample_period: 0.0,
+            temperature_sensor_fault: FaultMode::Healthy,
+            stuck_sensor_window: None,
+            cooling_fan_fault: ActuatorFaultMode::Healthy,
+            oxygen_adc: None,
+            record_derivatives: false,
+            seed: 0,
+        }
+    }
+
+    /// The loop `main_console` used to hardcode: 100 steps of a rectified-sine
+    /// disturbance advancing one radian per 0.5 s step, with the battery
+    /// carrying half the stack load in discharge mode.
+    pub fn console() -> Self {
+        Self {
+            duration: 50.0,
+            load_disturbance_shape: DisturbanceShape::RectifiedSine { angular_frequency: 2.0 },
+            battery_load_share: 0.5,
+            ..Self::new()
+        }
+    }
+
+    /// Whole steps in the run. A duration that is not a whole number of steps
+    /// (see [`TimingWarning::UnevenSteps`]) ends on the last whole step.
+    pub fn steps(&self) -> usize {
+        let steps = self.duration / self.dt;
+        if (steps - steps.round()).abs() > 1e-6 * steps.max(1.0) {
+            steps.floor() as usize
+        } else {
+            steps.round() as usize
+        }
+    }
+
+    /// Timing choices that are valid but probably unintended (see [`TimingWarning`]).
+    pub fn timing_warnings(&self) -> Vec<TimingWarning> {
+        let mut warnings = Vec::new();
+        let steps = self.duration / self.dt;
+        if (steps - steps.round()).abs() > 1e-6 * steps.max(1.0) {
+            warnings.push(TimingWarning::UnevenSteps { steps });
+        }
+        let period_ms = self.dt * 1000.0;
+        if period_ms < BROWSER_TIMER_RESOLUTION_MS {
+            warnings.push(TimingWarning::BelowTimerResolution { period_ms });
+        }
+        warnings
+    }
+
+    /// Check every parameter and report all problems at once.
+    ///
+    /// Unlike failing on the first bad field, this collects every error so a
+    /// scenario can be fixed in a single pass.
+    pub fn validate(&self) -> Result<(), Vec<BmsError>> {
+        let mut errors = Vec::new();
+
+        check_positive(&mut errors, "dt", self.dt);
+        check_positive(&mut errors, "duration", self.duration);
+        check_positive(&mut errors, "fuel_cell_thermal_mass", self.fuel_cell_thermal_mass);
+        check_positive(&mut errors, "battery_capacity_ah", self.battery_capacity_ah);
+        check_range(&mut errors, "charging_current", self.charging_current, 0.0, f64::MAX);
+        if let Some(rate) = self.mode_switch_slew_rate {
+            check_positive(&mut errors, "mode_switch_slew_rate", rate);
+        }
+        if let Some(limit) = self.charge_voltage_limit {
+            check_positive(&mut errors, "charge_voltage_limit", limit);
+        }
+        check_range(&mut errors, "charge_termination_current", self.charge_termination_current, 0.0, f64::MAX);
+        check_range(&mut errors, "soc_lower_threshold", self.soc_lower_threshold, 0.0, 100.0);
+        check_range(&mut errors, "soc_upper_threshold", self.soc_upper_threshold, 0.0, 100.0);
+        if self.soc_lower_threshold > self.soc_upper_threshold {
+            errors.push(BmsError::InvertedThresholds {
+                field: "soc_threshold",
+                lower: self.soc_lower_threshold,
+                upper: self.soc_upper_threshold,
+            });
+        }
+        for (field, recovery, trip) in [
+            ("fuel_cell_trip_temp", self.fuel_cell_recovery_temp, self.fuel_cell_trip_temp),
+            ("battery_trip_temp", self.battery_recovery_temp, self.battery_trip_temp),
+        ] {
+            if recovery > trip {
+                errors.push(BmsError::InvertedThresholds { field, lower: recovery, upper: trip });
+            }
+        }
+        check_gains(&mut errors, ["oxygen_gains.kp", "oxygen_gains.ki", "oxygen_gains.kd"], &self.oxygen_gains);
+        check_gains(&mut errors, ["air_supply_gains.kp", "air_supply_gains.ki", "air_supply_gains.kd"], &self.air_supply_gains);
+        check_range(&mut errors, "desired_oxygen", self.desired_oxygen, 0.0, 1.0);
+        if let Some(lambda) = self.oxygen_excess_ratio_setpoint {
+            check_positive(&mut errors, "oxygen_excess_ratio_setpoint", lambda);
+        }
+        match self.power_split {
+            Some(SplitPolicy::FuelCellLeading { max_current }) => {
+                check_range(&mut errors, "power_split.max_current", max_current, 0.0, f64::MAX);
+            }
+            Some(SplitPolicy::Proportional { fuel_cell_share }) => {
+                check_range(&mut errors, "power_split.fuel_cell_share", fuel_cell_share, 0.0, 1.0);
+            }
+            None => {}
+        }
+        if let Some(efficiency) = self.dc_dc_efficiency {
+            // (0, 1]: one error for a value that is both non-positive and out of range.
+            if !(efficiency > 0.0 && efficiency <= 1.0) {
+                errors.push(BmsError::OutOfRange { field: "dc_dc_efficiency", value: efficiency, min: 0.0, max: 1.0 });
+            }
+        }
+        check_positive(&mut errors, "dc_dc_voltage_ratio", self.dc_dc_voltage_ratio);
+        if let Err(error) = check_efficiency_curve(&self.dc_dc_efficiency_curve) {
+            errors.push(error);
+        }
+        match self.dc_dc_command {
+            Some(ConverterCommand::Voltage(voltage)) => check_positive(&mut errors, "dc_dc_command", voltage),
+            Some(ConverterCommand::Current(current)) => check_range(&mut errors, "dc_dc_command", current, 0.0, f64::MAX),
+            None => {}
+        }
+        if let Some(limit) = self.grid_export_limit_w {
+            check_range(&mut errors, "grid_export_limit_w", limit, 0.0, f64::MAX);
+        }
+        check_range(&mut errors, "compressor_dead_time", self.compressor_dead_time, 0.0, f64::MAX);
+        check_range(&mut errors, "compressor_min_running_speed", self.compressor_min_running_speed, 0.0, f64::MAX);
+        if let Some(cathode) = self.cathode {
+            check_range(&mut errors, "cathode.time_constant", cathode.time_constant, 0.0, f64::MAX);
+            check_range(&mut errors, "cathode.depletion_per_amp", cathode.depletion_per_amp, 0.0, f64::MAX);
+        }
+        check_range(&mut errors, "humidity", self.humidity, 0.0, 1.0);
+        check_range(&mut errors, "ambient_humidity", self.ambient_humidity, 0.0, 1.0);
+        if let Some(AmbientProfile::Sinusoid { period, .. }) = self.ambient_profile {
+            check_positive(&mut errors, "ambient_profile.period", period);
+        }
+        check_range(&mut errors, "load_disturbance_noise", self.load_disturbance_noise, 0.0, f64::MAX);
+        if let DisturbanceShape::RectifiedSine { angular_frequency } = self.load_disturbance_shape {
+            check_positive(&mut errors, "load_disturbance_shape.angular_frequency", angular_frequency);
+        }
+        check_range(&mut errors, "battery_load_share", self.battery_load_share, 0.0, 1.0);
+        let noise = &self.sensor_noise;
+        for value in [noise.voltage_noise, noise.current_noise, noise.temp_noise, noise.hydrogen_flow_noise, noise.oxygen_noise, noise.soc_noise] {
+            check_range(&mut errors, "sensor_noise", value, 0.0, f64::MAX);
+        }
+        check_range(&mut errors, "cooling_hysteresis", self.cooling_hysteresis, 0.0, f64::MAX);
+        if let Some(protection) = self.protection {
+            check_range(&mut errors, "protection.recovery_margin", protection.recovery_margin, 0.0, f64::MAX);
+            check_positive(&mut errors, "protection.max_current", protection.max_current);
+            check_range(&mut errors, "protection.load_retained", protection.load_retained, 0.0, 1.0);
+        }
+        check_range(&mut errors, "cooling_min_on_time", self.cooling_min_on_time, 0.0, f64::MAX);
+        if let ThermalMode::Proportional { gain, .. } = self.cooling_mode {
+            check_positive(&mut errors, "cooling_mode.gain", gain);
+        }
+        check_range(&mut errors, "temperature_sample_period", self.temperature_sample_period, 0.0, f64::MAX);
+        if let Some(window) = self.stuck_sensor_window {
+            check_positive(&mut errors, "stuck_sensor_window", window as f64);
+        }
+        if let ActuatorFaultMode::Degraded { effectiveness } = self.cooling_fan_fault {
+            check_range(&mut errors, "cooling_fan_fault.effectiveness", effectiveness, 0.0, 1.0);
+        }
+        if let Some(adc) = self.oxygen_adc {
+            check_range(&mut errors, "oxygen_adc.bits", adc.bits as f64, 1.0, 16.0);
+            check_positive(&mut errors, "oxygen_adc.span", adc.max - adc.min);
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+impl Default for Scenario {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn check_positive(errors: &mut Vec<BmsError>, field: &'static str, value: f64) {
+    if !value.is_finite() || value <= 0.0 {
+        errors.push(BmsError::NonPositive { field, value });
+    }
+}
+
+fn check_range(errors: &mut Vec<BmsError>, field: &'static str, value: f64, min: f64, max: f64) {
+    if !(min..=max).contains(&value) {
+        errors.push(BmsError::OutOfRange { field, value, min, max });
+    }
+}
+
+/// Check each gain under its own field name, e.g. `oxygen_gains.kp`.
+fn check_gains(errors: &mut Vec<BmsError>, fields: [&'static str; 3], gains: &PidGains) {
+    for (field, value) in fields.into_iter().zip([gains.kp, gains.ki, gains.kd]) {
+        check_range(errors, field, value, 0.0, MAX_GAIN);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_scenario_is_valid() {
+        assert_eq!(Scenario::new().validate(), Ok(()));
+    }
+
+    #[test]
+    fn test_validate_reports_all_errors() {
+        let mut scenario = Scenario::new();
+        scenario.fuel_cell_thermal_mass = -5.0;
+        scenario.soc_lower_threshold = 80.0;
+        scenario.soc_upper_threshold = 70.0;
+        scenario.oxygen_gains.kp = -1.0;
+
+        let errors = scenario.validate().unwrap_err();
+        assert_eq!(errors.len(), 3);
+        assert!(errors.contains(&BmsError::NonPositive { field: "fuel_cell_thermal_mass", value: -5.0 }));
+        assert!(errors.contains(&BmsError::InvertedThresholds { field: "soc_threshold", lower: 80.0, upper: 70.0 }));
+        assert!(errors.iter().any(|e| matches!(e, BmsError::OutOfRange { field: "oxygen_gains.kp", .. })));
+    }
+
+    #[test]
+    fn test_console_preset_is_valid() {
+        let console = Scenario::console();
+        assert_eq!(console.validate(), Ok(()));
+        let invalid = Scenario { battery_load_share: 1.5, ..console };
+        assert_eq!(invalid.validate().unwrap_err().len(), 1);
+    }
+
+    #[test]
+    fn test_timing_warnings() {
+        assert!(Scenario::new().timing_warnings().is_empty());
+        let long = Scenario { duration: 600.0, dt: 0.1, ..Scenario::new() };
+        assert!(long.timing_warnings().is_empty());
+        let uneven = Scenario { duration: 10.0, dt: 0.3, ..Scenario::new() };
+        assert!(matches!(uneven.timing_warnings()[..], [TimingWarning::UnevenSteps { .. }]));
+        assert_eq!(uneven.steps(), 33);
+        assert_eq!(Scenario::console().steps(), 100);
+        let fast = Scenario { duration: 1.0, dt: 0.001, ..Scenario::new() };
+        assert_eq!(fast.timing_warnings(), vec![TimingWarning::BelowTimerResolution { period_ms: 1.0 }]);
+    }
+
+    #[test]
+    fn test_dc_dc_efficiency_reports_one_error() {
+        for efficiency in [0.0, -0.5, 1.5, f64::NAN] {
+            let scenario = Scenario { dc_dc_efficiency: Some(efficiency), ..Scenario::new() };
+            assert_eq!(scenario.validate().unwrap_err().len(), 1, "efficiency {}", efficiency);
+        }
+        assert_eq!(Scenario { dc_dc_efficiency: Some(1.0), ..Scenario::new() }.validate(), Ok(()));
+    }
+}
@@ -0,0 +1,89 @@
+use std::fmt;
+use std::mem;
+
+use crate::engine::SimulationEngine;
+
+/// Closure applying a parameter change to a running engine.
+pub type ParameterSetter = Box<dyn FnMut(&mut SimulationEngine)>;
+
+struct ScheduledChange {
+    time: f64,
+    label: String,
+    apply: ParameterSetter,
+}
+
+/// Time-ordered parameter changes applied by the engine during a run.
+///
+/// Each change is a closure, so any tunable (resistances, inertias, leak
+/// rates, controller setpoints, ...) can be stepped mid-run. A change
+/// scheduled at `t` takes effect from the first step starting at or after `t`
+/// and runs exactly once.
+#[derive(Default)]
+pub struct ParameterSchedule {
+    pending: Vec<ScheduledChange>,
+    applied: Vec<(f64, String)>,
+}
+
+impl ParameterSchedule {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Schedule `apply` at simulated time `time` (s). `label` names the change in logs.
+    pub fn at(mut self, time: f64, label: &str, apply: impl FnMut(&mut SimulationEngine) + 'static) -> Self {
+        self.push(time, label, apply);
+        self
+    }
+
+    pub fn push(&mut self, time: f64, label: &str, apply: impl FnMut(&mut SimulationEngine) + 'static) {
+        self.pending.push(ScheduledChange {
+            time,
+            label: label.to_string(),
+            apply: Box::new(apply),
+        });
+        // Stable sort keeps insertion order for changes at the same time.
+        self.pending.sort_by(|a, b| a.time.total_cmp(&b.time));
+    }
+
+    /// Changes not yet applied.
+    pub fn pending(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// `(time, label)` of every change applied so far, in order.
+    pub fn applied(&self) -> &[(f64, String)] {
+        &self.applied
+    }
+
+    /// Apply every change due at the engine's current time.
+    ///
+    /// The schedule is taken out of the engine while the closures run so they
+    /// can freely mutate it.
+    pub(crate) fn apply_due(engine: &mut SimulationEngine) {
+        if !engine.schedule.pending.first().is_some_and(|change| change.time <= engine.time) {
+            return;
+        }
+        let mut schedule = mem::take(&mut engine.schedule);
+        while schedule.pending.first().is_some_and(|change| change.time <= engine.time) {
+            let mut change = schedule.pending.remove(0);
+            log::info!("t = {:.2} s: applying scheduled change `{}`", engine.time, change.label);
+            (change.apply)(engine);
+            schedule.applied.push((engine.time, change.label));
+        }
+        // Keep anything a closure scheduled on the engine meanwhile.
+        for change in mem::take(&mut engine.schedule.pending) {
+            schedule.pending.push(change);
+        }
+        schedule.pending.sort_by(|a, b| a.time.total_cmp(&b.time));
+        engine.schedule = schedule;
+    }
+}
+
+impl fmt::Debug for ParameterSchedule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ParameterSchedule")
+            .field("pending", &self.pending.iter().map(|c| (c.time, &c.label)).collect::<Vec<_>>())
+            .field("applied", &self.applied)
+            .finish()
+    }
+}
@@ -1,34 +1,262 @@
-use crate::simulation::{FuelCell, Battery};
+use std::fmt;
 
-#[derive(Debug)]
+use serde::{Deserialize, Serialize};
+
+use crate::rng::SimRng;
+use crate::simulation::{AirSupplySystem, Battery, FuelCell};
+
+/// Linear sensor error: a reading becomes `gain * true + offset`.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Calibration {
+    pub offset: f64,
+    pub gain: f64,
+}
+
+impl Calibration {
+    pub fn new(offset: f64, gain: f64) -> Self {
+        Self { offset, gain }
+    }
+
+    pub fn apply(&self, value: f64) -> f64 {
+        self.gain * value + self.offset
+    }
+}
+
+impl Default for Calibration {
+    /// A perfectly calibrated sensor.
+    fn default() -> Self {
+        Self::new(0.0, 1.0)
+    }
+}
+
+/// Calibration errors and additive Gaussian noise (standard deviations) on
+/// each sensor reading.
+///
+/// The defaults are perfect sensors, which makes the noisy readers return exact values.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SensorConfig {
+    /// Fuel cell and battery voltage (V).
+    pub voltage_noise: f64,
+    /// Fuel cell and battery current (A).
+    pub current_noise: f64,
+    /// Fuel cell and battery temperature (°C).
+    pub temp_noise: f64,
+    /// Hydrogen flow (mol/s); the stack draws a few mmol/s at its operating point.
+    pub hydrogen_flow_noise: f64,
+    /// Oxygen concentration (0-1 scale).
+    pub oxygen_noise: f64,
+    /// Battery SoC (%).
+    pub soc_noise: f64,
+    pub voltage_calibration: Calibration,
+    pub current_calibration: Calibration,
+    pub temp_calibration: Calibration,
+    pub hydrogen_flow_calibration: Calibration,
+    pub oxygen_calibration: Calibration,
+    pub soc_calibration: Calibration,
+}
+
+/// Calibrated `value` plus N(0, std_dev²) noise; no sample is drawn for a zero deviation.
+fn measure(value: f64, calibration: Calibration, std_dev: f64, rng: &mut SimRng) -> f64 {
+    let value = calibration.apply(value);
+    if std_dev > 0.0 { rng.gaussian(value, std_dev) } else { value }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub struct FuelCellSensorData {
+    /// Simulation time the reading was taken (s).
+    pub timestamp: f64,
     pub voltage: f64,
     pub current: f64,
     pub hydrogen_flow: f64,
     pub temperature: f64,
     pub oxygen_concentration: f64,
+    pub oxygen_excess_ratio: f64,
+    pub air_stoichiometry: f64,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub struct BatterySensorData {
+    /// Simulation time the reading was taken (s).
+    pub timestamp: f64,
     pub soc: f64,
     pub voltage: f64,
     pub current: f64,
     pub temperature: f64,
 }
 
-pub fn read_fuel_cell_sensor(fuel_cell: &FuelCell) -> FuelCellSensorData {
+/// Every plant reading taken at one instant.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SystemSnapshot {
+    /// Simulation time of the readings (s).
+    pub timestamp: f64,
+    pub fuel_cell: FuelCellSensorData,
+    pub battery: BatterySensorData,
+    /// Cathode manifold pressure (Pa).
+    pub manifold_pressure: f64,
+    /// Compressor shaft speed (rad/s).
+    pub compressor_speed: f64,
+}
+
+/// An implausible sensor reading, naming the first check that failed.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub enum SensorFault {
+    /// A reading is NaN or infinite.
+    NonFinite { field: &'static str },
+    VoltageOutOfRange { value: f64, max: f64 },
+    OverTemperature { value: f64, limit: f64 },
+    SocOutOfRange { value: f64 },
+    /// A reading has not moved for the stuck-sensor window.
+    Stuck { field: &'static str, value: f64 },
+}
+
+impl fmt::Display for SensorFault {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SensorFault::NonFinite { field } => write!(f, "sensor `{}` reads a non-finite value", field),
+            SensorFault::VoltageOutOfRange { value, max } => {
+                write!(f, "voltage {:.2} V outside [0, {:.2}] V", value, max)
+            }
+            SensorFault::OverTemperature { value, limit } => {
+                write!(f, "temperature {:.1} °C above the {:.1} °C limit", value, limit)
+            }
+            SensorFault::SocOutOfRange { value } => write!(f, "SoC {:.1} % outside [0, 100] %", value),
+            SensorFault::Stuck { field, value } => write!(f, "sensor `{}` stuck at {:.2}", field, value),
+        }
+    }
+}
+
+/// Bounds a plausible reading must stay within.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct PlausibilityLimits {
+    /// Highest plausible voltage (V); the lowest is 0.
+    pub max_voltage: f64,
+    /// Temperature (°C) above which a reading counts as a fault.
+    pub max_temperature: f64,
+}
+
+/// Plausibility margin over the open-circuit voltage.
+const VOLTAGE_MARGIN: f64 = 1.2;
+/// Default plausibility temperature limit (°C), above any trip temperature.
+const MAX_PLAUSIBLE_TEMPERATURE: f64 = 120.0;
+/// Largest change (°C) between fresh temperature samples that the stuck-sensor
+/// check still counts as no change.
+pub const STUCK_TEMPERATURE_TOLERANCE: f64 = 1e-6;
+
+impl PlausibilityLimits {
+    /// Voltage up to 1.2 × the stack open-circuit voltage.
+    pub fn for_fuel_cell(fuel_cell: &FuelCell) -> Self {
+        Self { max_voltage: VOLTAGE_MARGIN * fuel_cell.base_ocv, max_temperature: MAX_PLAUSIBLE_TEMPERATURE }
+    }
+
+    /// Voltage up to 1.2 × the full-charge open-circuit voltage.
+    pub fn for_battery() -> Self {
+        Self { max_voltage: VOLTAGE_MARGIN * Battery::ocv_at(100.0), max_temperature: MAX_PLAUSIBLE_TEMPERATURE }
+    }
+}
+
+fn check_finite(fields: &[(&'static str, f64)]) -> Result<(), SensorFault> {
+    match fields.iter().find(|(_, value)| !value.is_finite()) {
+        Some(&(field, _)) => Err(SensorFault::NonFinite { field }),
+        None => Ok(()),
+    }
+}
+
+fn check_voltage_and_temperature(voltage: f64, temperature: f64, limits: &PlausibilityLimits) -> Result<(), SensorFault> {
+    if !(0.0..=limits.max_voltage).contains(&voltage) {
+        return Err(SensorFault::VoltageOutOfRange { value: voltage, max: limits.max_voltage });
+    }
+    if temperature > limits.max_temperature {
+        return Err(SensorFault::OverTemperature { value: temperature, limit: limits.max_temperature });
+    }
+    Ok(())
+}
+
+impl FuelCellSensorData {
+    /// Check the reading for plausibility. λ and stoichiometry are infinite at
+    /// no load by definition, so they are not checked.
+    pub fn validate(&self, limits: &PlausibilityLimits) -> Result<(), SensorFault> {
+        check_finite(&[
+            ("voltage", self.voltage),
+            ("current", self.current),
+            ("hydrogen_flow", self.hydrogen_flow),
+            ("temperature", self.temperature),
+            ("oxygen_concentration", self.oxygen_concentration),
+        ])?;
+        check_voltage_and_temperature(self.voltage, self.temperature, limits)
+    }
+}
+
+impl BatterySensorData {
+    pub fn validate(&self, limits: &PlausibilityLimits) -> Result<(), SensorFault> {
+        check_finite(&[
+            ("soc", self.soc),
+            ("voltage", self.voltage),
+            ("current", self.current),
+            ("temperature", self.temperature),
+        ])?;
+        check_voltage_and_temperature(self.voltage, self.temperature, limits)?;
+        if !(0.0..=100.0).contains(&self.soc) {
+            return Err(SensorFault::SocOutOfRange { value: self.soc });
+        }
+        Ok(())
+    }
+}
+
+/// Slack on the sample-due check so accumulated `time += dt` error does not skip a sample.
+const SAMPLE_TIME_TOLERANCE: f64 = 1e-9;
+
+/// Sample-and-hold wrapper: a fresh reading at most every `sample_period`
+/// seconds, the previous one in between. Models a sensor slower than the loop.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SampledSensor<T> {
+    /// Time between fresh readings (s); 0 samples on every call.
+    pub sample_period: f64,
+    /// The held reading and the time it was taken.
+    last: Option<(f64, T)>,
+}
+
+impl<T: Clone> SampledSensor<T> {
+    pub fn new(sample_period: f64) -> Self {
+        Self { sample_period, last: None }
+    }
+
+    /// The reading at `time`: calls `read` if a sample is due, otherwise
+    /// returns the held value without calling it.
+    pub fn sample(&mut self, time: f64, read: impl FnOnce() -> T) -> T {
+        match &self.last {
+            Some((sampled_at, value)) if time - sampled_at + SAMPLE_TIME_TOLERANCE < self.sample_period => value.clone(),
+            _ => {
+                let value = read();
+                self.last = Some((time, value.clone()));
+                value
+            }
+        }
+    }
+
+    /// Time the held reading was taken, if any.
+    pub fn last_sample_time(&self) -> Option<f64> {
+        self.last.as_ref().map(|(sampled_at, _)| *sampled_at)
+    }
+}
+
+pub fn read_fuel_cell_sensor(fuel_cell: &FuelCell, time: f64) -> FuelCellSensorData {
     FuelCellSensorData {
+        timestamp: time,
         voltage: fuel_cell.voltage,
         current: fuel_cell.current,
         hydrogen_flow: fuel_cell.hydrogen_flow,
         temperature: fuel_cell.temperature,
         oxygen_concentration: fuel_cell.oxygen_concentration,
+        oxygen_excess_ratio: fuel_cell.oxygen_excess_ratio,
+        air_stoichiometry: fuel_cell.air_stoichiometry,
     }
 }
 
-pub fn read_battery_sensor(battery: &Battery) -> BatterySensorData {
+pub fn read_battery_sensor(battery: &Battery, time: f64) -> BatterySensorData {
     BatterySensorData {
+        timestamp: time,
         soc: battery.soc,
         voltage: battery.voltage,
         current: battery.current,
@@ -36,22 +264,173 @@ pub fn read_battery_sensor(battery: &Battery) -> BatterySensorData {
     }
 }
 
+/// Read the fuel cell, battery and air supply together at `time`.
+pub fn read_system(fuel_cell: &FuelCell, battery: &Battery, air_supply: &AirSupplySystem, time: f64) -> SystemSnapshot {
+    SystemSnapshot {
+        timestamp: time,
+        fuel_cell: read_fuel_cell_sensor(fuel_cell, time),
+        battery: read_battery_sensor(battery, time),
+        manifold_pressure: air_supply.manifold.pressure,
+        compressor_speed: air_supply.compressor.speed,
+    }
+}
+
+pub fn read_fuel_cell_sensor_noisy(fuel_cell: &FuelCell, time: f64, config: &SensorConfig, rng: &mut SimRng) -> FuelCellSensorData {
+    let exact = read_fuel_cell_sensor(fuel_cell, time);
+    FuelCellSensorData {
+        voltage: measure(exact.voltage, config.voltage_calibration, config.voltage_noise, rng),
+        current: measure(exact.current, config.current_calibration, config.current_noise, rng),
+        hydrogen_flow: measure(exact.hydrogen_flow, config.hydrogen_flow_calibration, config.hydrogen_flow_noise, rng),
+        temperature: measure(exact.temperature, config.temp_calibration, config.temp_noise, rng),
+        oxygen_concentration: measure(exact.oxygen_concentration, config.oxygen_calibration, config.oxygen_noise, rng),
+        ..exact
+    }
+}
+
+pub fn read_battery_sensor_noisy(battery: &Battery, time: f64, config: &SensorConfig, rng: &mut SimRng) -> BatterySensorData {
+    let exact = read_battery_sensor(battery, time);
+    BatterySensorData {
+        timestamp: time,
+        soc: measure(exact.soc, config.soc_calibration, config.soc_noise, rng),
+        voltage: measure(exact.voltage, config.voltage_calibration, config.voltage_noise, rng),
+        current: measure(exact.current, config.current_calibration, config.current_noise, rng),
+        temperature: measure(exact.temperature, config.temp_calibration, config.temp_noise, rng),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::simulation::{Battery, FuelCell};
+    use crate::simulation::{AirSupplySystem, Battery, FuelCell};
 
     #[test]
     fn test_read_fuel_cell_sensor() {
         let fc = FuelCell::new();
-        let data = read_fuel_cell_sensor(&fc);
+        let data = read_fuel_cell_sensor(&fc, 0.0);
         assert_eq!(data.voltage, 60.0);
     }
 
     #[test]
     fn test_read_battery_sensor() {
         let bat = Battery::new();
-        let data = read_battery_sensor(&bat);
+        let data = read_battery_sensor(&bat, 0.0);
         assert_eq!(data.soc, 100.0);
     }
+
+    #[test]
+    fn test_read_system_shares_one_timestamp() {
+        let mut air_supply = AirSupplySystem::new();
+        air_supply.update(0.5, 0.5, 0.0, true);
+        let snapshot = read_system(&FuelCell::new(), &Battery::new(), &air_supply, 2.5);
+        assert_eq!(snapshot.timestamp, 2.5);
+        assert_eq!(snapshot.fuel_cell.timestamp, 2.5);
+        assert_eq!(snapshot.battery.timestamp, 2.5);
+        assert_eq!(snapshot.manifold_pressure, air_supply.manifold.pressure);
+        assert_eq!(snapshot.compressor_speed, air_supply.compressor.speed);
+        assert!(snapshot.compressor_speed > 0.0);
+    }
+
+    #[test]
+    fn test_sampled_sensor_holds_between_samples() {
+        let fc = FuelCell::new();
+        assert_eq!(read_fuel_cell_sensor(&fc, 3.5).timestamp, 3.5);
+
+        let mut sensor = SampledSensor::new(2.0);
+        let mut reads = 0;
+        let mut held = Vec::new();
+        for step in 0..10 {
+            let time = step as f64 * 0.5;
+            held.push(sensor.sample(time, || {
+                reads += 1;
+                time
+            }));
+        }
+        assert_eq!(reads, 3);
+        assert_eq!(held, [0.0, 0.0, 0.0, 0.0, 2.0, 2.0, 2.0, 2.0, 4.0, 4.0]);
+        assert_eq!(sensor.last_sample_time(), Some(4.0));
+    }
+
+    #[test]
+    fn test_noisy_readings_have_configured_spread() {
+        let fc = FuelCell::new();
+        let mut rng = SimRng::new(7);
+        let exact = read_fuel_cell_sensor_noisy(&fc, 0.0, &SensorConfig::default(), &mut rng);
+        assert_eq!(exact.voltage, fc.voltage);
+
+        let config = SensorConfig { voltage_noise: 0.5, ..SensorConfig::default() };
+        let samples: Vec<f64> = (0..2000).map(|_| read_fuel_cell_sensor_noisy(&fc, 0.0, &config, &mut rng).voltage).collect();
+        let mean = samples.iter().sum::<f64>() / samples.len() as f64;
+        let std_dev = (samples.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / samples.len() as f64).sqrt();
+        assert!((mean - fc.voltage).abs() < 0.05);
+        assert!((std_dev - 0.5).abs() < 0.05);
+        assert_eq!(read_battery_sensor_noisy(&Battery::new(), 0.0, &config, &mut rng).soc, 100.0);
+    }
+
+    #[test]
+    fn test_calibration_applies_gain_then_offset() {
+        let fc = FuelCell::new();
+        let config = SensorConfig {
+            temp_calibration: Calibration::new(2.0, 1.0),
+            voltage_calibration: Calibration::new(-1.0, 0.9),
+            ..SensorConfig::default()
+        };
+        let data = read_fuel_cell_sensor_noisy(&fc, 0.0, &config, &mut SimRng::new(0));
+        assert_eq!(data.temperature, fc.temperature + 2.0);
+        assert_eq!(data.voltage, 0.9 * fc.voltage - 1.0);
+        assert_eq!(data.current, fc.current);
+    }
+
+    #[test]
+    fn test_implausible_readings_are_flagged() {
+        let fc = FuelCell::new();
+        let limits = PlausibilityLimits::for_fuel_cell(&fc);
+        assert_eq!(read_fuel_cell_sensor(&fc, 0.0).validate(&limits), Ok(()));
+        let bad = |edit: fn(&mut FuelCellSensorData)| {
+            let mut data = read_fuel_cell_sensor(&fc, 0.0);
+            edit(&mut data);
+            data.validate(&limits)
+        };
+        assert_eq!(bad(|d| d.current = f64::NAN), Err(SensorFault::NonFinite { field: "current" }));
+        assert_eq!(bad(|d| d.voltage = 80.0), Err(SensorFault::VoltageOutOfRange { value: 80.0, max: 72.0 }));
+        assert!(matches!(bad(|d| d.voltage = -1.0), Err(SensorFault::VoltageOutOfRange { .. })));
+        assert!(matches!(bad(|d| d.temperature = 150.0), Err(SensorFault::OverTemperature { .. })));
+
+        let limits = PlausibilityLimits::for_battery();
+        let mut battery = read_battery_sensor(&Battery::new(), 0.0);
+        assert_eq!(battery.validate(&limits), Ok(()));
+        battery.soc = 104.0;
+        assert_eq!(battery.validate(&limits), Err(SensorFault::SocOutOfRange { value: 104.0 }));
+        battery.temperature = f64::INFINITY;
+        assert_eq!(battery.validate(&limits), Err(SensorFault::NonFinite { field: "temperature" }));
+    }
+
+    #[test]
+    fn test_excess_ratio_dips_on_load_step_then_recovers() {
+        use crate::control::PidController;
+        use crate::simulation::compressor::Compressor;
+
+        let target = 2.0;
+        let mut fc = FuelCell::new();
+        let mut compressor = Compressor::new();
+        let mut pid = PidController::new(0.002, 0.01, 0.0);
+        let ambient = 101325.0;
+        let mut excess_ratios = Vec::new();
+        for step in 0..200 {
+            let load = if step < 100 { 5.0 } else { 20.0 };
+            let lambda = read_fuel_cell_sensor(&fc, 0.0).oxygen_excess_ratio;
+            let measured = if lambda.is_finite() { lambda } else { target };
+            let torque = pid.compute(target, measured, 0.5).max(0.0);
+            let load_torque = compressor.load_torque(ambient, 298.0, ambient);
+            compressor.update(torque, load_torque, 0.5);
+            fc.update(load, 1.0, 1.0, 0.8, 0.5);
+            fc.update_air_ratios(compressor.mass_flow(ambient, 298.0, ambient));
+            excess_ratios.push(read_fuel_cell_sensor(&fc, 0.0).oxygen_excess_ratio);
+        }
+        // Settled at the target before the step.
+        assert!((excess_ratios[99] - target).abs() < 0.05);
+        // The step raises consumption before the compressor can respond.
+        assert!(excess_ratios[100] < target * 0.3);
+        // The controller brings it back.
+        assert!((excess_ratios[199] - target).abs() < 0.05);
+    }
 }
@@ -0,0 +1,82 @@
+use serde::{Deserialize, Serialize};
+
+use super::{FARADAY, GAS_CONSTANT};
+
+/// Anode hydrogen manifold fed by a pressure regulator.
+///
+/// The regulator pushes hydrogen in proportionally to the pressure deficit,
+/// up to `max_supply_flow`; the stack consumes `I·n / 2F` mol/s. When a load
+/// spike outruns the regulator the pressure falls and the stack starves.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AnodeManifold {
+    /// Anode pressure [Pa].
+    pub pressure: f64,
+    /// Anode volume [m³].
+    pub volume: f64,
+    /// Anode gas temperature [K].
+    pub temperature: f64,
+    /// Regulator setpoint [Pa].
+    pub supply_pressure: f64,
+    /// Regulator flow per pascal of deficit [mol/(s·Pa)].
+    pub regulator_gain: f64,
+    /// Regulator flow limit [mol/s].
+    pub max_supply_flow: f64,
+    /// Pressure below which the stack is starved of hydrogen [Pa].
+    pub starvation_pressure: f64,
+    /// Hydrogen supplied in the last update [mol/s].
+    pub supply_flow: f64,
+}
+
+impl AnodeManifold {
+    pub fn new() -> Self {
+        Self {
+            pressure: 150_000.0,
+            volume: 0.005,
+            temperature: 343.0,
+            supply_pressure: 150_000.0,
+            regulator_gain: 1e-6,
+            max_supply_flow: 0.01,
+            starvation_pressure: 110_000.0,
+            supply_flow: 0.0,
+        }
+    }
+
+    /// Hydrogen consumed by `cell_count` cells at `current` [mol/s].
+    pub fn consumption(current: f64, cell_count: u32) -> f64 {
+        current.max(0.0) * cell_count as f64 / (2.0 * FARADAY)
+    }
+
+    /// Advance by `dt` with the stack drawing `consumption` mol/s.
+    pub fn update(&mut self, consumption: f64, dt: f64) {
+        let deficit = (self.supply_pressure - self.pressure).max(0.0);
+        self.supply_flow = (self.regulator_gain * deficit).min(self.max_supply_flow);
+        let dp = GAS_CONSTANT * self.temperature / self.volume * (self.supply_flow - consumption) * dt;
+        self.pressure = (self.pressure + dp).max(0.0);
+    }
+
+    pub fn is_starved(&self) -> bool {
+        self.pressure < self.starvation_pressure
+    }
+}
+
+impl Default for AnodeManifold {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_regulator_holds_pressure_at_steady_load() {
+        let mut anode = AnodeManifold::new();
+        let consumption = AnodeManifold::consumption(10.0, 60);
+        for _ in 0..200 {
+            anode.update(consumption, 0.5);
+        }
+        assert!(!anode.is_starved());
+        assert!((anode.supply_flow - consumption).abs() < 1e-6);
+    }
+}
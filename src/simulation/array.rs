@@ -0,0 +1,38 @@
+use super::FuelCell;
+
+/// Stacks connected in parallel on a common bus, each with its own state.
+#[derive(Debug, Clone)]
+pub struct FuelCellArray {
+    pub stacks: Vec<FuelCell>,
+}
+
+impl FuelCellArray {
+    pub fn new(stacks: Vec<FuelCell>) -> Self {
+        Self { stacks }
+    }
+
+    /// Array of `count` identical default stacks.
+    pub fn uniform(count: usize) -> Self {
+        Self::new(vec![FuelCell::new(); count])
+    }
+
+    /// Update each stack with its share of the load (one entry per stack).
+    pub fn update(&mut self, loads: &[f64], cooling_duty: f64, oxygen_concentration: f64, humidity: f64, dt: f64) {
+        assert_eq!(loads.len(), self.stacks.len(), "one load per stack");
+        for (stack, &load) in self.stacks.iter_mut().zip(loads) {
+            stack.update(load, cooling_duty, oxygen_concentration, humidity, dt);
+        }
+    }
+
+    pub fn total_current(&self) -> f64 {
+        self.stacks.iter().map(|stack| stack.current).sum()
+    }
+
+    /// Hottest minus coolest stack temperature (°C).
+    pub fn temperature_spread(&self) -> f64 {
+        let temperatures = self.stacks.iter().map(|stack| stack.temperature);
+        let max = temperatures.clone().fold(f64::NEG_INFINITY, f64::max);
+        let min = temperatures.fold(f64::INFINITY, f64::min);
+        max - min
+    }
+}
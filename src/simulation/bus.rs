@@ -0,0 +1,121 @@
+use serde::{Deserialize, Serialize};
+
+use super::converter::{ConverterCommand, DcDcConverter};
+
+/// How a [`PowerBus`] divides the demand between the fuel cell and the battery.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum SplitPolicy {
+    /// The fuel cell follows the demand plus any charge request, up to
+    /// `max_current`; the battery covers the shortfall or absorbs the surplus.
+    FuelCellLeading { max_current: f64 },
+    /// The fuel cell supplies a fixed share (0-1) of the demand power and the
+    /// battery the rest; charge requests are ignored.
+    Proportional { fuel_cell_share: f64 },
+}
+
+impl Default for SplitPolicy {
+    fn default() -> Self {
+        SplitPolicy::FuelCellLeading { max_current: f64::MAX }
+    }
+}
+
+/// Source currents chosen by a [`PowerBus`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PowerSplit {
+    /// Current drawn from the fuel cell (A, at the stack voltage).
+    pub fuel_cell_current: f64,
+    /// Battery current (A, at the bus voltage, positive = charging).
+    pub battery_current: f64,
+    /// Power lost in the converter between stack and bus (W).
+    pub converter_loss: f64,
+}
+
+/// DC bus shared by the fuel cell and the battery.
+///
+/// Demand and charge requests are currents at the bus voltage. The split is
+/// solved as a power balance: the fuel cell delivers `I_fc·V_fc` and whatever
+/// is left over (or missing) flows into (or out of) the battery at `V_bus`.
+/// Through a converter the bus only sees the converter's output power.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub struct PowerBus {
+    pub policy: SplitPolicy,
+}
+
+impl PowerBus {
+    pub fn new(policy: SplitPolicy) -> Self {
+        Self { policy }
+    }
+
+    /// Split `demand` (A) plus a battery `charge_request` (A) between the sources.
+    ///
+    /// `fuel_cell_limit` caps the stack current, e.g. 0 while it is tripped. A
+    /// converter commanded to a fixed output current overrides the policy.
+    pub fn split(
+        &self,
+        demand: f64,
+        charge_request: f64,
+        bus_voltage: f64,
+        fuel_cell_voltage: f64,
+        fuel_cell_limit: f64,
+        converter: Option<&DcDcConverter>,
+    ) -> PowerSplit {
+        if bus_voltage <= 0.0 || fuel_cell_voltage <= 0.0 {
+            let battery_current = if bus_voltage > 0.0 { -demand } else { 0.0 };
+            return PowerSplit { fuel_cell_current: 0.0, battery_current, converter_loss: 0.0 };
+        }
+        let demand_power = demand * bus_voltage;
+        let (mut wanted_power, max_current) = match self.policy {
+            SplitPolicy::FuelCellLeading { max_current } => (demand_power + charge_request * bus_voltage, max_current),
+            SplitPolicy::Proportional { fuel_cell_share } => (fuel_cell_share * demand_power, f64::MAX),
+        };
+        if let Some(ConverterCommand::Current(current)) = converter.and_then(|c| c.command) {
+            wanted_power = current * bus_voltage;
+        }
+        let stack_power = converter.map_or(wanted_power, |c| c.input_power_for(wanted_power));
+        let fuel_cell_current = (stack_power / fuel_cell_voltage).clamp(0.0, max_current.min(fuel_cell_limit));
+        let stack_power = fuel_cell_current * fuel_cell_voltage;
+        let delivered = converter.map_or(stack_power, |c| c.output_power(stack_power));
+        let battery_current = (delivered - demand_power) / bus_voltage;
+        PowerSplit { fuel_cell_current, battery_current, converter_loss: stack_power - delivered }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_balances_power_across_voltages() {
+        let bus = PowerBus::new(SplitPolicy::FuelCellLeading { max_current: 20.0 });
+        // 10 A at 50 V from a 60 V stack; no charge request.
+        let split = bus.split(10.0, 0.0, 50.0, 60.0, f64::MAX, None);
+        assert!((split.fuel_cell_current - 500.0 / 60.0).abs() < 1e-12);
+        assert!(split.battery_current.abs() < 1e-12);
+        // The stack limit leaves the battery to cover the shortfall.
+        let split = bus.split(30.0, 0.0, 50.0, 60.0, f64::MAX, None);
+        assert_eq!(split.fuel_cell_current, 20.0);
+        assert!((split.battery_current - (1200.0 - 1500.0) / 50.0).abs() < 1e-12);
+        // A tripped stack supplies nothing.
+        assert_eq!(bus.split(10.0, 5.0, 50.0, 60.0, 0.0, None).battery_current, -10.0);
+
+        let shared = PowerBus::new(SplitPolicy::Proportional { fuel_cell_share: 0.25 });
+        let split = shared.split(10.0, 8.0, 50.0, 60.0, f64::MAX, None);
+        assert!((split.fuel_cell_current * 60.0 - 125.0).abs() < 1e-9);
+        assert!((split.battery_current + 7.5).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_converter_losses_come_out_of_the_stack() {
+        let bus = PowerBus::new(SplitPolicy::FuelCellLeading { max_current: f64::MAX });
+        let converter = DcDcConverter::new(0.9, 0.8);
+        let split = bus.split(10.0, 0.0, 50.0, 60.0, f64::MAX, Some(&converter));
+        // The stack covers the 500 W demand plus the 10 % conversion loss.
+        assert!((split.fuel_cell_current * 60.0 - 500.0 / 0.9).abs() < 1e-9);
+        assert!((split.converter_loss - 500.0 / 0.9 * 0.1).abs() < 1e-9);
+        assert!(split.battery_current.abs() < 1e-9);
+
+        let commanded = converter.with_command(ConverterCommand::Current(4.0));
+        let split = bus.split(10.0, 0.0, 50.0, 60.0, f64::MAX, Some(&commanded));
+        assert!((split.battery_current + 6.0).abs() < 1e-9);
+    }
+}
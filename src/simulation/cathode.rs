@@ -0,0 +1,67 @@
+use serde::{Deserialize, Serialize};
+
+/// Parameters of the cathode channel model.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CathodeConfig {
+    /// Transport lag from manifold to catalyst layer (s). 0 follows the inlet instantly.
+    pub time_constant: f64,
+    /// Drop in normalized oxygen concentration per ampere of stack current.
+    pub depletion_per_amp: f64,
+}
+
+impl Default for CathodeConfig {
+    fn default() -> Self {
+        Self { time_constant: 2.0, depletion_per_amp: 0.02 }
+    }
+}
+
+/// Oxygen along the cathode flow channel.
+///
+/// The concentration relaxes with a first-order lag towards the inlet value
+/// (from the manifold pressure) less a depletion proportional to current, so
+/// it falls under load and trails pressure changes.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CathodeChannel {
+    pub config: CathodeConfig,
+    /// Normalized oxygen concentration at the catalyst layer (0-1).
+    pub concentration: f64,
+}
+
+impl CathodeChannel {
+    pub fn new(config: CathodeConfig) -> Self {
+        Self { config, concentration: 1.0 }
+    }
+
+    /// Steady-state concentration for `inlet` oxygen at `current` (A).
+    pub fn steady_state(&self, inlet: f64, current: f64) -> f64 {
+        (inlet - self.config.depletion_per_amp * current.max(0.0)).clamp(0.0, 1.0)
+    }
+
+    /// Advance by `dt` with `inlet` oxygen and the stack drawing `current`.
+    pub fn update(&mut self, inlet: f64, current: f64, dt: f64) {
+        let target = self.steady_state(inlet, current);
+        self.concentration = if self.config.time_constant > 0.0 {
+            let alpha = 1.0 - (-dt / self.config.time_constant).exp();
+            self.concentration + alpha * (target - self.concentration)
+        } else {
+            target
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_concentration_lags_and_depletes_under_load() {
+        let mut cathode = CathodeChannel::new(CathodeConfig::default());
+        cathode.update(1.0, 10.0, 0.5);
+        assert!(cathode.concentration < 1.0 && cathode.concentration > 0.8);
+        for _ in 0..100 {
+            cathode.update(1.0, 10.0, 0.5);
+        }
+        assert!((cathode.concentration - 0.8).abs() < 1e-6);
+    }
+}
@@ -0,0 +1,33 @@
+use serde::{Deserialize, Serialize};
+
+use crate::rng::SimRng;
+
+/// One cell of a series fuel cell stack.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Cell {
+    /// Open-circuit voltage at ambient temperature (V).
+    pub base_ocv: f64,
+    /// Ohmic resistance of a new membrane (Ω).
+    pub r_internal: f64,
+    /// Terminal voltage from the last update (V).
+    pub voltage: f64,
+}
+
+impl Cell {
+    pub fn new(base_ocv: f64, r_internal: f64) -> Self {
+        Self { base_ocv, r_internal, voltage: base_ocv }
+    }
+
+    /// Split a stack's OCV and resistance over `count` cells, each parameter
+    /// perturbed by a uniform relative `spread` (e.g. 0.02 for ±2 %).
+    pub fn spread(count: u32, stack_ocv: f64, stack_resistance: f64, spread: f64, rng: &mut SimRng) -> Vec<Cell> {
+        let n = count.max(1) as f64;
+        (0..count)
+            .map(|_| {
+                let ocv = stack_ocv / n * (1.0 + rng.uniform(-spread, spread));
+                let r = stack_resistance / n * (1.0 + rng.uniform(-spread, spread));
+                Cell::new(ocv, r)
+            })
+            .collect()
+    }
+}
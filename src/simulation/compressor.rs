@@ -1,9 +1,109 @@
-#[derive(Debug)]
+use serde::{Deserialize, Serialize};
+
+use crate::error::BmsError;
+
+/// Ratio of specific heats of air.
+const GAMMA_AIR: f64 = 1.4;
+/// Specific heat of air at constant pressure [J/(kg·K)].
+const CP_AIR: f64 = 1005.0;
+
+/// Measured compressor map: mass flow and isentropic efficiency on a
+/// (speed, pressure ratio) grid, read by bilinear interpolation.
+///
+/// Queries outside the grid are clamped to its edges.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CompressorMap {
+    /// Speed axis, strictly ascending [rad/s].
+    speeds: Vec<f64>,
+    /// Pressure-ratio axis, strictly ascending.
+    pressure_ratios: Vec<f64>,
+    /// Mass flow [kg/s], `mass_flow[i][j]` at `speeds[i]`, `pressure_ratios[j]`.
+    mass_flow: Vec<Vec<f64>>,
+    /// Isentropic efficiency (0-1], same layout as `mass_flow`.
+    efficiency: Vec<Vec<f64>>,
+}
+
+impl CompressorMap {
+    pub fn new(
+        speeds: Vec<f64>,
+        pressure_ratios: Vec<f64>,
+        mass_flow: Vec<Vec<f64>>,
+        efficiency: Vec<Vec<f64>>,
+    ) -> Result<Self, BmsError> {
+        let invalid = |reason| Err(BmsError::InvalidTable { table: "compressor map", reason });
+        let ascending = |axis: &[f64]| !axis.is_empty() && axis.windows(2).all(|w| w[0] < w[1]);
+        if !ascending(&speeds) || !ascending(&pressure_ratios) {
+            return invalid("axes must be non-empty and strictly ascending");
+        }
+        let shaped = |grid: &[Vec<f64>]| grid.len() == speeds.len() && grid.iter().all(|row| row.len() == pressure_ratios.len());
+        if !shaped(&mass_flow) || !shaped(&efficiency) {
+            return invalid("grid shape must be speeds × pressure ratios");
+        }
+        if efficiency.iter().flatten().any(|&eta| !(eta > 0.0 && eta <= 1.0)) {
+            return invalid("efficiency must be in (0, 1]");
+        }
+        Ok(Self { speeds, pressure_ratios, mass_flow, efficiency })
+    }
+
+    /// Interpolated mass flow [kg/s].
+    pub fn mass_flow(&self, speed: f64, pressure_ratio: f64) -> f64 {
+        self.interpolate(&self.mass_flow, speed, pressure_ratio)
+    }
+
+    /// Interpolated isentropic efficiency.
+    pub fn efficiency(&self, speed: f64, pressure_ratio: f64) -> f64 {
+        self.interpolate(&self.efficiency, speed, pressure_ratio)
+    }
+
+    fn interpolate(&self, grid: &[Vec<f64>], speed: f64, pressure_ratio: f64) -> f64 {
+        let (i, s) = bracket(&self.speeds, speed);
+        let (j, t) = bracket(&self.pressure_ratios, pressure_ratio);
+        let at = |i: usize, j: usize| grid[i.min(self.speeds.len() - 1)][j.min(self.pressure_ratios.len() - 1)];
+        let low = at(i, j) + t * (at(i, j + 1) - at(i, j));
+        let high = at(i + 1, j) + t * (at(i + 1, j + 1) - at(i + 1, j));
+        low + s * (high - low)
+    }
+}
+
+/// Index of the lower grid point and fractional position towards the next,
+/// clamped to the axis.
+fn bracket(axis: &[f64], value: f64) -> (usize, f64) {
+    let last = axis.len() - 1;
+    if last == 0 || value <= axis[0] {
+        return (0, 0.0);
+    }
+    if value >= axis[last] {
+        return (last, 0.0);
+    }
+    let upper = axis.partition_point(|&x| x <= value);
+    let lower = upper - 1;
+    (lower, (value - axis[lower]) / (axis[upper] - axis[lower]))
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Compressor {
     /// Rotational speed (rad/s)
     pub speed: f64,
     /// Combined inertia of the compressor and motor [kg·m²]
     pub inertia: f64,
+    /// Idle speed floor while running, e.g. for air-bearing lubrication (rad/s). 0 disables it.
+    pub min_running_speed: f64,
+    /// Whether the compressor is commanded on; the speed floor only applies while running.
+    pub running: bool,
+    /// Number of times the floor had to hold the speed up after it dropped below the minimum.
+    pub min_speed_warnings: u32,
+    /// True while the floor is holding the speed.
+    floor_active: bool,
+    /// Surge line as the minimum stable flow per unit speed [kg/s per rad/s]; `None` disables it.
+    pub surge_flow_per_speed: Option<f64>,
+    /// Choke line as the maximum flow per unit speed [kg/s per rad/s]; `None` disables it.
+    pub choke_flow_per_speed: Option<f64>,
+    /// True while the operating point is beyond the surge line.
+    pub surge: bool,
+    /// Number of times the operating point crossed into surge.
+    pub surge_events: u32,
+    /// Measured map; `None` uses the analytic placeholder.
+    pub map: Option<CompressorMap>,
 }
 
 impl Compressor {
@@ -12,39 +112,182 @@ impl Compressor {
         Self {
             speed: 0.0,
             inertia: 0.1, // Example inertia value; adjust as needed.
+            min_running_speed: 0.0,
+            running: true,
+            min_speed_warnings: 0,
+            floor_active: false,
+            surge_flow_per_speed: None,
+            choke_flow_per_speed: None,
+            surge: false,
+            surge_events: 0,
+            map: None,
         }
     }
+
+    /// A compressor whose flow and load torque come from a measured map.
+    pub fn with_map(map: CompressorMap) -> Self {
+        Self { map: Some(map), ..Self::new() }
+    }
     
     /// Update the compressor speed based on motor torque input and load torque.
     ///
     /// dω/dt = (T_motor - T_load) / inertia
+    ///
+    /// Once a running compressor has spun up to `min_running_speed` it is held
+    /// there rather than allowed to drop below; each new excursion logs a warning.
     pub fn update(&mut self, motor_torque: f64, load_torque: f64, dt: f64) {
+        let was_above_floor = self.speed >= self.min_running_speed;
         let acceleration = (motor_torque - load_torque) / self.inertia;
         self.speed += acceleration * dt;
         if self.speed < 0.0 {
             self.speed = 0.0;
         }
+        let hold = self.running && was_above_floor && self.speed < self.min_running_speed;
+        if hold {
+            self.speed = self.min_running_speed;
+            if !self.floor_active {
+                self.min_speed_warnings += 1;
+                log::warn!("compressor speed command below minimum running speed {} rad/s; holding floor", self.min_running_speed);
+            }
+        }
+        self.floor_active = hold;
     }
     
-    /// Compute the compressor mass flow rate [kg/s] using a simplified compressor map.
+    /// Compute the compressor mass flow rate [kg/s] from the map, or without one
+    /// from the analytic fit `k·ω·exp(−α(PR − 1))`.
     ///
-    /// This placeholder function uses an exponential decay with respect to the pressure ratio.
+    /// The flow is capped at the choke line. Beyond the surge line it is left at
+    /// the (collapsing) map value, never raised to the line; `check_surge` flags
+    /// that region instead.
     pub fn mass_flow(&self, inlet_pressure: f64, _inlet_temp: f64, outlet_pressure: f64) -> f64 {
+        let flow = self.map_flow(inlet_pressure, outlet_pressure);
+        self.choke_flow_per_speed.map_or(flow, |ratio| flow.min(ratio * self.speed))
+    }
+
+    /// Unclamped flow from the compressor map [kg/s].
+    fn map_flow(&self, inlet_pressure: f64, outlet_pressure: f64) -> f64 {
         // Pressure ratio: outlet/inlet
         let pressure_ratio = outlet_pressure / inlet_pressure;
+        if let Some(map) = &self.map {
+            return map.mass_flow(self.speed, pressure_ratio);
+        }
         // Constants (these would be obtained via curve fitting in a real system)
         let k = 0.001;  // scaling constant for mass flow
         let alpha = 1.0;
         self.speed * k * (-alpha * (pressure_ratio - 1.0)).exp()
     }
+
+    /// Update the `surge` flag for the present operating point and return it.
+    ///
+    /// Surge is flagged when the map flow at this speed and pressure ratio falls
+    /// below the surge line; each new crossing is counted and logged.
+    pub fn check_surge(&mut self, inlet_pressure: f64, outlet_pressure: f64) -> bool {
+        let flow = self.map_flow(inlet_pressure, outlet_pressure);
+        let surging = self.speed > 0.0 && self.surge_flow_per_speed.is_some_and(|ratio| flow < ratio * self.speed);
+        if surging && !self.surge {
+            self.surge_events += 1;
+            log::warn!("compressor surge at pressure ratio {:.2}", outlet_pressure / inlet_pressure);
+        }
+        self.surge = surging;
+        surging
+    }
     
-    /// Compute the load torque required by the compressor (a placeholder).
+    /// Compute the load torque required by the compressor.
     ///
-    /// In practice, this would be derived from the compressor map.
+    /// With a map this is the isentropic compression power divided by the map
+    /// efficiency and the speed; otherwise a placeholder proportional to flow.
     pub fn load_torque(&self, inlet_pressure: f64, inlet_temp: f64, outlet_pressure: f64) -> f64 {
+        if let Some(map) = &self.map {
+            if self.speed <= 0.0 {
+                return 0.0;
+            }
+            let pressure_ratio = outlet_pressure / inlet_pressure;
+            let mass_flow = self.mass_flow(inlet_pressure, inlet_temp, outlet_pressure);
+            let isentropic = CP_AIR * inlet_temp * (pressure_ratio.max(1.0).powf((GAMMA_AIR - 1.0) / GAMMA_AIR) - 1.0);
+            return mass_flow * isentropic / map.efficiency(self.speed, pressure_ratio) / self.speed;
+        }
         // For example, assume load torque is proportional to the mass flow rate.
         let mass_flow = self.mass_flow(inlet_pressure, inlet_temp, outlet_pressure);
         let constant = 50.0; // N·m per (kg/s), arbitrary value.
         constant * mass_flow
     }
 }
+
+/// Input: `(motor_torque, load_torque)`.
+impl super::Step for Compressor {
+    type Input = (f64, f64);
+
+    fn step(&mut self, (motor_torque, load_torque): Self::Input, dt: f64) {
+        self.update(motor_torque, load_torque, dt);
+    }
+}
+
+impl Default for Compressor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_running_compressor_holds_minimum_speed() {
+        let mut compressor = Compressor::new();
+        compressor.min_running_speed = 100.0;
+        compressor.speed = 150.0;
+        for _ in 0..50 {
+            compressor.update(0.0, 5.0, 0.1);
+            assert!(compressor.speed >= 100.0, "speed fell to {}", compressor.speed);
+        }
+        assert_eq!(compressor.min_speed_warnings, 1, "one warning per excursion");
+
+        // Stopped compressors may spin down freely.
+        compressor.running = false;
+        compressor.update(0.0, 5.0, 0.1);
+        assert!(compressor.speed < 100.0);
+    }
+
+    #[test]
+    fn test_map_interpolates_bilinearly() {
+        let map = CompressorMap::new(
+            vec![0.0, 1000.0],
+            vec![1.0, 3.0],
+            vec![vec![0.0, 0.0], vec![0.1, 0.05]],
+            vec![vec![0.6, 0.6], vec![0.7, 0.8]],
+        )
+        .unwrap();
+        assert!((map.mass_flow(500.0, 2.0) - 0.0375).abs() < 1e-12);
+        // Clamped outside the grid.
+        assert!((map.mass_flow(2000.0, 0.5) - 0.1).abs() < 1e-12);
+
+        let mut compressor = Compressor::with_map(map);
+        compressor.speed = 1000.0;
+        let outlet = 2.0 * 101325.0;
+        assert!((compressor.mass_flow(101325.0, 298.0, outlet) - 0.075).abs() < 1e-12);
+        let expected_torque = 0.075 * 1005.0 * 298.0 * (2f64.powf(0.4 / 1.4) - 1.0) / 0.75 / 1000.0;
+        assert!((compressor.load_torque(101325.0, 298.0, outlet) - expected_torque).abs() < 1e-9);
+
+        assert!(CompressorMap::new(vec![1.0, 0.0], vec![1.0], vec![vec![0.0]; 2], vec![vec![0.5]; 2]).is_err());
+    }
+
+    #[test]
+    fn test_choke_caps_flow_and_surge_is_flagged_not_inflated() {
+        let mut compressor = Compressor::new();
+        compressor.speed = 100.0;
+        compressor.surge_flow_per_speed = Some(0.0004);
+        compressor.choke_flow_per_speed = Some(0.0008);
+        // Low pressure ratio: the map asks for 0.1 kg/s, the choke line caps it.
+        assert!((compressor.mass_flow(101325.0, 298.0, 101325.0) - 0.08).abs() < 1e-12);
+        assert!(!compressor.check_surge(101325.0, 101325.0));
+        // High pressure ratio: the map flow drops below the surge line and stays there.
+        let outlet = 3.0 * 101325.0;
+        let flow = compressor.mass_flow(101325.0, 298.0, outlet);
+        assert!((flow - 0.1 * (-2.0f64).exp()).abs() < 1e-12);
+        assert!(flow < 0.0004 * compressor.speed);
+        assert!(compressor.check_surge(101325.0, outlet));
+        assert!(compressor.check_surge(101325.0, outlet));
+        assert_eq!(compressor.surge_events, 1);
+    }
+}
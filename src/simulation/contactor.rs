@@ -0,0 +1,38 @@
+/// A DC contactor (relay) connecting the battery to the bus.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Contactor {
+    closed: bool,
+    /// Number of close operations (wear counter).
+    pub close_count: u32,
+}
+
+impl Contactor {
+    /// Create an open contactor.
+    pub fn new() -> Self {
+        Self {
+            closed: false,
+            close_count: 0,
+        }
+    }
+
+    pub fn close(&mut self) {
+        if !self.closed {
+            self.closed = true;
+            self.close_count += 1;
+        }
+    }
+
+    pub fn open(&mut self) {
+        self.closed = false;
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.closed
+    }
+}
+
+impl Default for Contactor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,173 @@
+use serde::{Deserialize, Serialize};
+
+use crate::error::BmsError;
+
+/// Stack heat (model units) per watt of converter loss. The stack's own heat
+/// model books 2.5 units per amp, which is about 60 W of heat at 60 V, so one
+/// unit is roughly 25 W.
+pub const CONVERTER_HEAT_PER_WATT: f64 = 0.04;
+
+/// Setpoint a [`DcDcConverter`] regulates on its output side.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum ConverterCommand {
+    /// Hold the output at this voltage (V).
+    Voltage(f64),
+    /// Deliver this current onto the bus (A); honoured by the power bus split.
+    Current(f64),
+}
+
+/// DC-DC converter between the fuel cell and the battery bus.
+///
+/// Steps the input voltage by `voltage_ratio` (or holds a commanded output
+/// voltage) and delivers `efficiency` of the input power; the rest is lost as heat.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DcDcConverter {
+    /// Output power / input power (0-1] when no efficiency curve is set.
+    pub efficiency: f64,
+    /// Output voltage / input voltage when no voltage is commanded.
+    pub voltage_ratio: f64,
+    /// Efficiency against input power as `(watts, efficiency)` breakpoints,
+    /// interpolated linearly and clamped at the ends. Empty uses `efficiency`.
+    pub efficiency_curve: Vec<(f64, f64)>,
+    /// Commanded output voltage or current; `None` follows `voltage_ratio`.
+    pub command: Option<ConverterCommand>,
+    /// Stack heat (model units) per watt of conversion loss; defaults to
+    /// [`CONVERTER_HEAT_PER_WATT`]. The converter sits on the stack's cooling
+    /// loop, so its loss heats the stack.
+    pub heat_per_watt: f64,
+}
+
+impl DcDcConverter {
+    pub fn new(efficiency: f64, voltage_ratio: f64) -> Self {
+        Self {
+            efficiency,
+            voltage_ratio,
+            efficiency_curve: Vec::new(),
+            command: None,
+            heat_per_watt: CONVERTER_HEAT_PER_WATT,
+        }
+    }
+
+    /// Replace the constant efficiency with a curve (see [`check_efficiency_curve`]).
+    pub fn with_efficiency_curve(mut self, curve: Vec<(f64, f64)>) -> Result<Self, BmsError> {
+        check_efficiency_curve(&curve)?;
+        self.efficiency_curve = curve;
+        Ok(self)
+    }
+
+    pub fn with_command(mut self, command: ConverterCommand) -> Self {
+        self.command = Some(command);
+        self
+    }
+
+    /// Efficiency at a given input power (W).
+    pub fn efficiency_at(&self, input_power: f64) -> f64 {
+        let curve = &self.efficiency_curve;
+        let (Some(&(p0, e0)), Some(&(pn, en))) = (curve.first(), curve.last()) else {
+            return self.efficiency;
+        };
+        if input_power <= p0 {
+            return e0;
+        }
+        if input_power >= pn {
+            return en;
+        }
+        let i = curve.partition_point(|&(p, _)| p <= input_power);
+        let ((pa, ea), (pb, eb)) = (curve[i - 1], curve[i]);
+        ea + (eb - ea) * (input_power - pa) / (pb - pa)
+    }
+
+    pub fn output_voltage(&self, input_voltage: f64) -> f64 {
+        match self.command {
+            Some(ConverterCommand::Voltage(voltage)) => voltage,
+            _ => self.voltage_ratio * input_voltage,
+        }
+    }
+
+    /// Power delivered for a given input power (W).
+    pub fn output_power(&self, input_power: f64) -> f64 {
+        let input_power = input_power.max(0.0);
+        self.efficiency_at(input_power) * input_power
+    }
+
+    /// Input power needed to deliver `output_power` (W).
+    pub fn input_power_for(&self, output_power: f64) -> f64 {
+        let output_power = output_power.max(0.0);
+        // Fixed-point on P_in = P_out / η(P_in); η varies slowly with power.
+        let mut input_power = output_power / self.efficiency;
+        for _ in 0..20 {
+            input_power = output_power / self.efficiency_at(input_power);
+        }
+        input_power
+    }
+
+    /// Output current for a given input voltage and current (A).
+    pub fn output_current(&self, input_voltage: f64, input_current: f64) -> f64 {
+        let output_voltage = self.output_voltage(input_voltage);
+        if output_voltage <= 0.0 {
+            return 0.0;
+        }
+        self.output_power(input_voltage * input_current) / output_voltage
+    }
+
+    /// Power lost in conversion for a given input power (W).
+    pub fn loss(&self, input_power: f64) -> f64 {
+        input_power.max(0.0) - self.output_power(input_power)
+    }
+
+    /// Heat the conversion loss adds to the stack (model units).
+    pub fn heat(&self, input_power: f64) -> f64 {
+        self.heat_per_watt * self.loss(input_power)
+    }
+}
+
+impl Default for DcDcConverter {
+    fn default() -> Self {
+        // ~60 V stack onto a ~53 V battery bus.
+        Self::new(0.95, 53.0 / 60.0)
+    }
+}
+
+/// Check that an efficiency curve has strictly increasing powers and efficiencies in (0, 1].
+pub fn check_efficiency_curve(curve: &[(f64, f64)]) -> Result<(), BmsError> {
+    let invalid = |reason| Err(BmsError::InvalidTable { table: "converter efficiency", reason });
+    if curve.iter().any(|&(_, e)| e == 0.0 || !(0.0..=1.0).contains(&e)) {
+        return invalid("efficiencies must lie in (0, 1]");
+    }
+    if curve.windows(2).any(|w| w[1].0 <= w[0].0) {
+        return invalid("powers must be strictly increasing");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_output_power_is_input_power_times_efficiency() {
+        let converter = DcDcConverter::new(0.9, 0.8);
+        let (voltage, current) = (60.0, 10.0);
+        let output_power = converter.output_voltage(voltage) * converter.output_current(voltage, current);
+        assert!((output_power - 0.9 * voltage * current).abs() < 1e-9);
+        assert!((converter.loss(voltage * current) - 60.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_efficiency_curve_and_commanded_voltage() {
+        let converter = DcDcConverter::new(0.95, 0.8)
+            .with_efficiency_curve(vec![(0.0, 0.80), (500.0, 0.96), (1000.0, 0.92)])
+            .unwrap()
+            .with_command(ConverterCommand::Voltage(48.0));
+        assert!((converter.efficiency_at(250.0) - 0.88).abs() < 1e-12);
+        assert_eq!(converter.efficiency_at(2000.0), 0.92);
+        // Light load converts less efficiently and so loses a larger share.
+        assert!(converter.loss(100.0) / 100.0 > converter.loss(500.0) / 500.0);
+        assert!((converter.output_current(60.0, 10.0) * 48.0 - 0.952 * 600.0).abs() < 1e-9);
+        let input = converter.input_power_for(400.0);
+        assert!((converter.output_power(input) - 400.0).abs() < 1e-9);
+
+        let unsorted = DcDcConverter::default().with_efficiency_curve(vec![(500.0, 0.9), (100.0, 0.9)]);
+        assert!(matches!(unsorted, Err(BmsError::InvalidTable { .. })));
+    }
+}
@@ -0,0 +1,38 @@
+use serde::{Deserialize, Serialize};
+
+/// Lumped coolant node between the stack body and ambient.
+///
+/// The stack exchanges heat with the coolant through `conductance`; the
+/// coolant rejects heat to ambient through the radiator. Its temperature
+/// therefore lags the cell temperature by roughly `thermal_mass / conductance`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CoolantLoop {
+    /// Coolant (outlet) temperature [°C].
+    pub temperature: f64,
+    /// Coolant heat capacity (model units, as `FuelCell::thermal_mass`).
+    pub thermal_mass: f64,
+    /// Stack-to-coolant thermal conductance (model units per °C).
+    pub conductance: f64,
+}
+
+impl CoolantLoop {
+    pub fn new(temperature: f64) -> Self {
+        Self {
+            temperature,
+            thermal_mass: 60.0,
+            conductance: 2.0,
+        }
+    }
+
+    /// Heat flowing from a stack at `stack_temperature` into the coolant.
+    pub fn heat_from(&self, stack_temperature: f64) -> f64 {
+        self.conductance * (stack_temperature - self.temperature)
+    }
+
+    /// Advance by `dt` given the heat absorbed from the stack and the radiator
+    /// rejection rate to `ambient_temp`.
+    pub fn update(&mut self, heat_in: f64, rejection_rate: f64, ambient_temp: f64, dt: f64) {
+        let heat_out = rejection_rate * (self.temperature - ambient_temp);
+        self.temperature += dt * (heat_in - heat_out) / self.thermal_mass;
+    }
+}
@@ -0,0 +1,63 @@
+use std::collections::VecDeque;
+
+use serde::{Deserialize, Serialize};
+
+/// Pure transport delay implemented as a ring buffer of past samples.
+///
+/// Unlike a first-order lag, the output is the input from exactly
+/// `dead_time` seconds ago (rounded to whole steps).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DelayLine {
+    /// Dead time [s]. Zero passes the input straight through.
+    pub dead_time: f64,
+    /// Output before any delayed sample is available.
+    pub initial_value: f64,
+    buffer: VecDeque<f64>,
+}
+
+impl DelayLine {
+    pub fn new(dead_time: f64, initial_value: f64) -> Self {
+        Self {
+            dead_time,
+            initial_value,
+            buffer: VecDeque::new(),
+        }
+    }
+
+    /// Push the current input and return the delayed output for a step of `dt`.
+    pub fn push(&mut self, value: f64, dt: f64) -> f64 {
+        let steps = if dt > 0.0 { (self.dead_time / dt).round() as usize } else { 0 };
+        if steps == 0 {
+            self.buffer.clear();
+            return value;
+        }
+        // Pad with the initial value when the delay grows (or on first use).
+        while self.buffer.len() < steps {
+            self.buffer.push_front(self.initial_value);
+        }
+        while self.buffer.len() > steps {
+            self.buffer.pop_front();
+        }
+        self.buffer.push_back(value);
+        self.buffer.pop_front().unwrap_or(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_step_appears_after_dead_time() {
+        let mut delay = DelayLine::new(1.0, 0.0);
+        let outputs: Vec<f64> = (0..15).map(|_| delay.push(1.0, 0.1)).collect();
+        assert!(outputs[..10].iter().all(|&v| v == 0.0));
+        assert!(outputs[10..].iter().all(|&v| v == 1.0));
+    }
+
+    #[test]
+    fn test_zero_dead_time_passes_through() {
+        let mut delay = DelayLine::new(0.0, 0.0);
+        assert_eq!(delay.push(3.0, 0.5), 3.0);
+    }
+}
@@ -1,4 +1,6 @@
-#[derive(Debug)]
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Manifold {
     /// Current pressure in the manifold [Pa]
     pub pressure: f64,
@@ -6,6 +8,10 @@ pub struct Manifold {
     pub volume: f64,
     /// Manifold temperature [K]
     pub temperature: f64,
+    /// Partial pressure of water vapor [Pa]
+    pub vapor_pressure: f64,
+    /// Vapor mole fraction of the incoming air
+    pub inlet_vapor_fraction: f64,
 }
 
 impl Manifold {
@@ -15,6 +21,8 @@ impl Manifold {
             volume,
             temperature,
             pressure: initial_pressure,
+            vapor_pressure: 0.0,
+            inlet_vapor_fraction: 0.0,
         }
     }
     
@@ -33,19 +41,20 @@ impl Manifold {
     /// is_discharging: true when the system is in discharge mode.
     pub fn update(&mut self, mass_flow_in: f64, mass_flow_out: f64, dt: f64, is_discharging: bool) {
         let r_air = 287.0;
+        let vapor_fraction = if self.pressure > 0.0 { self.vapor_pressure / self.pressure } else { 0.0 };
         let ambient_pressure = 101325.0;
         let target_pressure = 380000.0; // 4 bar target
         
         // Mass balance: increase pressure if inflow exceeds outflow.
-        let dP_mass = (r_air * self.temperature / self.volume) * (mass_flow_in - mass_flow_out) * dt;
+        let dp_mass = (r_air * self.temperature / self.volume) * (mass_flow_in - mass_flow_out) * dt;
         
         // Baseline leak: continuously vent a fraction of the excess pressure.
         let k_leak = 0.05;
-        let dP_leak = k_leak * (self.pressure - ambient_pressure) * dt;
+        let dp_leak = k_leak * (self.pressure - ambient_pressure) * dt;
         
         // Active vent: if pressure exceeds the target, vent extra pressure.
         let k_vent = if is_discharging { 0.1 } else { 0.05 };
-        let dP_vent = if self.pressure > target_pressure {
+        let dp_vent = if self.pressure > target_pressure {
             k_vent * (self.pressure - target_pressure) * dt
         } else {
             0.0
@@ -54,17 +63,62 @@ impl Manifold {
         // Additional control term: proportional feedback that subtracts pressure
         // proportional to the error above target. Use a higher gain during discharge.
         let k_control = if is_discharging { 0.2 } else { 0.1 };
-        let dP_control = if self.pressure > target_pressure {
+        let dp_control = if self.pressure > target_pressure {
             k_control * (self.pressure - target_pressure) * dt
         } else {
             0.0
         };
         
-        self.pressure += dP_mass - dP_leak - dP_vent - dP_control;
+        self.pressure += dp_mass - dp_leak - dp_vent - dp_control;
         
         // Ensure pressure does not fall below ambient.
         if self.pressure < ambient_pressure {
             self.pressure = ambient_pressure;
         }
+
+        // Incoming air dilutes the vapor fraction toward the inlet's; outflow
+        // and venting remove vapor and dry air alike. Excess vapor condenses.
+        let gas_mass = self.pressure * self.volume / (r_air * self.temperature);
+        let mixing = (mass_flow_in.max(0.0) * dt / gas_mass).min(1.0);
+        let vapor_fraction = vapor_fraction + mixing * (self.inlet_vapor_fraction - vapor_fraction);
+        self.vapor_pressure = (vapor_fraction * self.pressure).min(saturation_pressure(self.temperature - 273.15));
+    }
+
+    /// Relative humidity of the manifold gas (0-1).
+    pub fn relative_humidity(&self) -> f64 {
+        (self.vapor_pressure / saturation_pressure(self.temperature - 273.15)).clamp(0.0, 1.0)
+    }
+}
+
+/// Input: `(mass_flow_in, mass_flow_out, is_discharging)`.
+impl super::Step for Manifold {
+    type Input = (f64, f64, bool);
+
+    fn step(&mut self, (mass_flow_in, mass_flow_out, is_discharging): Self::Input, dt: f64) {
+        self.update(mass_flow_in, mass_flow_out, dt, is_discharging);
+    }
+}
+
+/// Saturation vapor pressure of water [Pa] at `celsius` (Magnus formula).
+pub fn saturation_pressure(celsius: f64) -> f64 {
+    611.2 * (17.62 * celsius / (243.12 + celsius)).exp()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_humid_inflow_raises_relative_humidity() {
+        let mut manifold = Manifold::new(0.1, 298.0, 101325.0);
+        // Ambient air at 50 % RH and 25 °C, compressed into the manifold.
+        manifold.inlet_vapor_fraction = 0.5 * saturation_pressure(24.85) / 101325.0;
+        for _ in 0..200 {
+            manifold.update(0.05, 0.04, 0.5, true);
+        }
+        assert!(manifold.pressure > 101325.0);
+        let expected = manifold.inlet_vapor_fraction * manifold.pressure / saturation_pressure(24.85);
+        assert!((manifold.relative_humidity() - expected.min(1.0)).abs() < 1e-3);
+        assert!(manifold.relative_humidity() > 0.5, "compression raises the relative humidity");
     }
 }
@@ -0,0 +1,277 @@
+use serde::{Deserialize, Serialize};
+
+use crate::energy::EnergyLedger;
+
+use super::Battery;
+
+/// How a [`BatteryPack`] equalizes cell states of charge.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum BalancingStrategy {
+    /// No balancing.
+    #[default]
+    None,
+    /// Bleed resistors discharge cells above the mean SoC; the energy is lost as heat.
+    Passive,
+    /// A charge shuttle moves charge from the highest cell to the lowest one.
+    Active,
+}
+
+/// `series × parallel` cells with a balancing circuit.
+///
+/// Cells are stored series group by series group: `cells[s * parallel + p]`.
+/// Voltages add across series groups; the cells of a group share its current.
+///
+/// This is a library-level model: the engine runs a single lumped [`Battery`],
+/// so no `Scenario` field selects a strategy. Callers that build a pack (it
+/// deserializes, strategy included) drive [`BatteryPack::balance`] with their
+/// own ledger.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BatteryPack {
+    pub cells: Vec<Battery>,
+    /// Number of series groups.
+    pub series: usize,
+    /// Cells in parallel within each group.
+    pub parallel: usize,
+    pub strategy: BalancingStrategy,
+    /// Master switch for the balancing circuit, whatever the strategy.
+    pub balancing_enabled: bool,
+    /// Active shuttle current (A).
+    pub balancing_current: f64,
+    /// SoC spread (percentage points) below which a cell is considered balanced.
+    pub balancing_threshold: f64,
+    /// Fraction of the charge taken by the active shuttle that reaches the low cell.
+    pub shuttle_efficiency: f64,
+    /// Passive bleed resistor across each cell (Ω); a cell bleeds at `ocv / balancing_resistance`.
+    pub balancing_resistance: f64,
+    /// Highest cell SoC (%) from which passive balancing runs, e.g. 90 to
+    /// balance only near full; 0 balances at any SoC.
+    pub balancing_start_soc: f64,
+}
+
+impl BatteryPack {
+    /// A pack of `series × parallel` identical default cells, without balancing.
+    pub fn new(series: usize, parallel: usize) -> Self {
+        let mut pack = Self::from_cells(vec![Battery::new(); series * parallel], BalancingStrategy::None);
+        pack.series = series;
+        pack.parallel = parallel;
+        pack
+    }
+
+    /// A series string of the given cells.
+    pub fn from_cells(cells: Vec<Battery>, strategy: BalancingStrategy) -> Self {
+        Self {
+            series: cells.len(),
+            parallel: 1,
+            cells,
+            strategy,
+            balancing_enabled: true,
+            balancing_current: 0.5,
+            balancing_threshold: 0.5,
+            shuttle_efficiency: 0.95,
+            balancing_resistance: 100.0,
+            balancing_start_soc: 0.0,
+        }
+    }
+
+    /// Apply a pack current (A, positive = charging) for `dt` seconds.
+    ///
+    /// Within a series group the current divides by cell conductance, so a
+    /// high-resistance cell carries less of it.
+    pub fn update(&mut self, current: f64, dt: f64) {
+        for group in self.cells.chunks_mut(self.parallel.max(1)) {
+            let conductance: f64 = group.iter().map(|cell| 1.0 / cell.effective_resistance()).sum();
+            for cell in group.iter_mut() {
+                let share = current * (1.0 / cell.effective_resistance()) / conductance;
+                cell.update(share.max(0.0), (-share).max(0.0), false, dt);
+            }
+        }
+    }
+
+    /// Pack terminal voltage: the sum of the series groups' mean cell voltages (V).
+    pub fn pack_voltage(&self) -> f64 {
+        self.cells
+            .chunks(self.parallel.max(1))
+            .map(|group| group.iter().map(|cell| cell.voltage).sum::<f64>() / group.len() as f64)
+            .sum()
+    }
+
+    /// Pack SoC (%): the weakest cell's, since it limits the whole pack.
+    pub fn pack_soc(&self) -> f64 {
+        self.soc_bounds().0
+    }
+
+    /// Mean cell SoC (%).
+    pub fn mean_soc(&self) -> f64 {
+        self.cells.iter().map(|cell| cell.soc).sum::<f64>() / self.cells.len().max(1) as f64
+    }
+
+    /// SoC (%) of every cell, in storage order.
+    pub fn cell_socs(&self) -> Vec<f64> {
+        self.cells.iter().map(|cell| cell.soc).collect()
+    }
+
+    /// Highest minus lowest cell SoC (percentage points).
+    pub fn soc_spread(&self) -> f64 {
+        let (min, max) = self.soc_bounds();
+        max - min
+    }
+
+    /// Sum of the cells' available energy (Wh).
+    pub fn total_energy_wh(&self) -> f64 {
+        self.cells.iter().map(Battery::available_energy_wh).sum()
+    }
+
+    /// Run the balancing circuit for `dt` seconds and return the energy it
+    /// dissipated (Wh), for callers without an [`EnergyLedger`].
+    pub fn balance_step(&mut self, dt: f64) -> f64 {
+        let mut ledger = EnergyLedger::new();
+        self.balance(dt, &mut ledger);
+        ledger.balancing_loss_wh
+    }
+
+    /// Run the balancing circuit for `dt` seconds, booking its losses in `ledger`.
+    pub fn balance(&mut self, dt: f64, ledger: &mut EnergyLedger) {
+        if !self.balancing_enabled || self.cells.len() < 2 || self.soc_spread() <= self.balancing_threshold {
+            return;
+        }
+        let charge_ah = self.balancing_current * dt / 3600.0;
+        let energy_before = self.total_energy_wh();
+        match self.strategy {
+            BalancingStrategy::None => return,
+            BalancingStrategy::Passive => {
+                let max = self.soc_bounds().1;
+                if max < self.balancing_start_soc || self.balancing_resistance <= 0.0 {
+                    return;
+                }
+                let mean = self.mean_soc();
+                for cell in &mut self.cells {
+                    if cell.soc - mean > self.balancing_threshold {
+                        let bleed_current = Battery::ocv_at(cell.soc) / self.balancing_resistance;
+                        shift_charge(cell, -bleed_current * dt / 3600.0);
+                    }
+                }
+            }
+            BalancingStrategy::Active => {
+                let soc = |i: &usize| self.cells[*i].soc;
+                let high = (0..self.cells.len()).max_by(|a, b| soc(a).total_cmp(&soc(b))).unwrap();
+                let low = (0..self.cells.len()).min_by(|a, b| soc(a).total_cmp(&soc(b))).unwrap();
+                shift_charge(&mut self.cells[high], -charge_ah);
+                shift_charge(&mut self.cells[low], charge_ah * self.shuttle_efficiency);
+            }
+        }
+        ledger.record_balancing_loss(energy_before - self.total_energy_wh());
+    }
+
+    fn soc_bounds(&self) -> (f64, f64) {
+        self.cells
+            .iter()
+            .fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), cell| (min.min(cell.soc), max.max(cell.soc)))
+    }
+}
+
+/// Add `charge_ah` (negative removes) to a cell, scaled by its usable capacity.
+fn shift_charge(cell: &mut Battery, charge_ah: f64) {
+    let capacity_ah = cell.capacity_ah * cell.soh;
+    cell.soc = (cell.soc + charge_ah / capacity_ah * 100.0).clamp(0.0, 100.0);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unbalanced_pack(strategy: BalancingStrategy) -> BatteryPack {
+        let cells = [60.0, 70.0, 80.0]
+            .into_iter()
+            .map(|soc| Battery { soc, ..Battery::new() })
+            .collect();
+        BatteryPack::from_cells(cells, strategy)
+    }
+
+    fn balance_for(pack: &mut BatteryPack, steps: usize) -> EnergyLedger {
+        let mut ledger = EnergyLedger::new();
+        for _ in 0..steps {
+            pack.balance(0.5, &mut ledger);
+        }
+        ledger
+    }
+
+    #[test]
+    fn test_passive_balancing_dissipates_energy() {
+        let mut pack = unbalanced_pack(BalancingStrategy::Passive);
+        let energy_before = pack.total_energy_wh();
+        let ledger = balance_for(&mut pack, 600);
+        assert!(pack.soc_spread() < 10.0);
+        assert!(pack.total_energy_wh() < 0.95 * energy_before);
+        assert!((ledger.balancing_loss_wh - (energy_before - pack.total_energy_wh())).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_active_balancing_roughly_conserves_energy() {
+        let mut pack = unbalanced_pack(BalancingStrategy::Active);
+        let energy_before = pack.total_energy_wh();
+        balance_for(&mut pack, 600);
+        assert!(pack.soc_spread() < 10.0);
+        assert!(pack.total_energy_wh() > 0.97 * energy_before);
+
+        let mut idle = unbalanced_pack(BalancingStrategy::None);
+        balance_for(&mut idle, 600);
+        assert_eq!(idle.soc_spread(), 20.0);
+    }
+
+    #[test]
+    fn test_bleed_balancing_converges_from_five_percent_spread() {
+        let cells = [95.0, 97.5, 100.0]
+            .into_iter()
+            .map(|soc| Battery { soc, ..Battery::with_capacity(2.0) })
+            .collect();
+        let mut pack = BatteryPack::from_cells(cells, BalancingStrategy::Passive);
+        pack.balancing_start_soc = 90.0;
+        let mut disabled = BatteryPack { balancing_enabled: false, ..pack.clone() };
+        let mut below_start = BatteryPack { balancing_start_soc: 100.5, ..pack.clone() };
+
+        let mut bled_wh = 0.0;
+        let mut elapsed = 0.0;
+        while pack.soc_spread() > 2.0 && elapsed < 3600.0 {
+            bled_wh += pack.balance_step(0.5);
+            bled_wh += disabled.balance_step(0.5);
+            bled_wh += below_start.balance_step(0.5);
+            elapsed += 0.5;
+        }
+        // ~0.53 A of bleed brings a 5 % spread on 2 Ah cells under 2 % in minutes.
+        assert!(elapsed > 300.0 && elapsed < 1200.0, "balanced after {} s", elapsed);
+        assert_eq!(pack.cell_socs()[0], 95.0, "the lowest cell never bleeds");
+        assert!(bled_wh > 0.0);
+        assert_eq!(disabled.soc_spread(), 5.0);
+        assert_eq!(below_start.soc_spread(), 5.0);
+    }
+
+    #[test]
+    fn test_weak_cell_limits_series_parallel_pack() {
+        let mut pack = BatteryPack::new(3, 2);
+        for cell in &mut pack.cells {
+            cell.soc = 80.0;
+        }
+        let mut healthy = pack.clone();
+        // One cell of the middle group has lost half its capacity.
+        pack.cells[2].soh = 0.5;
+        for _ in 0..20 {
+            pack.update(-2.0, 0.5);
+            healthy.update(-2.0, 0.5);
+        }
+        assert!(pack.pack_soc() < pack.mean_soc());
+        assert_eq!(pack.pack_soc(), pack.cells[2].soc);
+        // The weak cell's higher resistance steers current to its neighbour.
+        assert!(pack.cells[3].current < pack.cells[2].current);
+        assert!(pack.pack_voltage() < healthy.pack_voltage());
+        assert!((pack.pack_voltage() - 3.0 * pack.cells[0].voltage).abs() < 1.0);
+    }
+
+    #[test]
+    fn test_strategy_is_read_with_the_pack() {
+        let pack = unbalanced_pack(BalancingStrategy::Active);
+        let json = serde_json::to_string(&pack).unwrap();
+        let read: BatteryPack = serde_json::from_str(&json.replace("\"Active\"", "\"Passive\"")).unwrap();
+        assert_eq!(read.strategy, BalancingStrategy::Passive);
+        assert_eq!(read.cell_socs(), pack.cell_socs());
+    }
+}
@@ -0,0 +1,303 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::control::{AirSupplyController, BatteryController, CcCvCharger, OxygenController, ThermalController, ThermalTrip};
+use crate::energy::{EnergyLedger, ExportSink};
+use crate::engine::{ObserverGains, SimulationEngine};
+use crate::error::BmsError;
+use crate::hal::{ActuatorFault, SimulatedActuator, StuckSensorDetector};
+use crate::rng::SimRng;
+use crate::scenario::Scenario;
+use crate::schedule::ParameterSchedule;
+use crate::sensors::SampledSensor;
+use crate::simulation::converter::DcDcConverter;
+use crate::simulation::{AirSupplySystem, Battery, FuelCell};
+use crate::supervisor::{LoadProtection, Supervisor};
+use crate::telemetry::{SinkRegistry, StateDerivatives};
+
+/// Complete serializable engine state.
+///
+/// Telemetry sinks and the parameter schedule hold trait objects and closures,
+/// so they are not captured; re-attach them after restoring. The sensor fault
+/// is re-derived from the next reading.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EngineSnapshot {
+    pub scenario: Scenario,
+    pub fuel_cell: FuelCell,
+    pub battery: Battery,
+    pub air_supply: AirSupplySystem,
+    pub oxygen_controller: OxygenController,
+    pub air_supply_controller: AirSupplyController,
+    pub battery_controller: BatteryController,
+    pub charger: Option<CcCvCharger>,
+    pub observer_gains: ObserverGains,
+    pub supervisor: Supervisor,
+    pub protection: Option<LoadProtection>,
+    pub thermal_controller: ThermalController,
+    pub fuel_cell_trip: ThermalTrip,
+    pub battery_trip: ThermalTrip,
+    pub disturbance_rng: SimRng,
+    pub sensor_rng: SimRng,
+    pub temperature_sensor: SampledSensor<f64>,
+    pub stuck_sensor: Option<StuckSensorDetector>,
+    pub ledger: EnergyLedger,
+    pub converter: Option<DcDcConverter>,
+    pub export: Option<ExportSink>,
+    pub charging_mode: bool,
+    pub cooling_active: bool,
+    pub cooling_duty: f64,
+    pub current_ramp_active: bool,
+    pub motor_torque: f64,
+    pub oxygen_measured: f64,
+    pub air_measured: f64,
+    pub cooling_measured: f64,
+    pub derivatives: Option<StateDerivatives>,
+    pub time: f64,
+    pub steps_taken: usize,
+}
+
+impl EngineSnapshot {
+    pub fn to_json(&self) -> Result<String, BmsError> {
+        serde_json::to_string(self).map_err(|err| BmsError::Serialization { message: err.to_string() })
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, BmsError> {
+        serde_json::from_str(json).map_err(|err| BmsError::Serialization { message: err.to_string() })
+    }
+
+    /// Write the snapshot as JSON, replacing `path` atomically.
+    pub fn save(&self, path: &Path) -> Result<(), BmsError> {
+        let json = serde_json::to_string(self).map_err(|err| persistence_error(path, err))?;
+        let tmp = path.with_extension("tmp");
+        fs::write(&tmp, json).map_err(|err| persistence_error(&tmp, err))?;
+        fs::rename(&tmp, path).map_err(|err| persistence_error(path, err))
+    }
+
+    pub fn load(path: &Path) -> Result<Self, BmsError> {
+        let json = fs::read_to_string(path).map_err(|err| persistence_error(path, err))?;
+        serde_json::from_str(&json).map_err(|err| persistence_error(path, err))
+    }
+}
+
+fn persistence_error(path: &Path, err: impl std::fmt::Display) -> BmsError {
+    BmsError::Persistence {
+        path: path.display().to_string(),
+        message: err.to_string(),
+    }
+}
+
+/// Periodic automatic checkpoints of a running engine.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Checkpointer {
+    pub path: PathBuf,
+    /// Simulated time between checkpoints (s).
+    pub interval: f64,
+    /// Simulated time of the last checkpoint written, if any.
+    pub last_checkpoint: Option<f64>,
+}
+
+impl Checkpointer {
+    pub fn new(path: impl Into<PathBuf>, interval: f64) -> Self {
+        Self {
+            path: path.into(),
+            interval,
+            last_checkpoint: None,
+        }
+    }
+
+    fn is_due(&self, time: f64) -> bool {
+        time - self.last_checkpoint.unwrap_or(0.0) >= self.interval
+    }
+}
+
+impl SimulationEngine {
+    pub fn snapshot(&self) -> EngineSnapshot {
+        EngineSnapshot {
+            scenario: self.scenario.clone(),
+            fuel_cell: self.fuel_cell.clone(),
+            battery: self.battery.clone(),
+            air_supply: self.air_supply.clone(),
+            oxygen_controller: self.oxygen_controller.clone(),
+            air_supply_controller: self.air_supply_controller.clone(),
+            battery_controller: self.battery_controller.clone(),
+            charger: self.charger.clone(),
+            observer_gains: self.observer_gains,
+            supervisor: self.supervisor.clone(),
+            protection: self.protection.clone(),
+            thermal_controller: self.thermal_controller.clone(),
+            fuel_cell_trip: self.fuel_cell_trip.clone(),
+            battery_trip: self.battery_trip.clone(),
+            disturbance_rng: self.disturbance_rng.clone(),
+            sensor_rng: self.sensor_rng.clone(),
+            temperature_sensor: self.temperature_sensor.clone(),
+            stuck_sensor: self.stuck_sensor.clone(),
+            ledger: self.ledger.clone(),
+            converter: self.converter.clone(),
+            export: self.export.clone(),
+            charging_mode: self.charging_mode,
+            cooling_active: self.cooling_active,
+            cooling_duty: self.cooling_duty,
+            current_ramp_active: self.current_ramp_active,
+            motor_torque: self.motor_torque,
+            oxygen_measured: self.oxygen_measured,
+            air_measured: self.air_measured,
+            cooling_measured: self.cooling_measured,
+            derivatives: self.derivatives,
+            time: self.time,
+            steps_taken: self.steps_taken,
+        }
+    }
+
+    /// Rebuild an engine from a snapshot, with no sinks, schedule or checkpoints.
+    pub fn from_snapshot(snapshot: EngineSnapshot) -> Self {
+        let cooling_fan = ActuatorFault::new(SimulatedActuator { state: snapshot.cooling_active }, snapshot.scenario.cooling_fan_fault);
+        Self {
+            scenario: snapshot.scenario,
+            fuel_cell: snapshot.fuel_cell,
+            battery: snapshot.battery,
+            air_supply: snapshot.air_supply,
+            oxygen_controller: snapshot.oxygen_controller,
+            air_supply_controller: snapshot.air_supply_controller,
+            battery_controller: snapshot.battery_controller,
+            charger: snapshot.charger,
+            observer_gains: snapshot.observer_gains,
+            supervisor: snapshot.supervisor,
+            protection: snapshot.protection,
+            thermal_controller: snapshot.thermal_controller,
+            fuel_cell_trip: snapshot.fuel_cell_trip,
+            battery_trip: snapshot.battery_trip,
+            disturbance_rng: snapshot.disturbance_rng,
+            sensor_rng: snapshot.sensor_rng,
+            temperature_sensor: snapshot.temperature_sensor,
+            stuck_sensor: snapshot.stuck_sensor,
+            sensor_fault: None,
+            ledger: snapshot.ledger,
+            converter: snapshot.converter,
+            export: snapshot.export,
+            sinks: SinkRegistry::new(),
+            schedule: ParameterSchedule::new(),
+            checkpoints: None,
+            cooling_fan,
+            charging_mode: snapshot.charging_mode,
+            cooling_active: snapshot.cooling_active,
+            cooling_duty: snapshot.cooling_duty,
+            current_ramp_active: snapshot.current_ramp_active,
+            motor_torque: snapshot.motor_torque,
+            oxygen_measured: snapshot.oxygen_measured,
+            air_measured: snapshot.air_measured,
+            cooling_measured: snapshot.cooling_measured,
+            derivatives: snapshot.derivatives,
+            time: snapshot.time,
+            steps_taken: snapshot.steps_taken,
+        }
+    }
+
+    /// Write a checkpoint to `path` every `interval` seconds of simulated time.
+    pub fn enable_checkpoints(&mut self, path: impl Into<PathBuf>, interval: f64) {
+        self.checkpoints = Some(Checkpointer::new(path, interval));
+    }
+
+    /// Restart from the checkpoint at `path`. Checkpointing continues to the same file.
+    pub fn resume(path: impl Into<PathBuf>, interval: f64) -> Result<Self, BmsError> {
+        let path = path.into();
+        let mut engine = Self::from_snapshot(EngineSnapshot::load(&path)?);
+        let mut checkpoints = Checkpointer::new(path, interval);
+        checkpoints.last_checkpoint = Some(engine.time);
+        engine.checkpoints = Some(checkpoints);
+        Ok(engine)
+    }
+
+    /// Write a checkpoint if one is due. Failures are logged, not fatal, so a
+    /// full disk does not abort a long run.
+    pub(crate) fn checkpoint_if_due(&mut self) {
+        let due = self.checkpoints.as_ref().is_some_and(|c| c.is_due(self.time));
+        if !due {
+            return;
+        }
+        let snapshot = self.snapshot();
+        if let Some(checkpoints) = &mut self.checkpoints {
+            match snapshot.save(&checkpoints.path) {
+                Ok(()) => checkpoints.last_checkpoint = Some(self.time),
+                Err(err) => log::warn!("{}", err),
+            }
+        }
+    }
+}
+
+/// Serde adapter for `f64` fields that may be infinite or NaN, which JSON
+/// cannot represent as numbers; those are stored as strings.
+pub(crate) mod non_finite {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(value: &f64, serializer: S) -> Result<S::Ok, S::Error> {
+        if value.is_finite() {
+            serializer.serialize_f64(*value)
+        } else {
+            serializer.serialize_str(&value.to_string())
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<f64, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Repr {
+            Number(f64),
+            Text(String),
+        }
+        match Repr::deserialize(deserializer)? {
+            Repr::Number(value) => Ok(value),
+            Repr::Text(text) => text.parse().map_err(serde::de::Error::custom),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::telemetry::StepReport;
+
+    #[test]
+    fn test_resume_after_crash_matches_uninterrupted_run() {
+        let scenario = Scenario { load_disturbance_noise: 2.0, seed: 5, ..Scenario::default() };
+        let mut reference = SimulationEngine::new(scenario.clone());
+        let uninterrupted: Vec<StepReport> = (0..60).map(|_| reference.step()).collect();
+
+        let path = std::env::temp_dir().join(format!("bms_checkpoint_{}.json", std::process::id()));
+        let mut crashed = SimulationEngine::new(scenario);
+        crashed.enable_checkpoints(&path, 5.0);
+        for _ in 0..25 {
+            crashed.step();
+        }
+        drop(crashed);
+
+        let mut resumed = SimulationEngine::resume(&path, 5.0).expect("checkpoint readable");
+        // Checkpoints at 5, 10 s: the crash at 12.5 s loses the steps after 10 s.
+        assert_eq!(resumed.time, 10.0);
+        let skipped = (resumed.time / resumed.scenario.dt) as usize;
+        for expected in &uninterrupted[skipped..] {
+            assert_eq!(&resumed.step(), expected);
+        }
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_json_snapshot_resumes_with_tweaked_parameter() {
+        let mut original = SimulationEngine::new(Scenario::default());
+        while original.time < 30.0 {
+            original.step();
+        }
+        let json = original.snapshot().to_json().unwrap();
+
+        let mut resumed = SimulationEngine::from_snapshot(EngineSnapshot::from_json(&json).unwrap());
+        let mut tweaked = EngineSnapshot::from_json(&json).unwrap();
+        tweaked.fuel_cell.r_internal *= 3.0;
+        let mut tweaked = SimulationEngine::from_snapshot(tweaked);
+        let expected = original.step();
+        assert_eq!(resumed.step(), expected);
+        assert!(tweaked.step().fuel_cell_voltage < expected.fuel_cell_voltage);
+
+        assert!(matches!(EngineSnapshot::from_json("{"), Err(BmsError::Serialization { .. })));
+    }
+}
@@ -0,0 +1,297 @@
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+/// Operating mode of the whole system.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SystemState {
+    /// Stack start-up transient in progress.
+    Startup,
+    /// Discharging with the battery at or above its upper SoC threshold.
+    Normal,
+    /// Stack recharging the battery.
+    Charging,
+    /// Battery drawn down towards its lower SoC threshold.
+    Discharging,
+    /// Over-temperature or over-current protection is shedding stack load.
+    Protected,
+    /// A trip or sensor fault is active; the affected units are isolated.
+    Fault,
+}
+
+impl fmt::Display for SystemState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            SystemState::Startup => "startup",
+            SystemState::Normal => "normal",
+            SystemState::Charging => "charging",
+            SystemState::Discharging => "discharging",
+            SystemState::Protected => "protected",
+            SystemState::Fault => "fault",
+        };
+        f.write_str(name)
+    }
+}
+
+/// What the supervisor sees each step.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SupervisorInputs {
+    /// Battery SoC (%).
+    pub soc: f64,
+    /// Charging requested by the SoC hysteresis in `BatteryController`.
+    pub charge_requested: bool,
+    pub fuel_cell_starting: bool,
+    pub fuel_cell_tripped: bool,
+    pub battery_tripped: bool,
+    /// The fuel cell sensor reading failed its plausibility check.
+    pub sensor_fault: bool,
+    /// [`LoadProtection`] is shedding load.
+    pub protected: bool,
+}
+
+/// Per-component commands for one step.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SupervisorCommands {
+    /// The stack may carry load.
+    pub fuel_cell_enabled: bool,
+    /// The battery contactor is closed.
+    pub battery_connected: bool,
+    /// Run the cooling at full duty whatever the thermal controller says.
+    pub force_cooling: bool,
+}
+
+/// Central state machine holding the safety interlocks.
+///
+/// Faults take precedence over every other state and clear as soon as their
+/// cause does; `ThermalTrip` supplies the temperature hysteresis.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Supervisor {
+    pub state: SystemState,
+    /// SoC (%) at or above which discharging counts as `Normal`.
+    pub normal_soc: f64,
+    /// Time (s) spent in the current state.
+    pub time_in_state: f64,
+    /// Number of state changes since creation.
+    pub transitions: u32,
+}
+
+impl Supervisor {
+    /// A supervisor already in the state `initial` calls for, so the first
+    /// update does not report a change the system never made.
+    pub fn new(normal_soc: f64, initial: SupervisorInputs) -> Self {
+        Self { state: Self::state_for(normal_soc, &initial), normal_soc, time_in_state: 0.0, transitions: 0 }
+    }
+
+    /// Advance by `dt` seconds and return the commands for this step.
+    pub fn update(&mut self, inputs: SupervisorInputs, dt: f64) -> SupervisorCommands {
+        let next = Self::state_for(self.normal_soc, &inputs);
+        if next == self.state {
+            self.time_in_state += dt;
+        } else {
+            self.state = next;
+            self.time_in_state = 0.0;
+            self.transitions += 1;
+        }
+        SupervisorCommands {
+            fuel_cell_enabled: !(inputs.fuel_cell_tripped || inputs.sensor_fault),
+            battery_connected: !inputs.battery_tripped,
+            force_cooling: inputs.sensor_fault || inputs.protected,
+        }
+    }
+
+    fn state_for(normal_soc: f64, inputs: &SupervisorInputs) -> SystemState {
+        if inputs.fuel_cell_tripped || inputs.battery_tripped || inputs.sensor_fault {
+            SystemState::Fault
+        } else if inputs.protected {
+            SystemState::Protected
+        } else if inputs.fuel_cell_starting {
+            SystemState::Startup
+        } else if inputs.charge_requested {
+            SystemState::Charging
+        } else if inputs.soc >= normal_soc {
+            SystemState::Normal
+        } else {
+            SystemState::Discharging
+        }
+    }
+}
+
+/// Hard limits beyond which stack load is shed.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ProtectionLimits {
+    /// Measured stack temperature (°C) above which load is shed.
+    pub max_temperature: f64,
+    /// Protection holds until the temperature is this far (°C) below the limit.
+    pub recovery_margin: f64,
+    /// Highest stack current (A); the default sits well above the ~12 A the
+    /// stack draws in normal operation.
+    pub max_current: f64,
+    /// Fraction (0-1) of the commanded load kept while over temperature; 0
+    /// disconnects the stack.
+    pub load_retained: f64,
+}
+
+impl Default for ProtectionLimits {
+    fn default() -> Self {
+        Self { max_temperature: 70.0, recovery_margin: 5.0, max_current: 20.0, load_retained: 0.5 }
+    }
+}
+
+/// Condition that engaged [`LoadProtection`].
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum ProtectionEvent {
+    OverTemperature { temperature: f64, limit: f64 },
+    /// The commanded current (A) exceeded the limit (A).
+    OverCurrent { current: f64, limit: f64 },
+}
+
+impl fmt::Display for ProtectionEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProtectionEvent::OverTemperature { temperature, limit } => {
+                write!(f, "stack at {:.1} °C above the {:.1} °C limit", temperature, limit)
+            }
+            ProtectionEvent::OverCurrent { current, limit } => {
+                write!(f, "commanded {:.2} A above the {:.2} A limit", current, limit)
+            }
+        }
+    }
+}
+
+/// Over-temperature and over-current load shedding.
+///
+/// Over temperature the load is scaled by `load_retained` (with hysteresis);
+/// over current it is clamped to the limit. Either forces cooling on through
+/// the supervisor.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LoadProtection {
+    pub limits: ProtectionLimits,
+    pub over_temperature: bool,
+    pub over_current: bool,
+    /// Number of times protection engaged since creation.
+    pub events: u32,
+}
+
+impl LoadProtection {
+    pub fn new(limits: ProtectionLimits) -> Self {
+        Self { limits, over_temperature: false, over_current: false, events: 0 }
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.over_temperature || self.over_current
+    }
+
+    /// Stack current limit (A).
+    pub fn current_limit(&self) -> f64 {
+        self.limits.max_current
+    }
+
+    /// Shed `load` (A) as the limits require. Returns the load to apply and
+    /// the condition that engaged this step, if any.
+    pub fn apply(&mut self, load: f64, temperature: f64) -> (f64, Option<ProtectionEvent>) {
+        let limits = self.limits;
+        let mut event = None;
+        if self.over_temperature {
+            if temperature <= limits.max_temperature - limits.recovery_margin {
+                self.over_temperature = false;
+            }
+        } else if temperature > limits.max_temperature {
+            self.over_temperature = true;
+            event = Some(ProtectionEvent::OverTemperature { temperature, limit: limits.max_temperature });
+        }
+        let load = if self.over_temperature { load * limits.load_retained } else { load };
+
+        let limit = self.current_limit();
+        let was_over_current = self.over_current;
+        self.over_current = load > limit;
+        if self.over_current && !was_over_current {
+            event = event.or(Some(ProtectionEvent::OverCurrent { current: load, limit }));
+        }
+        if event.is_some() {
+            self.events += 1;
+        }
+        (load.min(limit), event)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn healthy(soc: f64, charge_requested: bool) -> SupervisorInputs {
+        SupervisorInputs {
+            soc,
+            charge_requested,
+            fuel_cell_starting: false,
+            fuel_cell_tripped: false,
+            battery_tripped: false,
+            sensor_fault: false,
+            protected: false,
+        }
+    }
+
+    #[test]
+    fn test_supervisor_walks_the_operating_modes() {
+        let starting = SupervisorInputs { fuel_cell_starting: true, ..healthy(90.0, false) };
+        let mut supervisor = Supervisor::new(75.0, starting);
+        assert_eq!(supervisor.state, SystemState::Startup);
+        supervisor.update(starting, 0.5);
+        assert_eq!(supervisor.state, SystemState::Startup);
+        supervisor.update(healthy(90.0, false), 0.5);
+        assert_eq!(supervisor.state, SystemState::Normal);
+        supervisor.update(healthy(70.0, false), 0.5);
+        assert_eq!(supervisor.state, SystemState::Discharging);
+        let commands = supervisor.update(healthy(64.0, true), 0.5);
+        assert_eq!(supervisor.state, SystemState::Charging);
+        assert!(commands.fuel_cell_enabled && commands.battery_connected);
+        supervisor.update(healthy(66.0, true), 0.5);
+        assert_eq!(supervisor.time_in_state, 0.5);
+        assert_eq!(supervisor.transitions, 3);
+    }
+
+    #[test]
+    fn test_fault_isolates_only_the_affected_unit() {
+        let mut supervisor = Supervisor::new(75.0, healthy(90.0, false));
+        let commands = supervisor.update(SupervisorInputs { sensor_fault: true, ..healthy(90.0, false) }, 0.5);
+        assert_eq!(supervisor.state, SystemState::Fault);
+        assert!(!commands.fuel_cell_enabled && commands.force_cooling && commands.battery_connected);
+
+        let commands = supervisor.update(SupervisorInputs { battery_tripped: true, ..healthy(90.0, false) }, 0.5);
+        assert_eq!(supervisor.state, SystemState::Fault);
+        assert!(commands.fuel_cell_enabled && !commands.battery_connected && !commands.force_cooling);
+
+        supervisor.update(healthy(90.0, false), 0.5);
+        assert_eq!(supervisor.state, SystemState::Normal);
+    }
+
+    #[test]
+    fn test_over_temperature_sheds_load_until_recovery() {
+        let mut protection = LoadProtection::new(ProtectionLimits::default());
+        assert_eq!(protection.apply(10.0, 69.0), (10.0, None));
+        let (load, event) = protection.apply(10.0, 71.0);
+        assert_eq!(load, 5.0);
+        assert_eq!(event, Some(ProtectionEvent::OverTemperature { temperature: 71.0, limit: 70.0 }));
+        // Still shedding inside the recovery band, with no new event.
+        assert_eq!(protection.apply(10.0, 67.0), (5.0, None));
+        assert_eq!(protection.apply(10.0, 65.0), (10.0, None));
+        assert!(!protection.is_active());
+        assert_eq!(protection.events, 1);
+
+        let mut supervisor = Supervisor::new(75.0, healthy(90.0, false));
+        let commands = supervisor.update(SupervisorInputs { protected: true, ..healthy(90.0, false) }, 0.5);
+        assert_eq!(supervisor.state, SystemState::Protected);
+        assert!(commands.force_cooling && commands.fuel_cell_enabled);
+    }
+
+    #[test]
+    fn test_over_current_clamps_to_the_current_limit() {
+        let mut protection = LoadProtection::new(ProtectionLimits { max_current: 8.0, ..ProtectionLimits::default() });
+        let (load, event) = protection.apply(12.0, 40.0);
+        assert_eq!(load, 8.0);
+        assert_eq!(event, Some(ProtectionEvent::OverCurrent { current: 12.0, limit: 8.0 }));
+        assert_eq!(protection.apply(12.0, 40.0), (8.0, None));
+        assert_eq!(protection.apply(6.0, 40.0), (6.0, None));
+        assert!(!protection.is_active());
+    }
+}
@@ -0,0 +1,477 @@
+use std::collections::VecDeque;
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::BmsError;
+
+/// Per-step rates of change of the major states, `(new - old) / dt`.
+///
+/// Debug output for diagnosing stiffness: a derivative that explodes points at
+/// the state (and dt) causing an instability.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub struct StateDerivatives {
+    /// °C/s.
+    pub fuel_cell_temperature: f64,
+    /// 1/s.
+    pub membrane_hydration: f64,
+    /// Pa/s.
+    pub manifold_pressure: f64,
+    /// rad/s².
+    pub compressor_speed: f64,
+    /// %/s.
+    pub battery_soc: f64,
+}
+
+/// Snapshot of the system state emitted after each simulation step.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct StepReport {
+    pub time: f64,
+    pub fuel_cell_voltage: f64,
+    pub fuel_cell_current: f64,
+    pub fuel_cell_temperature: f64,
+    /// Hydrogen supplied to the anode [mol/s].
+    pub hydrogen_flow: f64,
+    pub membrane_hydration: f64,
+    pub oxygen_concentration: f64,
+    pub oxygen_excess_ratio: f64,
+    pub air_stoichiometry: f64,
+    pub manifold_pressure: f64,
+    pub compressor_speed: f64,
+    pub battery_soc: f64,
+    pub battery_voltage: f64,
+    pub battery_current: f64,
+    pub battery_temperature: f64,
+    /// Electrical power delivered by the stack (W, positive = generating).
+    pub fuel_cell_power: f64,
+    /// Battery terminal power (W, positive = charging, negative = discharging).
+    pub battery_power: f64,
+    /// Compressor shaft power, torque × speed (W, positive = consumed).
+    pub compressor_power: f64,
+    /// Power left for the external load: stack output minus battery charging
+    /// and compressor consumption (W).
+    pub net_power: f64,
+    /// Setpoint of the oxygen (load) controller.
+    pub oxygen_setpoint: f64,
+    /// Oxygen concentration read by the sensor.
+    pub oxygen_measured: f64,
+    /// Setpoint of the air supply (compressor) controller: the excess ratio λ
+    /// when `Scenario::oxygen_excess_ratio_setpoint` is set, else the oxygen concentration.
+    pub air_setpoint: f64,
+    /// What the air supply controller measured against `air_setpoint`: λ or
+    /// the oxygen concentration.
+    pub air_measured: f64,
+    /// Cooling switch-on threshold (°C).
+    pub cooling_setpoint: f64,
+    /// Temperature the cooling controller acted on (°C): stack body or coolant node.
+    pub cooling_measured: f64,
+    pub charging_mode: bool,
+    pub cooling_active: bool,
+    /// State derivatives, when `Scenario::record_derivatives` is set.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub derivatives: Option<StateDerivatives>,
+}
+
+/// Numeric telemetry channels of a [`StepReport`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Channel {
+    FuelCellVoltage,
+    FuelCellCurrent,
+    FuelCellTemperature,
+    HydrogenFlow,
+    MembraneHydration,
+    OxygenConcentration,
+    OxygenExcessRatio,
+    AirStoichiometry,
+    ManifoldPressure,
+    CompressorSpeed,
+    BatterySoc,
+    BatteryVoltage,
+    BatteryCurrent,
+    BatteryTemperature,
+}
+
+impl Channel {
+    /// Value of this channel in a report.
+    pub fn value(self, report: &StepReport) -> f64 {
+        match self {
+            Channel::FuelCellVoltage => report.fuel_cell_voltage,
+            Channel::FuelCellCurrent => report.fuel_cell_current,
+            Channel::FuelCellTemperature => report.fuel_cell_temperature,
+            Channel::HydrogenFlow => report.hydrogen_flow,
+            Channel::MembraneHydration => report.membrane_hydration,
+            Channel::OxygenConcentration => report.oxygen_concentration,
+            Channel::OxygenExcessRatio => report.oxygen_excess_ratio,
+            Channel::AirStoichiometry => report.air_stoichiometry,
+            Channel::ManifoldPressure => report.manifold_pressure,
+            Channel::CompressorSpeed => report.compressor_speed,
+            Channel::BatterySoc => report.battery_soc,
+            Channel::BatteryVoltage => report.battery_voltage,
+            Channel::BatteryCurrent => report.battery_current,
+            Channel::BatteryTemperature => report.battery_temperature,
+        }
+    }
+
+    fn value_mut(self, report: &mut StepReport) -> &mut f64 {
+        match self {
+            Channel::FuelCellVoltage => &mut report.fuel_cell_voltage,
+            Channel::FuelCellCurrent => &mut report.fuel_cell_current,
+            Channel::FuelCellTemperature => &mut report.fuel_cell_temperature,
+            Channel::HydrogenFlow => &mut report.hydrogen_flow,
+            Channel::MembraneHydration => &mut report.membrane_hydration,
+            Channel::OxygenConcentration => &mut report.oxygen_concentration,
+            Channel::OxygenExcessRatio => &mut report.oxygen_excess_ratio,
+            Channel::AirStoichiometry => &mut report.air_stoichiometry,
+            Channel::ManifoldPressure => &mut report.manifold_pressure,
+            Channel::CompressorSpeed => &mut report.compressor_speed,
+            Channel::BatterySoc => &mut report.battery_soc,
+            Channel::BatteryVoltage => &mut report.battery_voltage,
+            Channel::BatteryCurrent => &mut report.battery_current,
+            Channel::BatteryTemperature => &mut report.battery_temperature,
+        }
+    }
+}
+
+/// Smoothing applied to one telemetry channel.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Smoothing {
+    /// Pass the raw value through.
+    None,
+    /// Mean of the last `window` samples.
+    MovingAverage { window: usize },
+    /// y += alpha * (x - y), with 0 < alpha <= 1.
+    Exponential { alpha: f64 },
+}
+
+/// Filter state for one channel.
+#[derive(Debug, Clone)]
+struct ChannelFilter {
+    smoothing: Smoothing,
+    samples: VecDeque<f64>,
+    state: Option<f64>,
+}
+
+impl ChannelFilter {
+    fn new(smoothing: Smoothing) -> Self {
+        Self {
+            smoothing,
+            samples: VecDeque::new(),
+            state: None,
+        }
+    }
+
+    fn filter(&mut self, value: f64) -> f64 {
+        match self.smoothing {
+            Smoothing::None => value,
+            Smoothing::MovingAverage { window } => {
+                self.samples.push_back(value);
+                while self.samples.len() > window.max(1) {
+                    self.samples.pop_front();
+                }
+                self.samples.iter().sum::<f64>() / self.samples.len() as f64
+            }
+            Smoothing::Exponential { alpha } => {
+                let next = match self.state {
+                    Some(prev) => prev + alpha * (value - prev),
+                    None => value,
+                };
+                self.state = Some(next);
+                next
+            }
+        }
+    }
+}
+
+/// Per-channel smoothing stage applied to reports before they are emitted.
+///
+/// Only telemetry output passes through here; the control path always sees
+/// the raw values.
+#[derive(Debug, Clone, Default)]
+pub struct Smoother {
+    filters: Vec<(Channel, ChannelFilter)>,
+}
+
+impl Smoother {
+    /// A smoother that passes every channel through unchanged.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Builder-style variant of [`Smoother::set`].
+    pub fn with(mut self, channel: Channel, smoothing: Smoothing) -> Self {
+        self.set(channel, smoothing);
+        self
+    }
+
+    /// Configure (or reconfigure, resetting its state) smoothing for a channel.
+    pub fn set(&mut self, channel: Channel, smoothing: Smoothing) {
+        self.filters.retain(|(c, _)| *c != channel);
+        if smoothing != Smoothing::None {
+            self.filters.push((channel, ChannelFilter::new(smoothing)));
+        }
+    }
+
+    /// Return a copy of `report` with the configured channels smoothed.
+    pub fn apply(&mut self, report: &StepReport) -> StepReport {
+        let mut smoothed = report.clone();
+        for (channel, filter) in &mut self.filters {
+            let value = channel.value_mut(&mut smoothed);
+            *value = filter.filter(*value);
+        }
+        smoothed
+    }
+}
+
+/// Destination for per-step reports (logger, file, database, ...).
+///
+/// Implement this to plug a custom sink into the engine without modifying the crate.
+pub trait TelemetrySink {
+    /// Name used in error reports.
+    fn name(&self) -> &str {
+        "sink"
+    }
+
+    fn emit(&mut self, report: &StepReport) -> Result<(), BmsError>;
+}
+
+/// Registered sinks plus per-sink failure bookkeeping.
+///
+/// Every report is offered to every sink; a failing sink is counted and logged
+/// but never prevents the others from receiving the report.
+#[derive(Default)]
+pub struct SinkRegistry {
+    sinks: Vec<Box<dyn TelemetrySink>>,
+    failures: Vec<usize>,
+    last_error: Option<BmsError>,
+}
+
+impl SinkRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add(&mut self, sink: Box<dyn TelemetrySink>) {
+        self.sinks.push(sink);
+        self.failures.push(0);
+    }
+
+    pub fn len(&self) -> usize {
+        self.sinks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sinks.is_empty()
+    }
+
+    /// Send `report` to every sink, returning how many of them failed.
+    pub fn emit(&mut self, report: &StepReport) -> usize {
+        let mut failed = 0;
+        for (sink, failures) in self.sinks.iter_mut().zip(self.failures.iter_mut()) {
+            if let Err(err) = sink.emit(report) {
+                log::warn!("{}", err);
+                *failures += 1;
+                failed += 1;
+                self.last_error = Some(err);
+            }
+        }
+        failed
+    }
+
+    /// Failure count of each sink, in registration order.
+    pub fn failures(&self) -> &[usize] {
+        &self.failures
+    }
+
+    /// The most recent sink error, if any.
+    pub fn last_error(&self) -> Option<&BmsError> {
+        self.last_error.as_ref()
+    }
+}
+
+impl fmt::Debug for SinkRegistry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let names: Vec<&str> = self.sinks.iter().map(|s| s.name()).collect();
+        f.debug_struct("SinkRegistry")
+            .field("sinks", &names)
+            .field("failures", &self.failures)
+            .finish()
+    }
+}
+
+/// How per-step reports are written to a text stream.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OutputFormat {
+    /// Human-readable lines (the console's historical output).
+    #[default]
+    Text,
+    /// One JSON object per step (NDJSON).
+    Json,
+}
+
+impl OutputFormat {
+    /// Parse a format name (`text` or `json`).
+    pub fn parse(name: &str) -> Result<Self, BmsError> {
+        match name {
+            "text" => Ok(OutputFormat::Text),
+            "json" => Ok(OutputFormat::Json),
+            other => Err(BmsError::InvalidArgument {
+                argument: other.to_string(),
+                reason: "expected `text` or `json`",
+            }),
+        }
+    }
+
+    /// Pick the format from command-line arguments (`--format json` or `--format=json`).
+    ///
+    /// Defaults to text when the flag is absent.
+    pub fn from_args<I: IntoIterator<Item = String>>(args: I) -> Result<Self, BmsError> {
+        let mut args = args.into_iter();
+        let mut format = OutputFormat::default();
+        while let Some(arg) = args.next() {
+            if arg == "--format" {
+                let value = args.next().ok_or(BmsError::InvalidArgument {
+                    argument: arg,
+                    reason: "missing value",
+                })?;
+                format = Self::parse(&value)?;
+            } else if let Some(value) = arg.strip_prefix("--format=") {
+                format = Self::parse(value)?;
+            }
+        }
+        Ok(format)
+    }
+
+    /// Render one report. JSON output is a single line with no trailing newline.
+    pub fn format(self, step: usize, report: &StepReport) -> String {
+        match self {
+            OutputFormat::Text => format!(
+                "Step {}:\n  Fuel Cell -> Voltage: {:.2} V, Current: {:.2} A, Hydrogen Flow: {:.2e} mol/s, Temp: {:.2} °C\n  Battery   -> SoC: {:.2} %, Voltage: {:.2} V, Current: {:.2} A, Temp: {:.2} °C\n  Power     -> Fuel Cell: {:.1} W, Battery: {:.1} W, Compressor: {:.1} W, Net: {:.1} W",
+                step,
+                report.fuel_cell_voltage,
+                report.fuel_cell_current,
+                report.hydrogen_flow,
+                report.fuel_cell_temperature,
+                report.battery_soc,
+                report.battery_voltage,
+                report.battery_current,
+                report.battery_temperature,
+                report.fuel_cell_power,
+                report.battery_power,
+                report.compressor_power,
+                report.net_power,
+            ),
+            // StepReport only holds plain numbers and booleans, so serialization cannot fail.
+            OutputFormat::Json => serde_json::to_string(report).expect("StepReport is always serializable"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A report with every numeric channel set to `value`.
+    fn report_with(value: f64) -> StepReport {
+        StepReport {
+            time: 0.0,
+            fuel_cell_voltage: value,
+            fuel_cell_current: value,
+            fuel_cell_temperature: value,
+            hydrogen_flow: value,
+            membrane_hydration: value,
+            oxygen_concentration: value,
+            oxygen_excess_ratio: value,
+            air_stoichiometry: value,
+            manifold_pressure: value,
+            compressor_speed: value,
+            battery_soc: value,
+            battery_voltage: value,
+            battery_current: value,
+            battery_temperature: value,
+            fuel_cell_power: value,
+            battery_power: value,
+            compressor_power: value,
+            net_power: value,
+            oxygen_setpoint: value,
+            oxygen_measured: value,
+            air_setpoint: value,
+            air_measured: value,
+            cooling_setpoint: value,
+            cooling_measured: value,
+            charging_mode: false,
+            cooling_active: false,
+            derivatives: None,
+        }
+    }
+
+    fn variance(values: &[f64]) -> f64 {
+        let mean = values.iter().sum::<f64>() / values.len() as f64;
+        values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / values.len() as f64
+    }
+
+    #[test]
+    fn test_exponential_smoothing_reduces_variance() {
+        let true_value = 50.0;
+        let mut smoother = Smoother::new().with(Channel::FuelCellVoltage, Smoothing::Exponential { alpha: 0.1 });
+        // Deterministic pseudo-random noise in [-1, 1).
+        let mut seed: u64 = 12345;
+        let mut raw = Vec::new();
+        let mut smoothed = Vec::new();
+        for _ in 0..2000 {
+            seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            let noise = (seed >> 11) as f64 / (1u64 << 53) as f64 * 2.0 - 1.0;
+            let report = report_with(true_value + noise);
+            raw.push(report.fuel_cell_voltage);
+            smoothed.push(smoother.apply(&report).fuel_cell_voltage);
+        }
+        // Skip the start-up transient.
+        let smoothed = &smoothed[100..];
+        let raw = &raw[100..];
+        assert!(variance(smoothed) < variance(raw) / 5.0);
+        let mean = smoothed.iter().sum::<f64>() / smoothed.len() as f64;
+        assert!((mean - true_value).abs() < 0.05);
+    }
+
+    #[test]
+    fn test_unconfigured_channels_pass_through() {
+        let mut smoother = Smoother::new().with(Channel::BatterySoc, Smoothing::MovingAverage { window: 2 });
+        smoother.apply(&report_with(0.0));
+        let out = smoother.apply(&report_with(10.0));
+        assert_eq!(out.battery_soc, 5.0);
+        assert_eq!(out.fuel_cell_voltage, 10.0);
+    }
+
+    fn args(list: &[&str]) -> Vec<String> {
+        list.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_output_format_selection() {
+        assert_eq!(OutputFormat::from_args(args(&["main_console"])), Ok(OutputFormat::Text));
+        assert_eq!(OutputFormat::from_args(args(&["main_console", "--format", "json"])), Ok(OutputFormat::Json));
+        assert_eq!(OutputFormat::from_args(args(&["main_console", "--format=text"])), Ok(OutputFormat::Text));
+        assert!(OutputFormat::from_args(args(&["main_console", "--format", "xml"])).is_err());
+        assert!(OutputFormat::from_args(args(&["main_console", "--format"])).is_err());
+    }
+
+    #[test]
+    fn test_json_output_is_one_object_per_step() {
+        let output: Vec<String> = (0..3)
+            .map(|step| OutputFormat::Json.format(step, &report_with(step as f64)))
+            .collect();
+        let ndjson = output.join("\n");
+        let lines: Vec<&str> = ndjson.lines().collect();
+        assert_eq!(lines.len(), 3);
+        for (step, line) in lines.iter().enumerate() {
+            let value: serde_json::Value = serde_json::from_str(line).unwrap();
+            assert!(value.is_object());
+            assert_eq!(value["battery_soc"], serde_json::json!(step as f64));
+        }
+    }
+
+    #[test]
+    fn test_text_output_shows_hydrogen_flow_in_mol_per_second() {
+        let report = StepReport { hydrogen_flow: 0.0042, ..report_with(1.0) };
+        assert!(OutputFormat::Text.format(0, &report).contains("Hydrogen Flow: 4.20e-3 mol/s"));
+    }
+}
@@ -0,0 +1,121 @@
+use crate::engine::{Measurements, SimulationEngine};
+use crate::telemetry::StepReport;
+
+/// One sample of a reference trace: the applied load and the observed response.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ReferencePoint {
+    /// Sample time (s). Informational; samples are replayed one engine step each.
+    pub time: f64,
+    /// Stack load applied at this sample (A).
+    pub load: f64,
+    /// Observed stack voltage (V).
+    pub voltage: f64,
+    /// Observed stack current (A).
+    pub current: f64,
+    /// Observed stack temperature (°C).
+    pub temperature: f64,
+}
+
+impl ReferencePoint {
+    /// Reference sample recorded from a step report.
+    pub fn from_report(report: &StepReport, load: f64) -> Self {
+        Self {
+            time: report.time,
+            load,
+            voltage: report.fuel_cell_voltage,
+            current: report.fuel_cell_current,
+            temperature: report.fuel_cell_temperature,
+        }
+    }
+}
+
+/// Error statistics of one channel against the reference.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct ChannelError {
+    pub rmse: f64,
+    pub max_error: f64,
+}
+
+impl ChannelError {
+    fn from_errors(errors: &[f64]) -> Self {
+        if errors.is_empty() {
+            return Self::default();
+        }
+        let mean_square = errors.iter().map(|e| e * e).sum::<f64>() / errors.len() as f64;
+        Self {
+            rmse: mean_square.sqrt(),
+            max_error: errors.iter().fold(0.0, |max, e| max.max(e.abs())),
+        }
+    }
+}
+
+/// Per-channel model error over a reference trace.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct ValidationReport {
+    pub samples: usize,
+    pub voltage: ChannelError,
+    pub current: ChannelError,
+    pub temperature: ChannelError,
+}
+
+impl SimulationEngine {
+    /// Replay the reference loads through the model and compare its response.
+    ///
+    /// Each reference point drives one step with the recorded load as a
+    /// measured input; no observer correction is applied, so the errors
+    /// reflect the open-loop model.
+    pub fn validate_against(&mut self, reference: &[ReferencePoint]) -> ValidationReport {
+        let mut voltage = Vec::with_capacity(reference.len());
+        let mut current = Vec::with_capacity(reference.len());
+        let mut temperature = Vec::with_capacity(reference.len());
+        for point in reference {
+            let report = self.update_with_measurements(&Measurements { load: Some(point.load), ..Measurements::default() });
+            voltage.push(report.fuel_cell_voltage - point.voltage);
+            current.push(report.fuel_cell_current - point.current);
+            temperature.push(report.fuel_cell_temperature - point.temperature);
+        }
+        ValidationReport {
+            samples: reference.len(),
+            voltage: ChannelError::from_errors(&voltage),
+            current: ChannelError::from_errors(&current),
+            temperature: ChannelError::from_errors(&temperature),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scenario::Scenario;
+
+    fn reference_trace() -> Vec<ReferencePoint> {
+        let mut engine = SimulationEngine::new(Scenario::default());
+        (0..40)
+            .map(|k| {
+                let load = if k < 20 { 5.0 } else { 15.0 };
+                let report = engine.update_with_measurements(&Measurements { load: Some(load), ..Measurements::default() });
+                ReferencePoint::from_report(&report, load)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_self_generated_reference_validates_with_zero_error() {
+        let reference = reference_trace();
+        let report = SimulationEngine::new(Scenario::default()).validate_against(&reference);
+        assert_eq!(report.samples, 40);
+        for channel in [report.voltage, report.current, report.temperature] {
+            assert!(channel.rmse < 1e-9 && channel.max_error < 1e-9, "{:?}", channel);
+        }
+    }
+
+    #[test]
+    fn test_mismatched_model_reports_error() {
+        let reference = reference_trace();
+        let mut engine = SimulationEngine::new(Scenario::default());
+        engine.fuel_cell.r_internal *= 2.0;
+        let report = engine.validate_against(&reference);
+        assert!(report.voltage.rmse > 0.1);
+        assert!(report.voltage.max_error >= report.voltage.rmse);
+    }
+}